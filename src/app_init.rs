@@ -14,4 +14,4 @@ pub fn app_init() -> eframe::Result {
             Ok(Box::from(FileKrakenApp::new()))
         }),
     )
-}
\ No newline at end of file
+}
@@ -0,0 +1,990 @@
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use file_kraken::processing::duplicate_folders::{
+    find_duplicate_folders, resolve_duplicate_folder, DuplicateFolder,
+};
+use file_kraken::processing::empty_folders::{find_empty_folders, remove_empty_folders};
+use file_kraken::processing::file_types::find_extension_mismatches;
+use file_kraken::processing::find_duplicates::find_file_duplicates;
+use file_kraken::processing::large_files::{find_large_files, LargeFilesOrder};
+use file_kraken::processing::resolve::resolve_duplicate;
+use file_kraken::processing::scan::scan_location_files;
+use file_kraken::processing::statistics::get_file_statistics;
+use file_kraken::processing::undo::undo_last_operation;
+use file_kraken::state::deletion::DeletionMode;
+use file_kraken::state::duplicate::{DuplicateResolution, FileKrakenDuplicateType};
+use file_kraken::state::file::FileKrakenFile;
+use file_kraken::state::keeper_rule::KeeperRule;
+use file_kraken::state::location::{FileKrakenLocationState, FileKrakenLocationType};
+use file_kraken::utils::hashing::HashAlgorithm;
+use file_kraken::utils::image_hash::ImageHashAlgorithm;
+use file_kraken::utils::{format_file_size, format_timestamp, parse_file_size};
+use file_kraken::{AppState, FileKrakenError};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+/// Returned by `dupes` and `dupe-folders` when at least one duplicate group was found
+pub const EXIT_DUPLICATES_FOUND: i32 = 3;
+
+/// Run File Kraken without the GUI.
+///
+/// Exit codes: 0 on success, 1 on errors, 2 on invalid usage and
+/// 3 if `dupes` or `dupe-folders` found duplicates.
+#[derive(Parser)]
+#[command(
+    name = "file-kraken",
+    version,
+    override_usage = "file-kraken --project <PROJECT> <COMMAND>"
+)]
+struct Cli {
+    /// Project file to operate on, before or after the command
+    #[arg(long, short, global = true)]
+    project: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Scan locations for files
+    Scan {
+        /// Only scan this location instead of all non-excluded locations
+        location: Option<String>,
+    },
+    /// Find duplicate files and list them
+    Dupes,
+    /// Delete all duplicates that are eligible for deletion, or replace them with hard
+    /// links, depending on the project's resolution
+    Delete {
+        /// Only list the files that would be deleted or linked
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Find duplicate files, then list folders whose files and subfolders have the same
+    /// content and folders sharing much of their content
+    DupeFolders {
+        /// Only compare contents, not the names of files and subfolders
+        #[arg(long)]
+        ignore_names: bool,
+        /// List pairs of folders sharing at least this percentage of their content
+        #[arg(long, default_value_t = 50.0)]
+        min_similarity: f64,
+        /// Resolve the files of all deletable folders using the project's resolution,
+        /// deleted folders are removed
+        #[arg(long)]
+        resolve: bool,
+    },
+    /// List folders without files, or remove them
+    EmptyFolders {
+        /// Treat folders that only contain files like .DS_Store or Thumbs.db as empty,
+        /// removing deletes these files
+        #[arg(long)]
+        ignore_junk: bool,
+        /// Remove all listed folders, deepest first
+        #[arg(long)]
+        remove: bool,
+    },
+    /// List the largest files of the project
+    LargeFiles {
+        /// Only list files of at least this size, e.g. 500M or 2G
+        #[arg(long, default_value = "100M", value_parser = parse_size)]
+        min_size: u64,
+        /// Only list files of these locations instead of all locations
+        #[arg(long)]
+        location: Vec<PathBuf>,
+        /// size, age or location
+        #[arg(long, default_value = "size")]
+        sort: LargeFilesOrder,
+        /// Maximum number of files to list
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// List files whose content does not match their extension, e.g. a PNG image named .jpg
+    TypeMismatches,
+    /// Print a summary of the project
+    Report {
+        /// Also search for duplicates and include them in the summary
+        #[arg(long)]
+        duplicates: bool,
+    },
+    /// Print the number and size of the files per category, extension, content type, location
+    /// and age
+    Stats {
+        /// Print CSV with the columns group, label, files and bytes instead
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Manage the locations of the project
+    #[command(subcommand)]
+    Location(LocationCommand),
+    /// Manage patterns of files and folders skipped while scanning
+    #[command(subcommand)]
+    Ignore(IgnoreCommand),
+    /// Show or change the hash algorithm used to compare file contents
+    HashAlgorithm {
+        /// sha256, blake3 or xxh3
+        algorithm: Option<HashAlgorithm>,
+    },
+    /// Show or change whether scanning reads the members of zip and tar archives, to find
+    /// duplicates between archive members and files
+    ScanArchives {
+        /// true or false, false forgets all members read so far
+        enabled: Option<bool>,
+    },
+    /// Show or change whether `dupes` also groups JPEG and PNG images that look alike
+    SimilarImages {
+        /// true or false
+        enabled: Option<bool>,
+        /// Perceptual hash to compare images with: ahash, dhash or phash
+        #[arg(long)]
+        algorithm: Option<ImageHashAlgorithm>,
+        /// Number of the 64 hash bits similar images may differ in
+        #[arg(long)]
+        threshold: Option<u32>,
+    },
+    /// Show or change what happens to deleted files
+    DeletionMode {
+        /// permanent, trash or quarantine
+        mode: Option<DeletionMode>,
+    },
+    /// Show or change how duplicates are resolved by `delete`
+    Resolution {
+        /// delete, hardlink or reflink
+        resolution: Option<DuplicateResolution>,
+    },
+    /// Manage the rules choosing which file of a duplicate group is kept
+    #[command(subcommand)]
+    KeeperRules(KeeperRuleCommand),
+    /// Manage files moved into the quarantine directory
+    #[command(subcommand)]
+    Quarantine(QuarantineCommand),
+    /// List the latest destructive operations
+    Journal {
+        /// Number of operations to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Also list the files of each operation
+        #[arg(long)]
+        files: bool,
+    },
+    /// Revert the latest operation that was not undone yet
+    Undo,
+}
+
+#[derive(Subcommand)]
+enum LocationCommand {
+    /// List all locations
+    List,
+    /// Add a new location
+    Add {
+        path: PathBuf,
+        /// normal, preferred or excluded
+        #[arg(long = "type", default_value = "normal")]
+        location_type: FileKrakenLocationType,
+    },
+    /// Remove a location and all its files from the project
+    Remove { path: PathBuf },
+    /// Change the type of a location
+    SetType {
+        path: PathBuf,
+        /// normal, preferred or excluded
+        location_type: FileKrakenLocationType,
+    },
+    /// Change the priority of a location, duplicates in higher ones are kept
+    SetPriority {
+        path: PathBuf,
+        #[arg(allow_negative_numbers = true)]
+        priority: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum IgnoreCommand {
+    /// List the ignore patterns of the project or a location
+    List {
+        #[arg(long)]
+        location: Option<PathBuf>,
+    },
+    /// Add a glob like `*.tmp`, `**/node_modules` or `.git/`, or a regex as `re:<regex>`
+    Add {
+        pattern: String,
+        /// Only apply the pattern to this location instead of the whole project
+        #[arg(long)]
+        location: Option<PathBuf>,
+    },
+    /// Remove an ignore pattern
+    Remove {
+        pattern: String,
+        #[arg(long)]
+        location: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeeperRuleCommand {
+    /// List the rules in the order they are applied
+    List,
+    /// Append a rule: preferred_location, oldest_created, shortest_path, deepest_folder or
+    /// `path_matches:<regex>`
+    Add { rule: KeeperRule },
+    /// Remove the rule at a position of the list, starting at 1
+    Remove { position: usize },
+    /// Replace all rules, no rules make every duplicate group ineligible for deletion
+    Set { rules: Vec<KeeperRule> },
+}
+
+#[derive(Subcommand)]
+enum QuarantineCommand {
+    /// List quarantined files, newest first
+    List,
+    /// Move quarantined files back to their original paths
+    Restore {
+        /// Ids as shown by `quarantine list`
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Permanently delete quarantined files
+    Purge {
+        /// Only delete files quarantined at least this many days ago
+        #[arg(long, default_value_t = 0)]
+        older_than_days: u64,
+    },
+    /// Show or change the quarantine directory
+    Dir { path: Option<PathBuf> },
+}
+
+/// Parse the command line, run the command and return the process exit code
+pub fn run() -> i32 {
+    let cli = Cli::parse();
+    // global arguments can't be required by clap itself
+    let Some(project) = cli.project else {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "the following required arguments were not provided:\n  --project <PROJECT>",
+            )
+            .exit();
+    };
+
+    let creates_project = matches!(cli.command, Command::Location(LocationCommand::Add { .. }));
+    if !creates_project && !project.exists() {
+        eprintln!("Project file {} does not exist", project.display());
+        return EXIT_ERROR;
+    }
+
+    let app_state = Arc::new(AppState::default());
+    let last_progress = Mutex::new(None::<Instant>);
+    app_state.set_progress_listener(move |message| {
+        // long searches report once per file size, so keep stderr readable
+        let mut last_progress = last_progress.lock().unwrap();
+        if last_progress.is_none_or(|x| x.elapsed() >= Duration::from_secs(1)) {
+            eprintln!("{}", message);
+            *last_progress = Some(Instant::now());
+        }
+    });
+
+    let result = app_state
+        .connect_sqlite(&project.to_string_lossy())
+        .and_then(|_| run_command(&app_state, cli.command));
+    match result {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn run_command(app_state: &Arc<AppState>, command: Command) -> Result<i32, FileKrakenError> {
+    match command {
+        Command::Scan { location } => scan(app_state, location),
+        Command::Dupes => dupes(app_state),
+        Command::Delete { dry_run } => delete(app_state, dry_run),
+        Command::DupeFolders {
+            ignore_names,
+            min_similarity,
+            resolve,
+        } => dupe_folders(app_state, !ignore_names, min_similarity, resolve),
+        Command::EmptyFolders {
+            ignore_junk,
+            remove,
+        } => empty_folders(app_state, ignore_junk, remove),
+        Command::LargeFiles {
+            min_size,
+            location,
+            sort,
+            limit,
+        } => large_files(app_state, min_size, location, sort, limit),
+        Command::TypeMismatches => type_mismatches(app_state),
+        Command::Report { duplicates } => report(app_state, duplicates),
+        Command::Stats { csv } => stats(app_state, csv),
+        Command::Location(command) => location(app_state, command),
+        Command::Ignore(command) => ignore(app_state, command),
+        Command::HashAlgorithm { algorithm } => hash_algorithm(app_state, algorithm),
+        Command::ScanArchives { enabled } => scan_archives(app_state, enabled),
+        Command::SimilarImages {
+            enabled,
+            algorithm,
+            threshold,
+        } => similar_images(app_state, enabled, algorithm, threshold),
+        Command::DeletionMode { mode } => deletion_mode(app_state, mode),
+        Command::Resolution { resolution } => duplicate_resolution(app_state, resolution),
+        Command::KeeperRules(command) => keeper_rules(app_state, command),
+        Command::Quarantine(command) => quarantine(app_state, command),
+        Command::Journal { limit, files } => journal(app_state, limit, files),
+        Command::Undo => undo(app_state),
+    }
+}
+
+fn scan(app_state: &Arc<AppState>, location: Option<String>) -> Result<i32, FileKrakenError> {
+    let location_paths: Vec<String> = match location {
+        Some(location) => vec![location_path(&location)],
+        None => app_state
+            .get_locations_list_readonly()
+            .iter()
+            .filter(|x| x.location_type != FileKrakenLocationType::Excluded)
+            .map(|x| x.path.clone())
+            .collect(),
+    };
+
+    let mut exit_code = EXIT_OK;
+    for location_path in location_paths {
+        eprintln!("Scanning {} ...", location_path);
+        let report = scan_location_files(app_state.clone(), &location_path)?;
+        println!(
+            "{}: {} files ({} new, {} changed), {} removed, {} failed",
+            location_path,
+            report.nr_files,
+            report.nr_added,
+            report.nr_modified,
+            report.nr_removed,
+            report.failed_paths.len()
+        );
+        if report.nr_archives_read > 0 {
+            println!(
+                "{}: {} archives read",
+                location_path, report.nr_archives_read
+            );
+        }
+        for failed_path in &report.failed_paths {
+            eprintln!("Failed to read {}", failed_path);
+            exit_code = EXIT_ERROR;
+        }
+    }
+    Ok(exit_code)
+}
+
+fn dupes(app_state: &Arc<AppState>) -> Result<i32, FileKrakenError> {
+    find_file_duplicates(app_state.clone())?;
+
+    let duplicates = app_state
+        .find_duplicates_processing
+        .duplicates
+        .read()
+        .unwrap();
+    for duplicate in duplicates.iter() {
+        if duplicate.duplicate_type == FileKrakenDuplicateType::ArchiveContent {
+            println!("archives with the same members:");
+        } else if duplicate.duplicate_type == FileKrakenDuplicateType::SimilarImage {
+            println!("similar images:");
+        }
+        for file in &duplicate.other_files {
+            println!("keep    {}{}", file.path, links_suffix(file));
+        }
+        for file in &duplicate.deletable_files {
+            println!("delete  {}{}", file.path, links_suffix(file));
+        }
+        println!();
+    }
+    let nr_eligible: usize = duplicates.iter().map(|x| x.deletable_files.len()).sum();
+    println!(
+        "{} duplicate groups, {} eligible for deletion",
+        duplicates.len(),
+        nr_eligible
+    );
+
+    Ok(if duplicates.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_DUPLICATES_FOUND
+    })
+}
+
+/// Hint at other hard links to a file, which keep its data alive
+fn links_suffix(file: &FileKrakenFile) -> String {
+    match file.nr_links {
+        Some(nr_links) if nr_links > 1 => format!("  ({} links)", nr_links),
+        _ => String::new(),
+    }
+}
+
+fn delete(app_state: &Arc<AppState>, dry_run: bool) -> Result<i32, FileKrakenError> {
+    find_file_duplicates(app_state.clone())?;
+
+    let duplicates = app_state
+        .find_duplicates_processing
+        .duplicates
+        .read()
+        .unwrap()
+        .clone();
+    let resolution = app_state.get_duplicate_resolution()?;
+    let (verb, past_verb, description) = match resolution {
+        DuplicateResolution::Delete => ("delete", "deleted", "Delete all eligible duplicates"),
+        DuplicateResolution::Hardlink => (
+            "hardlink",
+            "hardlinked",
+            "Replace all eligible duplicates with hard links",
+        ),
+        DuplicateResolution::Reflink => (
+            "reflink",
+            "reflinked",
+            "Deduplicate the extents of all eligible duplicates",
+        ),
+    };
+    let mut exit_code = EXIT_OK;
+    let mut nr_resolved = 0;
+    let mut operation_id = None;
+    for (duplicate, file) in duplicates
+        .iter()
+        .flat_map(|x| x.deletable_files.iter().map(move |file| (x, file)))
+    {
+        if dry_run {
+            println!("would {}  {}", verb, file.path);
+            continue;
+        }
+        let operation_id = match operation_id {
+            Some(operation_id) => operation_id,
+            None => *operation_id.insert(app_state.begin_operation(description)?),
+        };
+        match resolve_duplicate(app_state, duplicate, file, operation_id) {
+            Ok(_) => {
+                println!("{}  {}", past_verb, file.path);
+                nr_resolved += 1;
+            }
+            Err(err) => {
+                eprintln!("Failed to {} {}: {}", verb, file.path, err);
+                exit_code = EXIT_ERROR;
+            }
+        }
+    }
+    if !dry_run {
+        println!("{} duplicates {}", nr_resolved, past_verb);
+        if resolution != DuplicateResolution::Delete {
+            println!(
+                "{} reclaimed",
+                format_file_size(
+                    *app_state
+                        .find_duplicates_processing
+                        .bytes_reclaimed
+                        .read()
+                        .unwrap()
+                )
+            );
+        }
+    }
+    Ok(exit_code)
+}
+
+fn dupe_folders(
+    app_state: &Arc<AppState>,
+    compare_names: bool,
+    min_similarity: f64,
+    resolve: bool,
+) -> Result<i32, FileKrakenError> {
+    // folders are compared by the hashes of their files
+    find_file_duplicates(app_state.clone())?;
+    let duplicate_folders = find_duplicate_folders(app_state, compare_names, min_similarity)?;
+
+    let folder_suffix = |folder: &DuplicateFolder| {
+        format!(
+            "  ({} files, {})",
+            folder.nr_files,
+            format_file_size(folder.size)
+        )
+    };
+    for group in duplicate_folders.groups.iter() {
+        for folder in group.kept_folders.iter() {
+            println!("keep    {}{}", folder.path, folder_suffix(folder));
+        }
+        for folder in group.deletable_folders.iter() {
+            println!("delete  {}{}", folder.path, folder_suffix(folder));
+        }
+        println!();
+    }
+    for similar in duplicate_folders.similar_folders.iter() {
+        println!(
+            "similar {:>3.0}%  {}  {}",
+            similar.similarity(),
+            similar.folder_a.path,
+            similar.folder_b.path
+        );
+    }
+    let nr_eligible: usize = duplicate_folders
+        .groups
+        .iter()
+        .map(|x| x.deletable_folders.len())
+        .sum();
+    println!(
+        "{} duplicate folder groups, {} eligible for deletion, {} similar folders",
+        duplicate_folders.groups.len(),
+        nr_eligible,
+        duplicate_folders.similar_folders.len()
+    );
+
+    if resolve && nr_eligible > 0 {
+        let operation_id = app_state.begin_operation("Resolve duplicate folders")?;
+        let mut exit_code = EXIT_OK;
+        for group in duplicate_folders.groups.iter() {
+            for (path, err) in resolve_duplicate_folder(app_state, group, operation_id)? {
+                eprintln!("Failed to resolve {}: {}", path, err);
+                exit_code = EXIT_ERROR;
+            }
+        }
+        return Ok(exit_code);
+    }
+    Ok(if duplicate_folders.groups.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_DUPLICATES_FOUND
+    })
+}
+
+fn empty_folders(
+    app_state: &Arc<AppState>,
+    ignore_junk: bool,
+    remove: bool,
+) -> Result<i32, FileKrakenError> {
+    let empty_folders = find_empty_folders(app_state, ignore_junk)?;
+    if !remove {
+        for folder in empty_folders.iter() {
+            println!("{}", folder.path);
+        }
+        println!("{} empty folders", empty_folders.len());
+        return Ok(EXIT_OK);
+    }
+    if empty_folders.is_empty() {
+        println!("0 empty folders removed");
+        return Ok(EXIT_OK);
+    }
+
+    let operation_id = app_state.begin_operation("Remove empty folders")?;
+    let failed = remove_empty_folders(app_state, &empty_folders, operation_id);
+    for (path, err) in failed.iter() {
+        eprintln!("Failed to remove {}: {}", path, err);
+    }
+    println!(
+        "{} empty folders removed",
+        empty_folders.len() - failed.len()
+    );
+    Ok(if failed.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_ERROR
+    })
+}
+
+fn large_files(
+    app_state: &Arc<AppState>,
+    min_size: u64,
+    locations: Vec<PathBuf>,
+    order: LargeFilesOrder,
+    limit: usize,
+) -> Result<i32, FileKrakenError> {
+    let location_paths: Vec<String> = locations.iter().map(location_path).collect();
+    for large_file in find_large_files(app_state, min_size, &location_paths, order, limit)? {
+        println!(
+            "{:>12}  {}  {}{}",
+            format_file_size(large_file.file.file_len),
+            format_timestamp(large_file.file.time_modified),
+            large_file.file.path,
+            links_suffix(&large_file.file)
+        );
+    }
+    Ok(EXIT_OK)
+}
+
+fn type_mismatches(app_state: &Arc<AppState>) -> Result<i32, FileKrakenError> {
+    for file in find_extension_mismatches(app_state)? {
+        println!(
+            "{:<28} {}",
+            file.mime_type.as_deref().unwrap_or_default(),
+            file.path
+        );
+    }
+    Ok(EXIT_OK)
+}
+
+fn report(app_state: &Arc<AppState>, with_duplicates: bool) -> Result<i32, FileKrakenError> {
+    let mut total_files = 0;
+    println!("Locations:");
+    for location in app_state.get_locations_list_readonly().iter() {
+        let nr_files = app_state
+            .get_files_by_location(&location.path)
+            .map(|files| files.read().unwrap().len())
+            .unwrap_or_default();
+        total_files += nr_files;
+        println!(
+            "  {:<10} {:<16} {:>10} files {:>12}  {}",
+            location.location_type.to_string(),
+            location.location_state.to_string(),
+            nr_files,
+            format_file_size(app_state.get_disk_usage(Some(&location.path))?),
+            location.path
+        );
+    }
+    // hard links between locations are counted once in the total
+    println!(
+        "Total: {} files, {}",
+        total_files,
+        format_file_size(app_state.get_disk_usage(None)?)
+    );
+
+    if with_duplicates {
+        find_file_duplicates(app_state.clone())?;
+        let duplicates = app_state
+            .find_duplicates_processing
+            .duplicates
+            .read()
+            .unwrap();
+        let eligible = duplicates.iter().flat_map(|x| x.deletable_files.iter());
+        println!(
+            "Duplicates: {} groups, {} eligible for deletion ({})",
+            duplicates.len(),
+            eligible.clone().count(),
+            format_file_size(eligible.map(|x| x.file_len).sum())
+        );
+    }
+    Ok(EXIT_OK)
+}
+
+fn stats(app_state: &Arc<AppState>, csv: bool) -> Result<i32, FileKrakenError> {
+    let statistics = get_file_statistics(app_state)?;
+    if csv {
+        print!("{}", statistics.to_csv());
+        return Ok(EXIT_OK);
+    }
+
+    println!(
+        "Total: {} files, {}",
+        statistics.nr_files,
+        format_file_size(statistics.size)
+    );
+    let groups = [
+        (
+            "Categories",
+            statistics
+                .by_category
+                .iter()
+                .map(|(_, x)| x)
+                .collect::<Vec<_>>(),
+        ),
+        (
+            "Extensions",
+            statistics.by_extension.iter().take(20).collect(),
+        ),
+        ("Content types", statistics.by_mime_type.iter().collect()),
+        ("Locations", statistics.by_location.iter().collect()),
+        ("Last modified", statistics.by_age.iter().collect()),
+    ];
+    for (title, rows) in groups {
+        println!("{}:", title);
+        for row in rows {
+            println!(
+                "  {:<24} {:>10} files {:>12}",
+                if row.label.is_empty() {
+                    "(none)"
+                } else {
+                    &row.label
+                },
+                row.nr_files,
+                format_file_size(row.size)
+            );
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn location(app_state: &Arc<AppState>, command: LocationCommand) -> Result<i32, FileKrakenError> {
+    match command {
+        LocationCommand::List => {
+            let mut locations = app_state.get_locations_list_readonly().clone();
+            locations.sort_by_key(|x| std::cmp::Reverse(x.priority));
+            for location in locations.iter() {
+                println!(
+                    "{:>4} {:<10} {:<16} {}",
+                    location.priority,
+                    location.location_type.to_string(),
+                    location.location_state.to_string(),
+                    location.path
+                );
+            }
+        }
+        LocationCommand::Add {
+            path,
+            location_type,
+        } => {
+            let path = std::fs::canonicalize(&path)
+                .map_err(|err| FileKrakenError::io(&path.to_string_lossy(), err))?;
+            app_state.add_location(
+                true,
+                &path.to_string_lossy(),
+                &location_type,
+                &FileKrakenLocationState::Unscanned,
+            )?;
+        }
+        LocationCommand::Remove { path } => {
+            app_state.remove_location(true, &location_path(&path))?;
+        }
+        LocationCommand::SetType {
+            path,
+            location_type,
+        } => {
+            app_state.modify_location_type(&location_path(&path), location_type)?;
+        }
+        LocationCommand::SetPriority { path, priority } => {
+            app_state.set_location_priority(true, &location_path(&path), priority)?;
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn ignore(app_state: &Arc<AppState>, command: IgnoreCommand) -> Result<i32, FileKrakenError> {
+    match command {
+        IgnoreCommand::List { location } => {
+            let location = location.map(location_path);
+            for pattern in app_state.get_ignore_patterns(location.as_deref())? {
+                println!("{}", pattern);
+            }
+        }
+        IgnoreCommand::Add { pattern, location } => {
+            let location = location.map(location_path);
+            app_state.add_ignore_pattern(location.as_deref(), &pattern)?;
+        }
+        IgnoreCommand::Remove { pattern, location } => {
+            let location = location.map(location_path);
+            app_state.remove_ignore_pattern(location.as_deref(), &pattern)?;
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn hash_algorithm(
+    app_state: &Arc<AppState>,
+    algorithm: Option<HashAlgorithm>,
+) -> Result<i32, FileKrakenError> {
+    match algorithm {
+        Some(algorithm) => app_state.set_hash_algorithm(algorithm)?,
+        None => println!("{}", app_state.get_hash_algorithm()?),
+    }
+    Ok(EXIT_OK)
+}
+
+fn scan_archives(app_state: &Arc<AppState>, enabled: Option<bool>) -> Result<i32, FileKrakenError> {
+    match enabled {
+        Some(enabled) => app_state.set_scan_archives(enabled)?,
+        None => println!("{}", app_state.get_scan_archives()?),
+    }
+    Ok(EXIT_OK)
+}
+
+fn similar_images(
+    app_state: &Arc<AppState>,
+    enabled: Option<bool>,
+    algorithm: Option<ImageHashAlgorithm>,
+    threshold: Option<u32>,
+) -> Result<i32, FileKrakenError> {
+    if enabled.is_none() && algorithm.is_none() && threshold.is_none() {
+        println!("enabled    {}", app_state.get_similar_images()?);
+        println!("algorithm  {}", app_state.get_image_hash_algorithm()?);
+        println!("threshold  {}", app_state.get_similar_image_threshold()?);
+    }
+    if let Some(enabled) = enabled {
+        app_state.set_similar_images(enabled)?;
+    }
+    if let Some(algorithm) = algorithm {
+        app_state.set_image_hash_algorithm(algorithm)?;
+    }
+    if let Some(threshold) = threshold {
+        app_state.set_similar_image_threshold(threshold)?;
+    }
+    Ok(EXIT_OK)
+}
+
+fn deletion_mode(
+    app_state: &Arc<AppState>,
+    mode: Option<DeletionMode>,
+) -> Result<i32, FileKrakenError> {
+    match mode {
+        Some(mode) => app_state.set_deletion_mode(mode)?,
+        None => println!("{}", app_state.get_deletion_mode()?),
+    }
+    Ok(EXIT_OK)
+}
+
+fn duplicate_resolution(
+    app_state: &Arc<AppState>,
+    resolution: Option<DuplicateResolution>,
+) -> Result<i32, FileKrakenError> {
+    match resolution {
+        Some(resolution) => app_state.set_duplicate_resolution(resolution)?,
+        None => println!("{}", app_state.get_duplicate_resolution()?),
+    }
+    Ok(EXIT_OK)
+}
+
+fn keeper_rules(
+    app_state: &Arc<AppState>,
+    command: KeeperRuleCommand,
+) -> Result<i32, FileKrakenError> {
+    let mut rules = app_state.get_keeper_rules()?;
+    match command {
+        KeeperRuleCommand::List => {
+            for (i, rule) in rules.iter().enumerate() {
+                println!(
+                    "{:<3} {:<40} {}",
+                    i + 1,
+                    rule.to_string(),
+                    rule.description()
+                );
+            }
+            return Ok(EXIT_OK);
+        }
+        KeeperRuleCommand::Add { rule } => rules.push(rule),
+        KeeperRuleCommand::Remove { position } => {
+            if position == 0 || position > rules.len() {
+                eprintln!("There is no rule at position {}", position);
+                return Ok(EXIT_ERROR);
+            }
+            rules.remove(position - 1);
+        }
+        KeeperRuleCommand::Set { rules: new_rules } => rules = new_rules,
+    }
+    app_state.set_keeper_rules(&rules)?;
+    Ok(EXIT_OK)
+}
+
+fn quarantine(
+    app_state: &Arc<AppState>,
+    command: QuarantineCommand,
+) -> Result<i32, FileKrakenError> {
+    match command {
+        QuarantineCommand::List => {
+            for file in app_state.get_quarantined_files()? {
+                println!(
+                    "{:<6} {:>10}  {}",
+                    file.id,
+                    format_file_size(file.file_len),
+                    file.original_path
+                );
+            }
+        }
+        QuarantineCommand::Restore { ids } => {
+            let mut exit_code = EXIT_OK;
+            for id in ids {
+                if let Err(err) = app_state.restore_quarantined_file(id) {
+                    eprintln!("Failed to restore {}: {}", id, err);
+                    exit_code = EXIT_ERROR;
+                }
+            }
+            return Ok(exit_code);
+        }
+        QuarantineCommand::Purge { older_than_days } => {
+            let nr_purged = app_state.purge_quarantine(older_than_days)?;
+            println!("{} quarantined files deleted", nr_purged);
+        }
+        QuarantineCommand::Dir { path } => match path {
+            Some(path) => {
+                let path = std::path::absolute(&path)
+                    .map_err(|err| FileKrakenError::io(&path.to_string_lossy(), err))?;
+                app_state.set_quarantine_dir(&path.to_string_lossy())?
+            }
+            None => println!("{}", app_state.get_quarantine_dir()?),
+        },
+    }
+    Ok(EXIT_OK)
+}
+
+fn journal(app_state: &Arc<AppState>, limit: usize, files: bool) -> Result<i32, FileKrakenError> {
+    for operation in app_state.get_operations(limit)? {
+        println!(
+            "{:<6} {:<6} {}",
+            operation.id,
+            if operation.undone { "undone" } else { "" },
+            operation.description
+        );
+        if files {
+            for entry in app_state.get_journal_entries(operation.id)? {
+                println!(
+                    "         {:<16} {}{}",
+                    entry.action.to_string(),
+                    entry.original_path,
+                    entry
+                        .destination
+                        .map(|x| format!(" -> {}", x))
+                        .unwrap_or_default()
+                );
+            }
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn undo(app_state: &Arc<AppState>) -> Result<i32, FileKrakenError> {
+    let Some(report) = undo_last_operation(app_state)? else {
+        println!("Nothing to undo");
+        return Ok(EXIT_OK);
+    };
+    if report.undone {
+        println!(
+            "Undid \"{}\": {} reverted",
+            report.operation.description, report.nr_reverted
+        );
+    } else {
+        println!(
+            "Could not undo \"{}\", nothing could be reverted",
+            report.operation.description
+        );
+    }
+    for reason in report.not_reverted.iter() {
+        eprintln!("Not reverted: {}", reason);
+    }
+    Ok(if report.not_reverted.is_empty() {
+        EXIT_OK
+    } else {
+        EXIT_ERROR
+    })
+}
+
+/// Parse a file size given on the command line, e.g. `500M`
+fn parse_size(size: &str) -> Result<u64, String> {
+    parse_file_size(size).ok_or_else(|| format!("invalid size {}", size))
+}
+
+/// Resolve a location given on the command line to the path stored in the project
+fn location_path(path: impl AsRef<Path>) -> String {
+    std::fs::canonicalize(path.as_ref())
+        .unwrap_or_else(|_| path.as_ref().to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_is_accepted_before_and_after_the_command() {
+        for args in [
+            ["file-kraken", "--project", "foo.fkrproj", "scan"],
+            ["file-kraken", "scan", "--project", "foo.fkrproj"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert_eq!(cli.project, Some(PathBuf::from("foo.fkrproj")));
+            assert!(matches!(cli.command, Command::Scan { location: None }));
+        }
+    }
+}
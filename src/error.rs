@@ -0,0 +1,144 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+
+/// Errors returned by the file-kraken engine.
+#[derive(Debug)]
+pub enum FileKrakenError {
+    /// No project file has been opened yet
+    NotConnected,
+    Sqlite(rusqlite::Error),
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    LocationNotFound(String),
+    LocationAlreadyExists(String),
+    NoParentLocation(String),
+    AlreadyScanning(String),
+    LocationExcluded(String),
+    InvalidIgnorePattern(String),
+    AlreadyProcessing,
+    UnknownFileType(String),
+    UnknownLocationType(String),
+    UnknownHashAlgorithm(String),
+    UnknownImageHashAlgorithm(String),
+    UnknownDeletionMode(String),
+    UnknownJournalAction(String),
+    UnknownDuplicateResolution(String),
+    UnknownLargeFilesOrder(String),
+    /// A duplicate was not deleted because it could not be confirmed to be identical
+    VerificationFailed {
+        path: String,
+        reason: String,
+    },
+    /// The filesystem could not share the extents of a duplicate with its kept file
+    DedupeFailed {
+        path: String,
+        reason: String,
+    },
+    InvalidKeeperRule(String),
+    /// A folder was not removed because it is no longer empty
+    FolderNotEmpty(String),
+    /// Files inside archives can only be deleted along with the whole archive
+    ArchiveMemberNotDeletable(String),
+    /// The project file was written by a newer version of File Kraken
+    ProjectVersionTooNew {
+        version: i64,
+        supported: i64,
+    },
+}
+
+impl fmt::Display for FileKrakenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FileKrakenError::NotConnected => write!(f, "no project file is connected"),
+            FileKrakenError::Sqlite(err) => write!(f, "database error: {}", err),
+            FileKrakenError::Io { path, source } => write!(f, "{}: {}", path, source),
+            FileKrakenError::LocationNotFound(path) => write!(f, "location {} not found", path),
+            FileKrakenError::LocationAlreadyExists(path) => {
+                write!(f, "location {} already exists", path)
+            }
+            FileKrakenError::NoParentLocation(path) => {
+                write!(f, "no parent location found for {}", path)
+            }
+            FileKrakenError::AlreadyScanning(path) => {
+                write!(f, "already scanning location {}", path)
+            }
+            FileKrakenError::LocationExcluded(path) => {
+                write!(f, "location {} is excluded from scanning", path)
+            }
+            FileKrakenError::InvalidIgnorePattern(pattern) => {
+                write!(f, "invalid ignore pattern {}", pattern)
+            }
+            FileKrakenError::AlreadyProcessing => write!(f, "already processing duplicates"),
+            FileKrakenError::UnknownFileType(file_type) => {
+                write!(f, "unknown file type {}", file_type)
+            }
+            FileKrakenError::UnknownLocationType(location_type) => {
+                write!(f, "unknown location type {}", location_type)
+            }
+            FileKrakenError::UnknownHashAlgorithm(algorithm) => {
+                write!(f, "unknown hash algorithm {}", algorithm)
+            }
+            FileKrakenError::UnknownImageHashAlgorithm(algorithm) => {
+                write!(f, "unknown image hash algorithm {}", algorithm)
+            }
+            FileKrakenError::UnknownDeletionMode(mode) => {
+                write!(f, "unknown deletion mode {}", mode)
+            }
+            FileKrakenError::UnknownJournalAction(action) => {
+                write!(f, "unknown journal action {}", action)
+            }
+            FileKrakenError::UnknownDuplicateResolution(resolution) => {
+                write!(f, "unknown duplicate resolution {}", resolution)
+            }
+            FileKrakenError::UnknownLargeFilesOrder(order) => {
+                write!(f, "unknown large files order {}", order)
+            }
+            FileKrakenError::VerificationFailed { path, reason } => {
+                write!(f, "refusing to delete {}: {}", path, reason)
+            }
+            FileKrakenError::DedupeFailed { path, reason } => {
+                write!(f, "cannot deduplicate {}: {}", path, reason)
+            }
+            FileKrakenError::InvalidKeeperRule(reason) => {
+                write!(f, "invalid keeper rule: {}", reason)
+            }
+            FileKrakenError::FolderNotEmpty(path) => write!(f, "folder {} is not empty", path),
+            FileKrakenError::ArchiveMemberNotDeletable(path) => {
+                write!(f, "{} is inside an archive and can't be deleted on its own", path)
+            }
+            FileKrakenError::ProjectVersionTooNew { version, supported } => write!(
+                f,
+                "project file has schema version {} but this version of File Kraken only supports up to {}, please update File Kraken",
+                version, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FileKrakenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileKrakenError::Sqlite(err) => Some(err),
+            FileKrakenError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for FileKrakenError {
+    fn from(err: rusqlite::Error) -> Self {
+        FileKrakenError::Sqlite(err)
+    }
+}
+
+impl FileKrakenError {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        FileKrakenError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
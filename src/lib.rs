@@ -0,0 +1,12 @@
+//! Engine of File Kraken: project state, location scanning and duplicate finding.
+//!
+//! The GUI binary is a thin consumer of this library; other tools can drive
+//! the same operations through [`AppState`] and the functions in [`processing`].
+
+pub mod error;
+pub mod processing;
+pub mod state;
+pub mod utils;
+
+pub use error::FileKrakenError;
+pub use state::AppState;
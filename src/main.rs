@@ -2,17 +2,21 @@
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
 mod app_init;
-mod processing;
-mod state;
+mod cli;
 mod tabs;
-mod utils;
+mod ui;
 
 use crate::app_init::app_init;
-use crate::tabs::tab_files::FileKrakenFileTabs;
+use crate::tabs::tab_files::{
+    DiskUsageTabState, DuplicateFoldersTabState, DuplicatesTabState, EmptyFoldersTabState,
+    FileKrakenFileTabs, LargeFilesTabState, OverviewTabState, QuarantineTabState,
+};
 use crate::tabs::tab_locations::LocationTabState;
 use crate::tabs::FileKrakenMainTabs;
-use crate::utils::dialogs::error_dialog;
+use crate::ui::dialogs::{error_dialog, info_dialog};
 use egui::{Align, FontId, Layout, RichText, Vec2};
+use file_kraken::processing::undo::{get_last_undoable_operation, undo_last_operation};
+use file_kraken::AppState;
 use rfd::FileDialog;
 use std::sync::Arc;
 use std::time::Duration;
@@ -25,17 +29,74 @@ pub struct FileKrakenApp {
 
     // state for each tab
     tab_state_locations: LocationTabState,
+    tab_state_overview: OverviewTabState,
+    tab_state_duplicates: DuplicatesTabState,
+    tab_state_duplicate_folders: DuplicateFoldersTabState,
+    tab_state_disk_usage: DiskUsageTabState,
+    tab_state_empty_folders: EmptyFoldersTabState,
+    tab_state_large_files: LargeFilesTabState,
+    tab_state_quarantine: QuarantineTabState,
 
     // main app state
-    app_state: Arc<state::AppState>,
+    app_state: Arc<AppState>,
 }
 
-fn try_connect_sqlite(_self: &mut FileKrakenApp, path: &str) -> () {
+fn try_connect_sqlite(_self: &mut FileKrakenApp, path: &str) {
     if let Err(err) = _self.app_state.connect_sqlite(path) {
-        error_dialog(&format!("Failed to create project file. Error: {}", err));
+        error_dialog(&format!("Failed to open project file. Error: {}", err));
     }
 }
 
+fn undo_last_operation_dialog(_self: &mut FileKrakenApp) {
+    let description = match get_last_undoable_operation(&_self.app_state) {
+        Ok(Some(operation)) => operation.description,
+        Ok(None) => {
+            info_dialog("Undo", "There is no operation to undo.");
+            return;
+        }
+        Err(err) => {
+            error_dialog(&format!("Failed to read the journal. Error: {}", err));
+            return;
+        }
+    };
+    let confirmed = rfd::MessageDialog::new()
+        .set_title("Undo last operation")
+        .set_description(format!("Undo \"{}\"?", description))
+        .set_buttons(rfd::MessageButtons::YesNo)
+        .show()
+        .eq(&rfd::MessageDialogResult::Yes);
+    if !confirmed {
+        return;
+    }
+
+    match undo_last_operation(&_self.app_state) {
+        Ok(Some(report)) => {
+            let mut message = if report.undone {
+                format!(
+                    "Undid \"{}\", {} changes reverted.",
+                    report.operation.description, report.nr_reverted
+                )
+            } else {
+                format!(
+                    "Could not undo \"{}\", nothing could be reverted.",
+                    report.operation.description
+                )
+            };
+            if !report.not_reverted.is_empty() {
+                message += &format!("\n\nCould not revert:\n{}", report.not_reverted.join("\n"));
+            }
+            info_dialog("Undo", &message);
+        }
+        Ok(None) => info_dialog("Undo", "There is no operation to undo."),
+        Err(err) => error_dialog(&format!("Failed to undo. Error: {}", err)),
+    }
+    _self.tab_state_quarantine.reload();
+    _self.tab_state_empty_folders.reload();
+    _self.tab_state_duplicate_folders.reload();
+    _self.tab_state_large_files.reload();
+    _self.tab_state_overview.reload();
+}
+
 impl FileKrakenApp {
     pub fn new() -> Self {
         let mut _self = Self::default();
@@ -53,7 +114,7 @@ impl eframe::App for FileKrakenApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         std::thread::sleep(Duration::from_micros((1.0 / 120.0 * 1_000_000.0) as u64));
         egui::CentralPanel::default().show(ctx, |ui| {
-            if self.app_state.is_sqlite_connected() == false {
+            if !self.app_state.is_sqlite_connected() {
                 ui.centered_and_justified(|ui| {
                     ui.vertical_centered(|ui| {
                         ui.heading("File Kraken");
@@ -117,6 +178,11 @@ impl eframe::App for FileKrakenApp {
                     FileKrakenMainTabs::Files,
                     RichText::new("Files"),
                 );
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui.button("↶ Undo last operation").clicked() {
+                        undo_last_operation_dialog(self);
+                    }
+                });
             });
             ui.separator();
             match self.current_tab {
@@ -131,6 +197,11 @@ fn main() -> eframe::Result {
     // init env logger
     env_logger::init();
 
+    // any arguments select the headless command line interface
+    if std::env::args_os().len() > 1 {
+        std::process::exit(cli::run());
+    }
+
     // run the app
     app_init()
 }
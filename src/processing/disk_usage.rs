@@ -0,0 +1,202 @@
+use crate::error::FileKrakenError;
+use crate::state::file::FileCategory;
+use crate::state::AppState;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Number of files kept per folder, smaller files are only counted in
+/// [`DiskUsageFolder::other_files_size`]
+const MAX_FILES_PER_FOLDER: usize = 100;
+
+/// A file directly inside a [`DiskUsageFolder`]
+#[derive(Debug, Clone)]
+pub struct DiskUsageFile {
+    pub path: String,
+    pub size: u64,
+    pub category: FileCategory,
+    /// Deletable in the current duplicate search
+    pub is_duplicate: bool,
+}
+
+/// Disk usage of a folder and everything below it
+#[derive(Debug, Clone, Default)]
+pub struct DiskUsageFolder {
+    pub path: String,
+    pub size: u64,
+    pub nr_files: u64,
+    /// Bytes per [`FileCategory`], indexed by [`FileCategory::index`]
+    pub size_by_category: [u64; FileCategory::ALL.len()],
+    /// Bytes of files that are deletable in the current duplicate search
+    pub duplicate_size: u64,
+    /// Largest first
+    pub subfolders: Vec<DiskUsageFolder>,
+    /// The largest files directly inside the folder, largest first
+    pub files: Vec<DiskUsageFile>,
+    /// Bytes and number of the files directly inside the folder not listed in `files`
+    pub other_files_size: u64,
+    pub nr_other_files: u64,
+}
+
+impl DiskUsageFolder {
+    /// The category taking up the most space
+    pub fn main_category(&self) -> FileCategory {
+        FileCategory::ALL
+            .into_iter()
+            .max_by_key(|x| self.size_by_category[x.index()])
+            .unwrap_or(FileCategory::Other)
+    }
+
+    /// Follow a list of subfolder indices down from this folder
+    pub fn descendant(&self, indices: &[usize]) -> Option<&DiskUsageFolder> {
+        indices
+            .iter()
+            .try_fold(self, |folder, &i| folder.subfolders.get(i))
+    }
+
+    fn add_file(&mut self, file: DiskUsageFile) {
+        self.size += file.size;
+        self.nr_files += 1;
+        self.size_by_category[file.category.index()] += file.size;
+        if file.is_duplicate {
+            self.duplicate_size += file.size;
+        }
+        self.files.push(file);
+        // only the largest files are kept, so folders with millions of files stay small
+        if self.files.len() >= 2 * MAX_FILES_PER_FOLDER {
+            self.truncate_files();
+        }
+    }
+
+    fn truncate_files(&mut self) {
+        self.files.sort_by_key(|x| std::cmp::Reverse(x.size));
+        for file in self
+            .files
+            .drain(MAX_FILES_PER_FOLDER.min(self.files.len())..)
+        {
+            self.other_files_size += file.size;
+            self.nr_other_files += 1;
+        }
+    }
+
+    fn add_subfolder(&mut self, subfolder: DiskUsageFolder) {
+        self.size += subfolder.size;
+        self.nr_files += subfolder.nr_files;
+        for (size, subfolder_size) in self
+            .size_by_category
+            .iter_mut()
+            .zip(subfolder.size_by_category)
+        {
+            *size += subfolder_size;
+        }
+        self.duplicate_size += subfolder.duplicate_size;
+        self.subfolders.push(subfolder);
+    }
+}
+
+/// Aggregate the sizes of all files below a location per folder.
+///
+/// Files of nested locations are included, hard links to the same data are counted once.
+/// Files count as duplicates if they are deletable in the latest duplicate search.
+pub fn get_disk_usage_tree(
+    app_state: &AppState,
+    location_path: &str,
+) -> Result<DiskUsageFolder, FileKrakenError> {
+    let duplicate_paths: HashSet<String> = app_state
+        .find_duplicates_processing
+        .duplicates
+        .read()
+        .unwrap()
+        .iter()
+        .flat_map(|x| x.deletable_files.iter().map(|file| file.path.clone()))
+        .collect();
+
+    // files directly inside each folder, the folders are linked up afterwards
+    let mut folders: HashMap<String, DiskUsageFolder> = HashMap::new();
+    // all paths below the location sort between "<location>/" and "<location>0"
+    let folder_prefix = location_path.trim_end_matches('/');
+    let range = (format!("{}/", folder_prefix), format!("{}0", folder_prefix));
+    app_state.with_sqlite(|sqlite| {
+        let mut query = sqlite.prepare(
+            "SELECT path, file_len, device, inode FROM files WHERE path > ?1 AND path < ?2;",
+        )?;
+        let mut rows = query.query([&range.0, &range.1])?;
+        let mut seen_inodes: HashSet<(i64, i64)> = HashSet::new();
+        while let Some(row) = rows.next()? {
+            let path: String = row.get(0)?;
+            let inode_id: Option<(i64, i64)> = row.get::<_, Option<i64>>(2)?.zip(row.get(3)?);
+            if inode_id.is_some_and(|x| !seen_inodes.insert(x)) {
+                continue;
+            }
+            let Some(folder_path) = Path::new(&path).parent().and_then(|x| x.to_str()) else {
+                continue;
+            };
+            let folder_path = folder_path.to_string();
+            let file = DiskUsageFile {
+                size: row.get(1)?,
+                category: FileCategory::from_path(&path),
+                is_duplicate: duplicate_paths.contains(&path),
+                path,
+            };
+            folders
+                .entry(folder_path.clone())
+                .or_insert_with(|| DiskUsageFolder {
+                    path: folder_path,
+                    ..Default::default()
+                })
+                .add_file(file);
+        }
+        Ok(())
+    })?;
+
+    // every folder between a file and the location is part of the tree
+    let mut subfolder_paths: HashMap<String, Vec<String>> = HashMap::new();
+    let mut pending: Vec<String> = folders.keys().cloned().collect();
+    while let Some(folder_path) = pending.pop() {
+        if folder_path == location_path {
+            continue;
+        }
+        let Some(parent_path) = Path::new(&folder_path)
+            .parent()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_string())
+        else {
+            continue;
+        };
+        subfolder_paths
+            .entry(parent_path.clone())
+            .or_default()
+            .push(folder_path);
+        if !folders.contains_key(&parent_path) {
+            folders.insert(
+                parent_path.clone(),
+                DiskUsageFolder {
+                    path: parent_path.clone(),
+                    ..Default::default()
+                },
+            );
+            pending.push(parent_path);
+        }
+    }
+
+    Ok(build_folder(location_path, &mut folders, &subfolder_paths))
+}
+
+fn build_folder(
+    folder_path: &str,
+    folders: &mut HashMap<String, DiskUsageFolder>,
+    subfolder_paths: &HashMap<String, Vec<String>>,
+) -> DiskUsageFolder {
+    let mut folder = folders
+        .remove(folder_path)
+        .unwrap_or_else(|| DiskUsageFolder {
+            path: folder_path.to_string(),
+            ..Default::default()
+        });
+    folder.truncate_files();
+    for subfolder_path in subfolder_paths.get(folder_path).into_iter().flatten() {
+        let subfolder = build_folder(subfolder_path, folders, subfolder_paths);
+        folder.add_subfolder(subfolder);
+    }
+    folder.subfolders.sort_by_key(|x| std::cmp::Reverse(x.size));
+    folder
+}
@@ -0,0 +1,572 @@
+use crate::error::FileKrakenError;
+use crate::processing::empty_folders::{is_junk_file, remove_empty_folders, EmptyFolder};
+use crate::processing::keeper::KeeperSelector;
+use crate::processing::resolve::resolve_duplicate;
+use crate::state::duplicate::{DuplicateResolution, FileKrakenDuplicate};
+use crate::state::file::FileKrakenFile;
+use crate::state::location::FileKrakenLocationType;
+use crate::state::AppState;
+use crate::utils::hashing::HashAlgorithm;
+use crate::utils::is_path_parent;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, MAIN_SEPARATOR};
+use std::sync::Arc;
+
+/// Stands in for the hash of empty files, which the duplicate search does not hash
+const EMPTY_FILE_HASH: &str = "empty";
+/// Content found in more folders than this, like license files, does not make folders
+/// similar to each other
+const MAX_FOLDERS_PER_HASH: usize = 20;
+
+/// A folder of a duplicate folder group, with the totals of its files and subfolders
+#[derive(Debug, Clone)]
+pub struct DuplicateFolder {
+    pub path: String,
+    pub nr_files: usize,
+    pub size: u64,
+}
+
+/// Folders whose files and subfolders have the same content
+#[derive(Debug, Clone)]
+pub struct DuplicateFolderGroup {
+    /// The folders that are kept, never empty
+    pub kept_folders: Vec<DuplicateFolder>,
+    /// The folders whose files are resolved in favor of the kept folder
+    pub deletable_folders: Vec<DuplicateFolder>,
+    /// Whether the names of the files and subfolders matched as well
+    pub compare_names: bool,
+}
+
+impl DuplicateFolderGroup {
+    /// All folders of the group, kept folders first
+    pub fn folders(&self) -> impl Iterator<Item = &DuplicateFolder> {
+        self.kept_folders
+            .iter()
+            .chain(self.deletable_folders.iter())
+    }
+
+    /// Keep only the folder at `path`, making all others deletable
+    pub fn set_kept_folder(&mut self, path: &str) {
+        let mut folders: Vec<DuplicateFolder> = self
+            .kept_folders
+            .drain(..)
+            .chain(self.deletable_folders.drain(..))
+            .collect();
+        if let Some(index) = folders.iter().position(|x| x.path == path) {
+            self.kept_folders.push(folders.remove(index));
+            self.deletable_folders = folders;
+        } else {
+            self.kept_folders = folders;
+        }
+    }
+}
+
+/// Two folders that only share part of their content
+#[derive(Debug, Clone)]
+pub struct SimilarFolders {
+    pub folder_a: DuplicateFolder,
+    pub folder_b: DuplicateFolder,
+    /// Size of the content found in both folders
+    pub shared_size: u64,
+}
+
+impl SimilarFolders {
+    /// Percentage of the content of the larger folder that is also in the other one
+    pub fn similarity(&self) -> f64 {
+        let size = self.folder_a.size.max(self.folder_b.size);
+        if size == 0 {
+            return 0.0;
+        }
+        self.shared_size as f64 * 100.0 / size as f64
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct DuplicateFolders {
+    /// Largest groups first, folders inside other duplicate folders are left out
+    pub groups: Vec<DuplicateFolderGroup>,
+    /// Most shared content first
+    pub similar_folders: Vec<SimilarFolders>,
+}
+
+/// Files and subfolders directly inside a folder, with the totals of all its content
+#[derive(Default)]
+struct FolderNode {
+    files: Vec<usize>,
+    subfolders: Vec<String>,
+    nr_files: usize,
+    size: u64,
+    /// Oldest known creation time of its files, 0 if unknown
+    time_created: u64,
+    /// `None` if the content of a file is unknown
+    hash: Option<String>,
+}
+
+/// Find folders of the scanned locations whose files and subfolders have the same content,
+/// optionally also requiring the same names, and pairs of folders sharing at least
+/// `min_similarity` percent of their content.
+///
+/// Folder hashes are built bottom up from the hashes of their files and subfolders, so only
+/// files hashed by the duplicate search with the project's algorithm are compared, other
+/// files never match. Junk files like `.DS_Store` are not compared. The kept folder of
+/// each group is chosen with the keeper rules, or by the shortest path if they are tied.
+pub fn find_duplicate_folders(
+    app_state: &AppState,
+    compare_names: bool,
+    min_similarity: f64,
+) -> Result<DuplicateFolders, FileKrakenError> {
+    let hash_algorithm = app_state.get_hash_algorithm()?;
+    let files = get_folder_files(app_state)?;
+    let hashes = get_content_hashes(&files, hash_algorithm);
+    let location_paths: Vec<String> = get_location_paths(app_state);
+    let folders = build_folder_tree(&files, &hashes, &location_paths, compare_names);
+
+    // location roots are never reported, deleting them would remove the location
+    let mut paths_by_hash: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (path, folder) in folders.iter() {
+        if let Some(hash) = &folder.hash {
+            if folder.nr_files > 0 && !location_paths.contains(path) {
+                paths_by_hash.entry(hash).or_default().push(path);
+            }
+        }
+    }
+    paths_by_hash.retain(|_, paths| paths.len() > 1);
+    let duplicate_paths: HashSet<&str> = paths_by_hash.values().flatten().copied().collect();
+
+    let summary = |path: &str| DuplicateFolder {
+        path: path.to_string(),
+        nr_files: folders[path].nr_files,
+        size: folders[path].size,
+    };
+    let keeper_selector = KeeperSelector::new(app_state)?;
+    let mut groups = vec![];
+    for paths in paths_by_hash.values() {
+        // subfolders of duplicate folders are reported along with their parents, one of
+        // them is only listed as the copy of other folders with the same content
+        let (covered_paths, mut paths): (Vec<&str>, Vec<&str>) = paths.iter().partition(|path| {
+            Path::new(path)
+                .parent()
+                .and_then(|x| x.to_str())
+                .is_some_and(|parent| duplicate_paths.contains(parent))
+        });
+        if paths.is_empty() {
+            continue;
+        }
+        if paths.len() < 2 {
+            paths.extend(covered_paths.iter().min());
+        }
+        // folders are chosen like files, by their path, location and oldest file
+        let candidates: Vec<FileKrakenFile> = paths
+            .iter()
+            .map(|path| FileKrakenFile {
+                path: path.to_string(),
+                file_len: folders[*path].size,
+                time_created: folders[*path].time_created,
+                ..Default::default()
+            })
+            .collect();
+        let (mut kept_folders, mut deletable_folders) = keeper_selector.split(candidates);
+        if deletable_folders.is_empty() {
+            // folders the keeper rules can't tell apart would leave nothing to resolve
+            kept_folders.sort_by(|a, b| a.path.len().cmp(&b.path.len()).then(a.path.cmp(&b.path)));
+            deletable_folders = kept_folders.split_off(1);
+        }
+        groups.push(DuplicateFolderGroup {
+            kept_folders: kept_folders.iter().map(|x| summary(&x.path)).collect(),
+            deletable_folders: deletable_folders.iter().map(|x| summary(&x.path)).collect(),
+            compare_names,
+        });
+    }
+    groups.sort_by(|a, b| {
+        b.kept_folders[0]
+            .size
+            .cmp(&a.kept_folders[0].size)
+            .then_with(|| a.kept_folders[0].path.cmp(&b.kept_folders[0].path))
+    });
+
+    let mut similar_folders = vec![];
+    let mut similar_paths: HashSet<(String, String)> = HashSet::new();
+    for (path_a, path_b, shared_size) in find_similar_folders(&files, &hashes, &folders) {
+        let same_group = duplicate_paths.contains(path_a.as_str())
+            && folders[&path_a].hash == folders[&path_b].hash;
+        let similar = SimilarFolders {
+            folder_a: summary(&path_a),
+            folder_b: summary(&path_b),
+            shared_size,
+        };
+        if !same_group && similar.similarity() >= min_similarity {
+            similar_paths.insert((path_a, path_b));
+            similar_folders.push(similar);
+        }
+    }
+    // subfolders of similar folders are usually similar as well
+    similar_folders.retain(|x| {
+        let parent = |path: &str| {
+            Path::new(path)
+                .parent()
+                .and_then(|x| x.to_str())
+                .map(|x| x.to_string())
+                .unwrap_or_default()
+        };
+        let (parent_a, parent_b) = (parent(&x.folder_a.path), parent(&x.folder_b.path));
+        !similar_paths.contains(&(parent_a.clone(), parent_b.clone()))
+            && !similar_paths.contains(&(parent_b, parent_a))
+    });
+    similar_folders.sort_by_key(|x| std::cmp::Reverse(x.shared_size));
+
+    Ok(DuplicateFolders {
+        groups,
+        similar_folders,
+    })
+}
+
+/// Resolve the files of the deletable folders of a group in favor of their counterparts in
+/// the kept folder, recording them in the journal under `operation_id`.
+///
+/// Every file is verified and resolved like a duplicate file, using the project's
+/// [`DuplicateResolution`]. Deleted folders are removed once all of their files are gone.
+/// Returns the files and folders that could not be resolved with the reason.
+pub fn resolve_duplicate_folder(
+    app_state: &Arc<AppState>,
+    group: &DuplicateFolderGroup,
+    operation_id: i64,
+) -> Result<Vec<(String, FileKrakenError)>, FileKrakenError> {
+    let Some(kept_folder) = group.kept_folders.first() else {
+        return Ok(vec![]);
+    };
+    let hash_algorithm = app_state.get_hash_algorithm()?;
+    let resolution = app_state.get_duplicate_resolution()?;
+    let files = get_folder_files(app_state)?;
+    let hashes = get_content_hashes(&files, hash_algorithm);
+
+    // the counterpart of a file has the same path relative to its folder or the same hash
+    let counterpart_key = |index: usize, folder_path: &str| -> Option<String> {
+        if group.compare_names {
+            Path::new(&files[index].path)
+                .strip_prefix(folder_path)
+                .ok()
+                .and_then(|x| x.to_str())
+                .map(|x| x.to_string())
+        } else {
+            hashes[index].clone()
+        }
+    };
+    let mut counterparts: HashMap<String, usize> = HashMap::new();
+    for index in folder_file_indexes(&files, &kept_folder.path) {
+        if is_junk_file(Path::new(&files[index].path)) {
+            continue;
+        }
+        if let Some(key) = counterpart_key(index, &kept_folder.path) {
+            counterparts.insert(key, index);
+        }
+    }
+
+    let mut failed = vec![];
+    for folder in group.deletable_folders.iter() {
+        let mut junk_files: HashMap<String, Vec<String>> = HashMap::new();
+        let mut nr_failed = 0;
+        for index in folder_file_indexes(&files, &folder.path) {
+            let file = &files[index];
+            if is_junk_file(Path::new(&file.path)) {
+                if let Some(parent) = Path::new(&file.path).parent().and_then(|x| x.to_str()) {
+                    junk_files
+                        .entry(parent.to_string())
+                        .or_default()
+                        .push(file.path.clone());
+                }
+                continue;
+            }
+            let Some(kept_index) = counterpart_key(index, &folder.path)
+                .and_then(|key| counterparts.get(&key).copied())
+                .filter(|&x| hashes[index].is_some() && hashes[x] == hashes[index])
+            else {
+                failed.push((
+                    file.path.clone(),
+                    FileKrakenError::VerificationFailed {
+                        path: file.path.clone(),
+                        reason: format!("no file with the same content in {}", kept_folder.path),
+                    },
+                ));
+                nr_failed += 1;
+                continue;
+            };
+            let duplicate = FileKrakenDuplicate {
+                other_files: vec![files[kept_index].clone()],
+                deletable_files: vec![file.clone()],
+                ..Default::default()
+            };
+            if let Err(err) = resolve_duplicate(app_state, &duplicate, file, operation_id) {
+                failed.push((file.path.clone(), err));
+                nr_failed += 1;
+            }
+        }
+
+        if resolution != DuplicateResolution::Delete || nr_failed > 0 {
+            continue;
+        }
+        let empty_folders: Vec<EmptyFolder> = app_state
+            .get_folders()?
+            .into_iter()
+            .filter(|(path, _)| *path == folder.path || is_path_parent(path, &folder.path))
+            .map(|(path, location_path)| EmptyFolder {
+                junk_files: junk_files.remove(&path).unwrap_or_default(),
+                path,
+                location_path,
+            })
+            .collect();
+        failed.extend(remove_empty_folders(
+            app_state,
+            &empty_folders,
+            operation_id,
+        ));
+    }
+    Ok(failed)
+}
+
+/// All files of the project outside of excluded locations, sorted by path
+fn get_folder_files(app_state: &AppState) -> Result<Vec<FileKrakenFile>, FileKrakenError> {
+    let excluded_paths: Vec<String> = app_state
+        .get_locations_list_readonly()
+        .iter()
+        .filter(|x| x.location_type == FileKrakenLocationType::Excluded)
+        .map(|x| x.path.clone())
+        .collect();
+    let mut files: Vec<FileKrakenFile> = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .prepare(&format!(
+                "SELECT {} FROM files;",
+                FileKrakenFile::SQL_COLUMNS
+            ))?
+            .query_map([], FileKrakenFile::from_row)?
+            .collect()
+    })?;
+    files.retain(|file| {
+        !excluded_paths
+            .iter()
+            .any(|x| *x == file.path || is_path_parent(&file.path, x))
+    });
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn get_location_paths(app_state: &AppState) -> Vec<String> {
+    app_state
+        .get_locations_list_readonly()
+        .iter()
+        .filter(|x| x.location_type != FileKrakenLocationType::Excluded)
+        .map(|x| x.path.clone())
+        .collect()
+}
+
+/// The hash of every file calculated with `hash_algorithm`, hard links that were not
+/// hashed on their own share the hash of their inode
+fn get_content_hashes(
+    files: &[FileKrakenFile],
+    hash_algorithm: HashAlgorithm,
+) -> Vec<Option<String>> {
+    let stored_hash = |file: &FileKrakenFile| {
+        if file.file_len == 0 {
+            Some(EMPTY_FILE_HASH.to_string())
+        } else if file.hash_algorithm.unwrap_or_default() == hash_algorithm {
+            file.hash.clone()
+        } else {
+            None
+        }
+    };
+    let mut hashes_by_inode = HashMap::new();
+    for file in files.iter() {
+        if let (Some(inode_id), Some(hash)) = (file.inode_id(), stored_hash(file)) {
+            hashes_by_inode.insert(inode_id, hash);
+        }
+    }
+    files
+        .iter()
+        .map(|file| {
+            stored_hash(file).or_else(|| {
+                file.inode_id()
+                    .and_then(|x| hashes_by_inode.get(&x).cloned())
+            })
+        })
+        .collect()
+}
+
+/// Indexes of the files inside a folder and its subfolders, `files` is sorted by path
+fn folder_file_indexes(files: &[FileKrakenFile], folder_path: &str) -> Vec<usize> {
+    let prefix = format!("{}{}", folder_path, MAIN_SEPARATOR);
+    let start = files.partition_point(|x| x.path < prefix);
+    (start..files.len())
+        .take_while(|&index| files[index].path.starts_with(&prefix))
+        .collect()
+}
+
+/// The folders containing the files below the outermost location roots, with their
+/// totals and hashes
+fn build_folder_tree(
+    files: &[FileKrakenFile],
+    hashes: &[Option<String>],
+    location_paths: &[String],
+    compare_names: bool,
+) -> HashMap<String, FolderNode> {
+    let mut folders: HashMap<String, FolderNode> = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if is_junk_file(Path::new(&file.path)) {
+            continue;
+        }
+        let Some(root) = location_paths
+            .iter()
+            .filter(|x| is_path_parent(&file.path, x))
+            .min_by_key(|x| x.len())
+        else {
+            continue;
+        };
+        let Some(parent) = Path::new(&file.path).parent().and_then(|x| x.to_str()) else {
+            continue;
+        };
+        folders
+            .entry(parent.to_string())
+            .or_default()
+            .files
+            .push(index);
+        // register the folder with its parents up to the location root
+        let mut folder = Path::new(parent);
+        while let Some(parent) = folder.parent() {
+            if folder == Path::new(root) || !parent.starts_with(root) {
+                break;
+            }
+            let (Some(folder_path), Some(parent_path)) = (folder.to_str(), parent.to_str()) else {
+                break;
+            };
+            let parent_node = folders.entry(parent_path.to_string()).or_default();
+            if parent_node.subfolders.iter().any(|x| x == folder_path) {
+                break;
+            }
+            parent_node.subfolders.push(folder_path.to_string());
+            folder = parent;
+        }
+    }
+
+    // deepest folders first, so the hashes of subfolders are known before their parents
+    let mut paths: Vec<String> = folders.keys().cloned().collect();
+    paths.sort_by_key(|x| std::cmp::Reverse(Path::new(x).components().count()));
+    let name = |path: &str| {
+        if compare_names {
+            Path::new(path)
+                .file_name()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            String::new()
+        }
+    };
+    for path in paths {
+        let node = &folders[&path];
+        let mut nr_files = node.files.len();
+        let mut size = 0;
+        let mut time_created = 0;
+        let mut oldest = |x: u64| {
+            if x != 0 && (time_created == 0 || x < time_created) {
+                time_created = x;
+            }
+        };
+        let mut entries = vec![];
+        let mut is_complete = true;
+        for &index in node.files.iter() {
+            size += files[index].file_len;
+            oldest(files[index].time_created);
+            match &hashes[index] {
+                Some(hash) => entries.push(format!("f {} {}", name(&files[index].path), hash)),
+                None => is_complete = false,
+            }
+        }
+        for subfolder in node.subfolders.iter() {
+            let subfolder_node = &folders[subfolder];
+            nr_files += subfolder_node.nr_files;
+            size += subfolder_node.size;
+            oldest(subfolder_node.time_created);
+            match &subfolder_node.hash {
+                Some(hash) => entries.push(format!("d {} {}", name(subfolder), hash)),
+                None => is_complete = false,
+            }
+        }
+        let hash = is_complete.then(|| {
+            entries.sort();
+            let mut hasher = HashAlgorithm::Sha256.hasher();
+            for entry in entries.iter() {
+                let _ = writeln!(hasher, "{}", entry);
+            }
+            hasher.finalize_hex()
+        });
+
+        let node = folders.get_mut(&path).unwrap();
+        node.nr_files = nr_files;
+        node.size = size;
+        node.time_created = time_created;
+        node.hash = hash;
+    }
+    folders
+}
+
+/// Pairs of folders that directly contain files with the same content, with the size of
+/// the content of their files and subfolders found in both
+fn find_similar_folders(
+    files: &[FileKrakenFile],
+    hashes: &[Option<String>],
+    folders: &HashMap<String, FolderNode>,
+) -> Vec<(String, String, u64)> {
+    let mut folders_by_hash: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (path, node) in folders.iter() {
+        for &index in node.files.iter() {
+            if let Some(hash) = hashes[index].as_deref().filter(|x| *x != EMPTY_FILE_HASH) {
+                folders_by_hash.entry(hash).or_default().insert(path);
+            }
+        }
+    }
+    let mut pairs: HashSet<(&str, &str)> = HashSet::new();
+    for paths in folders_by_hash.values() {
+        if paths.len() < 2 || paths.len() > MAX_FOLDERS_PER_HASH {
+            continue;
+        }
+        let mut paths: Vec<&str> = paths.iter().copied().collect();
+        paths.sort();
+        for (i, path_a) in paths.iter().enumerate() {
+            for path_b in paths[i + 1..].iter() {
+                if !is_path_parent(path_a, path_b) && !is_path_parent(path_b, path_a) {
+                    pairs.insert((path_a, path_b));
+                }
+            }
+        }
+    }
+
+    // number of files per hash and size in a folder and its subfolders
+    let mut contents: HashMap<&str, HashMap<(&str, u64), u64>> = HashMap::new();
+    let content = |path: &str| -> HashMap<(&str, u64), u64> {
+        let mut content = HashMap::new();
+        for index in folder_file_indexes(files, path) {
+            if let Some(hash) = hashes[index].as_deref() {
+                if !is_junk_file(Path::new(&files[index].path)) {
+                    *content.entry((hash, files[index].file_len)).or_default() += 1;
+                }
+            }
+        }
+        content
+    };
+    let mut similar = vec![];
+    for (path_a, path_b) in pairs {
+        for path in [path_a, path_b] {
+            if !contents.contains_key(path) {
+                contents.insert(path, content(path));
+            }
+        }
+        let shared_size = contents[path_a]
+            .iter()
+            .map(|(key, nr_a)| {
+                let nr_b = contents[path_b].get(key).copied().unwrap_or_default();
+                (*nr_a).min(nr_b) * key.1
+            })
+            .sum();
+        similar.push((path_a.to_string(), path_b.to_string(), shared_size));
+    }
+    similar
+}
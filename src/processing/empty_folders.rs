@@ -0,0 +1,170 @@
+use crate::error::FileKrakenError;
+use crate::state::journal::{JournalAction, JournalEntry};
+use crate::state::location::FileKrakenLocationType;
+use crate::state::AppState;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Names of files that operating systems leave behind in folders, which do not make a
+/// folder worth keeping
+pub const JUNK_FILE_NAMES: [&str; 3] = [".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// A folder without files, neither directly nor in its subfolders
+#[derive(Debug, Clone)]
+pub struct EmptyFolder {
+    pub path: String,
+    pub location_path: String,
+    /// Junk files directly inside the folder, deleted along with it
+    pub junk_files: Vec<String>,
+}
+
+pub fn is_junk_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| JUNK_FILE_NAMES.contains(&x))
+}
+
+/// Find the folders of the scanned locations that contain no files, optionally treating
+/// folders that only contain [`JUNK_FILE_NAMES`] as empty.
+///
+/// The folders stored by the last scans are checked against the disk again, so files that
+/// are ignored or could not be scanned keep their folders. The result is sorted by path.
+pub fn find_empty_folders(
+    app_state: &AppState,
+    ignore_junk: bool,
+) -> Result<Vec<EmptyFolder>, FileKrakenError> {
+    let location_paths: HashSet<String> = app_state
+        .get_locations_list_readonly()
+        .iter()
+        .map(|x| x.path.clone())
+        .collect();
+
+    // every ancestor of a file of the project is not empty
+    let mut non_empty_folders: HashSet<PathBuf> = HashSet::new();
+    for location in app_state.get_locations_list_readonly().iter() {
+        if location.location_type == FileKrakenLocationType::Excluded {
+            continue;
+        }
+        let Some(location_files) = app_state.get_files_by_location(&location.path) else {
+            continue;
+        };
+        for file_path in location_files.read().unwrap().keys() {
+            let file_path = Path::new(file_path);
+            if ignore_junk && is_junk_file(file_path) {
+                continue;
+            }
+            for folder in file_path.ancestors().skip(1) {
+                if !non_empty_folders.insert(folder.to_path_buf()) {
+                    break;
+                }
+            }
+        }
+    }
+
+    // deepest folders first, so subfolders are known to be empty before their parents
+    let mut candidates: Vec<(String, String)> = app_state
+        .get_folders()?
+        .into_iter()
+        .filter(|(path, _)| {
+            !location_paths.contains(path) && !non_empty_folders.contains(Path::new(path))
+        })
+        .collect();
+    candidates.sort_by_key(|(path, _)| std::cmp::Reverse(Path::new(path).components().count()));
+
+    let mut empty_folders: HashMap<PathBuf, EmptyFolder> = HashMap::new();
+    for (path, location_path) in candidates {
+        if let Some(junk_files) = find_junk_files(Path::new(&path), ignore_junk, &empty_folders) {
+            empty_folders.insert(
+                PathBuf::from(&path),
+                EmptyFolder {
+                    path,
+                    location_path,
+                    junk_files,
+                },
+            );
+        }
+    }
+
+    let mut empty_folders: Vec<EmptyFolder> = empty_folders.into_values().collect();
+    empty_folders.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(empty_folders)
+}
+
+/// The junk files of a folder on disk, `None` if it contains anything but junk files and
+/// empty folders or cannot be read
+fn find_junk_files(
+    path: &Path,
+    ignore_junk: bool,
+    empty_folders: &HashMap<PathBuf, EmptyFolder>,
+) -> Option<Vec<String>> {
+    let mut junk_files = vec![];
+    for entry in fs::read_dir(path).ok()? {
+        let entry = entry.ok()?;
+        let file_type = entry.file_type().ok()?;
+        let entry_path = entry.path();
+        if file_type.is_dir() && empty_folders.contains_key(&entry_path) {
+            continue;
+        }
+        if file_type.is_file() && ignore_junk && is_junk_file(&entry_path) {
+            junk_files.push(entry_path.to_str()?.to_string());
+            continue;
+        }
+        return None;
+    }
+    Some(junk_files)
+}
+
+/// Remove empty folders from disk, deepest first, recording them in the journal under
+/// `operation_id`.
+///
+/// Their junk files are deleted using the project's deletion mode. A folder whose
+/// contents changed since it was found is left untouched. Empty subfolders of a folder
+/// have to be removed along with it. Returns the folders that could not be removed with
+/// the reason.
+pub fn remove_empty_folders(
+    app_state: &AppState,
+    folders: &[EmptyFolder],
+    operation_id: i64,
+) -> Vec<(String, FileKrakenError)> {
+    let mut folders: Vec<&EmptyFolder> = folders.iter().collect();
+    folders.sort_by_key(|x| std::cmp::Reverse(Path::new(&x.path).components().count()));
+
+    let mut failed = vec![];
+    for folder in folders {
+        if let Err(err) = remove_empty_folder(app_state, folder, operation_id) {
+            failed.push((folder.path.clone(), err));
+        }
+    }
+    failed
+}
+
+fn remove_empty_folder(
+    app_state: &AppState,
+    folder: &EmptyFolder,
+    operation_id: i64,
+) -> Result<(), FileKrakenError> {
+    // verify that only the junk files found before are left
+    let entries =
+        fs::read_dir(&folder.path).map_err(|err| FileKrakenError::io(&folder.path, err))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| FileKrakenError::io(&folder.path, err))?;
+        let is_known_junk = entry
+            .path()
+            .to_str()
+            .is_some_and(|x| folder.junk_files.iter().any(|junk_file| junk_file == x));
+        if !is_known_junk || !entry.file_type().is_ok_and(|x| x.is_file()) {
+            return Err(FileKrakenError::FolderNotEmpty(folder.path.clone()));
+        }
+    }
+
+    for junk_file in folder.junk_files.iter() {
+        app_state.delete_file(operation_id, junk_file)?;
+    }
+    fs::remove_dir(&folder.path).map_err(|err| FileKrakenError::io(&folder.path, err))?;
+    app_state.record_journal_entry(
+        operation_id,
+        &JournalEntry::new(JournalAction::FolderRemoved, &folder.path),
+    )?;
+    app_state.remove_folder(&folder.path)
+}
@@ -0,0 +1,22 @@
+use crate::error::FileKrakenError;
+use crate::state::file::FileKrakenFile;
+use crate::state::AppState;
+
+/// Files whose detected content type contradicts their extension, sorted by path
+pub fn find_extension_mismatches(
+    app_state: &AppState,
+) -> Result<Vec<FileKrakenFile>, FileKrakenError> {
+    let files: Vec<FileKrakenFile> = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .prepare(&format!(
+                "SELECT {} FROM files WHERE mime_type IS NOT NULL ORDER BY path;",
+                FileKrakenFile::SQL_COLUMNS
+            ))?
+            .query_map([], FileKrakenFile::from_row)?
+            .collect()
+    })?;
+    Ok(files
+        .into_iter()
+        .filter(|x| x.has_extension_mismatch())
+        .collect())
+}
@@ -1,17 +1,29 @@
+use crate::error::FileKrakenError;
+use crate::processing::keeper::KeeperSelector;
+use crate::processing::similar_images::find_similar_images;
+use crate::processing::verify::verify_duplicate;
+use crate::state::archive::ArchiveMember;
 use crate::state::duplicate::{FileKrakenDuplicate, FileKrakenDuplicateType};
-use crate::state::file::{FileKrakenFile, FileKrakenFileType};
-use crate::state::location::{FileKrakenLocation, FileKrakenLocationType};
+use crate::state::file::FileKrakenFile;
 use crate::state::AppState;
-use crate::utils::get_longest_parent_path;
-use egui::ahash::HashMap;
+use crate::utils::hashing::HashAlgorithm;
+use log::error;
+use rusqlite::{params, OptionalExtension};
 use std::cmp::max;
+use std::collections::{HashMap, HashSet};
 use std::ops::DerefMut;
-use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
+
+/// Files grouped by one of their hashes
+type GroupedFiles = HashMap<String, Vec<FileKrakenFile>>;
+type FilesByHash = Arc<RwLock<GroupedFiles>>;
 
 #[derive(Default)]
 pub struct FindDuplicatesState {
     pub duplicates: RwLock<Vec<FileKrakenDuplicate>>,
     pub state: RwLock<FindDuplicatesStateType>,
+    /// Disk space freed by resolving duplicates of the current search without deleting them
+    pub bytes_reclaimed: RwLock<u64>,
 }
 
 #[derive(Default, PartialEq)]
@@ -22,43 +34,50 @@ pub enum FindDuplicatesStateType {
     Processed,
 }
 
-pub fn find_file_duplicates(app_state: Arc<AppState>) {
-    if run_find_file_duplicates(app_state.clone()).is_none() {
-        rfd::MessageDialog::new()
-            .set_title("Failed to find duplicates")
-            .set_description("Failed to find duplicates")
-            .show();
+/// Search all files of the project for duplicates, resetting the duplicates state on failure
+pub fn find_file_duplicates(app_state: Arc<AppState>) -> Result<(), FileKrakenError> {
+    let result = run_find_file_duplicates(app_state.clone());
+    if let Err(FileKrakenError::AlreadyProcessing) = result {
+        return result;
+    }
+    if result.is_err() {
         app_state
             .find_duplicates_processing
             .duplicates
             .write()
-            .expect("Failed to clear duplicates")
+            .unwrap()
             .clear();
         *get_duplicates_processing_state(&app_state).deref_mut() = FindDuplicatesStateType::None;
     }
+    result
 }
 
-pub fn run_find_file_duplicates(app_state: Arc<AppState>) -> Option<()> {
+pub fn run_find_file_duplicates(app_state: Arc<AppState>) -> Result<(), FileKrakenError> {
     if let FindDuplicatesStateType::Processing(_) =
         get_duplicates_processing_state(&app_state).deref_mut()
     {
-        rfd::MessageDialog::new()
-            .set_title("Already processing")
-            .set_description("Already processing duplicates")
-            .show();
-        return Some(());
+        return Err(FileKrakenError::AlreadyProcessing);
     }
     app_state
         .find_duplicates_processing
         .duplicates
         .write()
-        .ok()?
+        .unwrap()
         .clear();
+    *app_state
+        .find_duplicates_processing
+        .bytes_reclaimed
+        .write()
+        .unwrap() = 0;
 
-    set_processing_message(&app_state, "Scanning for file size matches...".to_string());
-    let mut duplicate_file_sizes = find_duplicate_file_sizes(&app_state.sqlite)?;
+    set_processing_message(
+        &app_state,
+        "Stage 1/3 | Scanning for file size matches...".to_string(),
+    );
+    let hash_algorithm = app_state.get_hash_algorithm()?;
+    let mut duplicate_file_sizes = find_duplicate_file_sizes(&app_state, hash_algorithm)?;
 
-    let files_by_size_by_hash = Arc::new(RwLock::new(HashMap::default()));
+    let files_by_size_by_hash: Arc<RwLock<HashMap<u64, GroupedFiles>>> = Arc::default();
     let mut duplicates_search_by_filesize_threads = vec![];
     let nr_total_sizes_to_check = duplicate_file_sizes.len();
     let chunk_size = nr_total_sizes_to_check / 16;
@@ -72,179 +91,347 @@ pub fn run_find_file_duplicates(app_state: Arc<AppState>) -> Option<()> {
         let files_by_size_by_hash = files_by_size_by_hash.clone();
         let nr_total_sizes_to_check = nr_total_sizes_to_check as f64;
         let sizes_checked_so_far = sizes_checked_so_far.clone();
-        duplicates_search_by_filesize_threads.push(std::thread::spawn(move || {
-            for duplicate_file_size in duplicate_file_size_chunk {
-                let mut files_by_size: Vec<FileKrakenFile> =
-                    get_files_by_size(&app_state, duplicate_file_size).unwrap();
-                let nr_files_by_size = files_by_size.len();
-                set_processing_message(
-                    &app_state,
-                    format!(
-                        "{:.2}% | Calculating hashes for {} files of size {}",
-                        sizes_checked_so_far.read().unwrap().clone() * 100.0
-                            / nr_total_sizes_to_check,
-                        nr_files_by_size,
-                        duplicate_file_size
-                    ),
-                );
-                let mut threads = vec![];
-                for files in files_by_size
-                    .chunks_mut(max(1, nr_files_by_size / 4))
-                    .map(|x| x.to_owned())
-                {
-                    let files_by_size_by_hash = files_by_size_by_hash
-                        .write()
-                        .unwrap()
-                        .entry(duplicate_file_size)
-                        .or_insert(Arc::new(RwLock::new(HashMap::default())))
-                        .clone();
-                    let _app_state = app_state.clone();
-                    threads.push(std::thread::spawn(move || {
-                        for mut file in files {
-                            // calc hash
-                            file.hash = Some(_app_state.calculate_file_hash(&file.path));
-                            files_by_size_by_hash
-                                .write()
-                                .unwrap()
-                                .entry(file.hash.clone().unwrap())
-                                .or_insert(vec![])
-                                .push(file.clone());
-                        }
-                    }));
-                }
-                for thread in threads {
-                    thread.join().expect("Failed to join hashing thread");
+        duplicates_search_by_filesize_threads.push(std::thread::spawn(
+            move || -> Result<(), FileKrakenError> {
+                for duplicate_file_size in duplicate_file_size_chunk {
+                    let files_by_size = collapse_hard_links(get_files_by_size(
+                        &app_state,
+                        duplicate_file_size,
+                        hash_algorithm,
+                    )?);
+                    // archive members are only reported along with files outside of archives
+                    if files_by_size.len() < 2
+                        || files_by_size.iter().all(|x| x.is_archive_member())
+                    {
+                        *sizes_checked_so_far.write().unwrap() += 1.0;
+                        continue;
+                    }
+                    let nr_files_by_size = files_by_size.len();
+                    let progress =
+                        *sizes_checked_so_far.read().unwrap() * 100.0 / nr_total_sizes_to_check;
+
+                    // most files of equal size already differ in their first or last bytes,
+                    // archive members only have a full hash to compare with
+                    let candidates: Vec<FileKrakenFile> = if files_by_size
+                        .iter()
+                        .any(|x| x.is_archive_member())
+                    {
+                        files_by_size
+                    } else {
+                        set_processing_message(
+                            &app_state,
+                            format!(
+                                "Stage 2/3 | {:.2}% | Calculating partial hashes for {} files of size {}",
+                                progress, nr_files_by_size, duplicate_file_size
+                            ),
+                        );
+                        group_files_by_hash(
+                            &app_state,
+                            files_by_size,
+                            HashStage::Partial(hash_algorithm),
+                        )?
+                            .into_values()
+                            .filter(|x| x.len() > 1)
+                            .flatten()
+                            .collect()
+                    };
+
+                    if !candidates.is_empty() {
+                        set_processing_message(
+                            &app_state,
+                            format!(
+                                "Stage 3/3 | {:.2}% | Calculating full hashes for {} of {} files of size {}",
+                                progress,
+                                candidates.len(),
+                                nr_files_by_size,
+                                duplicate_file_size
+                            ),
+                        );
+                        let files_by_hash =
+                            group_files_by_hash(
+                                &app_state,
+                                candidates,
+                                HashStage::Full(hash_algorithm),
+                            )?;
+                        files_by_size_by_hash
+                            .write()
+                            .unwrap()
+                            .insert(duplicate_file_size, files_by_hash);
+                    }
+                    *sizes_checked_so_far.write().unwrap() += 1.0;
                 }
-                *sizes_checked_so_far.write().unwrap() += 1.0;
-            }
-        }));
+                Ok(())
+            },
+        ));
     }
     for thread in duplicates_search_by_filesize_threads {
-        thread.join().ok()?;
+        thread.join().expect("Failed to join file size thread")?;
     }
 
     set_processing_message(
         &app_state,
         "Checking file-hashes for duplicates...".to_string(),
     );
-    let files_by_size_by_hash_lock = files_by_size_by_hash.write().ok()?;
-    for (_, files_by_size) in files_by_size_by_hash_lock.iter() {
-        for (_, files) in files_by_size.read().ok()?.iter() {
-            if files.len() > 1 {
-                let mut duplicates_list = app_state
+    let keeper_selector = KeeperSelector::new(&app_state)?;
+    let files_by_size_by_hash_lock = files_by_size_by_hash.read().unwrap();
+    for files_by_size in files_by_size_by_hash_lock.values() {
+        for files in files_by_size.values() {
+            if files.len() > 1 && files.iter().any(|x| !x.is_archive_member()) {
+                let (other_files, deletable_files) = keeper_selector.split(files.clone());
+                app_state
                     .find_duplicates_processing
                     .duplicates
                     .write()
-                    .ok()?;
-
-                let deletable_file = get_deletable_file(&app_state, &files);
-                let other_files = if deletable_file.is_some() {
-                    files
-                        .iter()
-                        .filter(|x| x.path != deletable_file.as_ref().unwrap().path)
-                        .cloned()
-                        .collect()
-                } else {
-                    files.clone()
-                };
-                duplicates_list.push(FileKrakenDuplicate {
-                    other_files,
-                    deletable_file,
-                    duplicate_type: FileKrakenDuplicateType::ExactMatch,
-                });
+                    .unwrap()
+                    .push(FileKrakenDuplicate {
+                        other_files,
+                        deletable_files,
+                        duplicate_type: FileKrakenDuplicateType::ExactMatch,
+                        verification_error: None,
+                    });
             }
         }
     }
 
+    let archive_duplicates = find_archive_content_duplicates(&app_state, hash_algorithm)?;
+    app_state
+        .find_duplicates_processing
+        .duplicates
+        .write()
+        .unwrap()
+        .extend(archive_duplicates);
+
+    if app_state.get_similar_images()? {
+        let exact_duplicates = app_state
+            .find_duplicates_processing
+            .duplicates
+            .read()
+            .unwrap()
+            .clone();
+        let similar_images = find_similar_images(
+            &app_state,
+            &exact_duplicates,
+            app_state.get_image_hash_algorithm()?,
+            app_state.get_similar_image_threshold()?,
+        )?;
+        app_state
+            .find_duplicates_processing
+            .duplicates
+            .write()
+            .unwrap()
+            .extend(similar_images);
+    }
+
     *get_duplicates_processing_state(&app_state).deref_mut() = FindDuplicatesStateType::Processed;
 
-    Some(())
+    Ok(())
 }
 
-fn get_deletable_file(
+/// Group archives whose members have the same names and contents, e.g. a zip and a tar.gz
+/// of the same folder.
+///
+/// Archives that are identical byte for byte are left to the regular search.
+fn find_archive_content_duplicates(
     app_state: &Arc<AppState>,
-    files: &Vec<FileKrakenFile>,
-) -> Option<FileKrakenFile> {
-    let (preferred_file, normal_file) = {
-        let file_locations: Vec<(FileKrakenFile, Option<FileKrakenLocation>)> = {
-            let locations = app_state.get_locations_list_readonly();
-            files
-                .iter()
-                .map(|file| {
-                    (
-                        file.clone(),
-                        get_longest_parent_path(&file.path, locations.iter())
-                            .map(|x| locations.iter().find(|loc| loc.path == x).unwrap().clone()),
-                    )
-                })
-                .collect()
-        };
+    hash_algorithm: HashAlgorithm,
+) -> Result<Vec<FileKrakenDuplicate>, FileKrakenError> {
+    let members: Vec<ArchiveMember> = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .prepare(&format!(
+                "SELECT {} FROM archive_members WHERE hash_algorithm = ?1 ORDER BY path;",
+                ArchiveMember::SQL_COLUMNS
+            ))?
+            .query_map([hash_algorithm.to_string()], ArchiveMember::from_row)?
+            .collect()
+    })?;
+    let mut contents_by_archive: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for member in members.iter() {
+        contents_by_archive
+            .entry(member.archive_path.clone())
+            .or_default()
+            .push((member.name().to_string(), member.hash.clone()));
+    }
+    let mut archives_by_contents: HashMap<Vec<(String, String)>, Vec<String>> = HashMap::new();
+    for (archive_path, contents) in contents_by_archive {
+        archives_by_contents
+            .entry(contents)
+            .or_default()
+            .push(archive_path);
+    }
 
-        (
-            file_locations
-                .iter()
-                .filter(|(_, location)| location.is_some())
-                .find(|(_, location)| {
-                    location
-                        .as_ref()
-                        .is_some_and(|loc| loc.location_type == FileKrakenLocationType::Preferred)
-                })
-                .map(|(file, _)| file.clone()),
-            file_locations
-                .iter()
-                .filter(|(_, location)| location.is_some())
-                .find(|(_, location)| {
-                    location
-                        .as_ref()
-                        .is_some_and(|loc| loc.location_type == FileKrakenLocationType::Normal)
-                })
-                .map(|(file, _)| file.clone()),
-        )
-    };
-
-    if preferred_file.is_some() && normal_file.is_some() {
-        normal_file
-    } else {
-        None
+    let exact_duplicates = app_state
+        .find_duplicates_processing
+        .duplicates
+        .read()
+        .unwrap()
+        .clone();
+    let keeper_selector = KeeperSelector::new(app_state)?;
+    let mut duplicates = vec![];
+    for archive_paths in archives_by_contents.into_values() {
+        if archive_paths.len() < 2
+            || exact_duplicates.iter().any(|duplicate| {
+                archive_paths
+                    .iter()
+                    .all(|path| duplicate.files().any(|x| x.path == *path))
+            })
+        {
+            continue;
+        }
+        let archives = collapse_hard_links(get_files_by_path(app_state, &archive_paths)?);
+        if archives.len() < 2 {
+            continue;
+        }
+        let (other_files, deletable_files) = keeper_selector.split(archives);
+        duplicates.push(FileKrakenDuplicate {
+            other_files,
+            deletable_files,
+            duplicate_type: FileKrakenDuplicateType::ArchiveContent,
+            verification_error: None,
+        });
     }
+    Ok(duplicates)
 }
 
-fn get_files_by_size(app_state: &Arc<AppState>, size: u64) -> Option<Vec<FileKrakenFile>> {
-    let sqlite_lock = app_state.sqlite.lock().unwrap();
-    let mut files = vec![];
-
-    let mut sqlite_query = sqlite_lock
-        .as_ref()?
-        .prepare(
-            "SELECT \
-        path, file_type, file_len, time_created, time_modified, hash_256 \
-        FROM files \
-        WHERE file_len = ?1",
-        )
-        .unwrap();
-    let mut select_files = sqlite_query.query([size]).ok()?;
-
-    while let Some(row) = select_files.next().ok()? {
-        let file_path: String = row.get(0).ok()?;
-        let file_type = match row.get::<usize, String>(1).ok()?.as_str() {
-            "normal" => FileKrakenFileType::Normal,
-            "archive" => FileKrakenFileType::Archive,
-            x => {
-                panic!("unknown file type {}", x)
+/// Hashes used to tell files of the same size apart, cheapest first
+#[derive(Clone, Copy)]
+enum HashStage {
+    Partial(HashAlgorithm),
+    Full(HashAlgorithm),
+}
+
+impl HashStage {
+    /// Calculate the hash of this stage, returning a key that only equals keys of
+    /// hashes calculated with the same algorithm
+    fn calculate(
+        self,
+        app_state: &AppState,
+        file: &mut FileKrakenFile,
+    ) -> Result<String, FileKrakenError> {
+        // archive members are hashed while scanning and never read on their own
+        if file.is_archive_member() {
+            return Ok(format!(
+                "{}:{}",
+                file.hash_algorithm.unwrap_or_default(),
+                file.hash.clone().unwrap_or_default()
+            ));
+        }
+        match self {
+            HashStage::Partial(algorithm) => {
+                let hash = app_state.calculate_file_partial_hash(&file.path, algorithm)?;
+                file.partial_hash = Some(hash.clone());
+                Ok(format!("{}:{}", algorithm, hash))
             }
-        };
+            HashStage::Full(algorithm) => {
+                let hash = app_state.calculate_file_hash(&file.path, algorithm)?;
+                file.hash = Some(hash.clone());
+                file.hash_algorithm = Some(algorithm);
+                Ok(format!("{}:{}", algorithm, hash))
+            }
+        }
+    }
+}
 
-        files.push(FileKrakenFile {
-            path: file_path,
-            file_type,
-            file_len: row.get(2).ok()?,
-            time_created: row.get(3).ok()?,
-            time_modified: row.get(4).ok()?,
-            hash: row.get(5).ok()?,
-        });
+/// Hash files on multiple threads and group them by the resulting hash
+fn group_files_by_hash(
+    app_state: &Arc<AppState>,
+    mut files: Vec<FileKrakenFile>,
+    stage: HashStage,
+) -> Result<GroupedFiles, FileKrakenError> {
+    let files_by_hash: FilesByHash = Arc::default();
+    let nr_files = files.len();
+    let mut threads = vec![];
+    for files in files.chunks_mut(max(1, nr_files / 4)).map(|x| x.to_owned()) {
+        let files_by_hash = files_by_hash.clone();
+        let app_state = app_state.clone();
+        threads.push(std::thread::spawn(
+            move || -> Result<(), FileKrakenError> {
+                for mut file in files {
+                    let hash = match stage.calculate(&app_state, &mut file) {
+                        Ok(x) => x,
+                        Err(err @ FileKrakenError::Io { .. }) => {
+                            // unreadable files can not be compared
+                            error!("Skipping file: {}", err);
+                            continue;
+                        }
+                        Err(err) => return Err(err),
+                    };
+                    files_by_hash
+                        .write()
+                        .unwrap()
+                        .entry(hash)
+                        .or_default()
+                        .push(file);
+                }
+                Ok(())
+            },
+        ));
     }
+    for thread in threads {
+        thread.join().expect("Failed to join hashing thread")?;
+    }
+
+    let files_by_hash = std::mem::take(&mut *files_by_hash.write().unwrap());
+    Ok(files_by_hash)
+}
 
-    Some(files)
+/// Keep only one path per inode, hard links share their data and deleting one of them
+/// frees no space
+pub(crate) fn collapse_hard_links(files: Vec<FileKrakenFile>) -> Vec<FileKrakenFile> {
+    let mut seen_inodes = HashSet::new();
+    files
+        .into_iter()
+        .filter(|file| file.inode_id().is_none_or(|x| seen_inodes.insert(x)))
+        .collect()
+}
+
+/// Files and archive members of a size, members are only included if they were hashed
+/// with `hash_algorithm`
+fn get_files_by_size(
+    app_state: &Arc<AppState>,
+    size: u64,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Vec<FileKrakenFile>, FileKrakenError> {
+    app_state.with_sqlite(|sqlite| {
+        let mut sqlite_query = sqlite.prepare(&format!(
+            "SELECT {} FROM files WHERE file_len = ?1",
+            FileKrakenFile::SQL_COLUMNS
+        ))?;
+        let mut files: Vec<FileKrakenFile> = sqlite_query
+            .query_map([size], FileKrakenFile::from_row)?
+            .collect::<Result<_, _>>()?;
+        let mut members_query = sqlite.prepare(&format!(
+            "SELECT {} FROM archive_members WHERE file_len = ?1 AND hash_algorithm = ?2",
+            ArchiveMember::SQL_COLUMNS
+        ))?;
+        for member in members_query.query_map(
+            params![size, hash_algorithm.to_string()],
+            ArchiveMember::from_row,
+        )? {
+            files.push(member?.to_file());
+        }
+        Ok(files)
+    })
+}
+
+fn get_files_by_path(
+    app_state: &Arc<AppState>,
+    paths: &[String],
+) -> Result<Vec<FileKrakenFile>, FileKrakenError> {
+    app_state.with_sqlite(|sqlite| {
+        let mut sqlite_query = sqlite.prepare(&format!(
+            "SELECT {} FROM files WHERE path = ?1",
+            FileKrakenFile::SQL_COLUMNS
+        ))?;
+        let mut files = vec![];
+        for path in paths {
+            files.extend(
+                sqlite_query
+                    .query_row([path], FileKrakenFile::from_row)
+                    .optional()?,
+            );
+        }
+        Ok(files)
+    })
 }
 
 pub fn get_duplicates_processing_state(
@@ -254,55 +441,143 @@ pub fn get_duplicates_processing_state(
 }
 
 pub fn set_processing_message(app_state: &Arc<AppState>, message: String) {
+    app_state.report_progress(&message);
     *get_duplicates_processing_state(app_state).deref_mut() =
         FindDuplicatesStateType::Processing(message);
 }
 
 fn find_duplicate_file_sizes(
-    sqlite: &Arc<Mutex<Option<rusqlite::Connection>>>,
-) -> Option<Vec<u64>> {
-    let sqlite_lock = sqlite.lock().unwrap();
-    let mut find_duplicate_file_sizes = sqlite_lock
-        .as_ref()
-        .unwrap()
-        .prepare("SELECT file_len, COUNT(*) c FROM files f GROUP BY file_len HAVING c > 1")
-        .ok()?;
-    let mut find_duplicate_file_sizes_query = find_duplicate_file_sizes.query([]).ok()?;
+    app_state: &Arc<AppState>,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Vec<u64>, FileKrakenError> {
+    app_state.with_sqlite(|sqlite| {
+        let mut find_duplicate_file_sizes = sqlite.prepare(
+            "SELECT file_len, COUNT(DISTINCT id) c FROM (\
+                    SELECT file_len, COALESCE(device || ':' || inode, path) id FROM files \
+                    UNION ALL \
+                    SELECT file_len, path FROM archive_members WHERE hash_algorithm = ?1\
+                ) GROUP BY file_len HAVING c > 1",
+        )?;
+        let duplicate_file_sizes = find_duplicate_file_sizes
+            .query_map([hash_algorithm.to_string()], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(duplicate_file_sizes)
+    })
+}
 
-    let mut duplicate_file_sizes = vec![];
-    while let Some(row) = find_duplicate_file_sizes_query.next().ok()? {
-        duplicate_file_sizes.push(row.get(0).ok()?);
-    }
+/// Delete a deletable file of a duplicate group after verifying it against a kept file,
+/// recording it in the journal under `operation_id`.
+///
+/// If verification fails, nothing is deleted and the file is flagged and kept instead.
+pub fn delete_duplicate(
+    app_state: &Arc<AppState>,
+    duplicate: &FileKrakenDuplicate,
+    deletable_file: &FileKrakenFile,
+    operation_id: i64,
+) -> Result<(), FileKrakenError> {
+    verify_flagging_failures(app_state, duplicate, deletable_file)?;
+    app_state.delete_file(operation_id, &deletable_file.path)?;
+    remove_resolved_file(app_state, &deletable_file.path);
+    Ok(())
+}
 
-    Some(duplicate_file_sizes)
+/// Verify a deletable file of a duplicate group, flagging it if the files turned out to
+/// differ
+pub(crate) fn verify_flagging_failures<'a>(
+    app_state: &AppState,
+    duplicate: &'a FileKrakenDuplicate,
+    deletable_file: &FileKrakenFile,
+) -> Result<&'a FileKrakenFile, FileKrakenError> {
+    verify_duplicate(app_state, duplicate, deletable_file).inspect_err(|err| {
+        if let FileKrakenError::VerificationFailed { reason, .. } = err {
+            flag_duplicate(app_state, &deletable_file.path, reason);
+        }
+    })
 }
 
-pub fn delete_duplicate(app_state: &Arc<AppState>, duplicate: &FileKrakenDuplicate) {
-    // delete from duplicates list
-    let duplicate_index = {
-        let duplicates_list = app_state
-            .find_duplicates_processing
-            .duplicates
-            .read()
-            .unwrap();
+/// Remove a resolved file from its duplicate group, dropping the group once all of its
+/// deletable files are resolved
+pub(crate) fn remove_resolved_file(app_state: &AppState, deletable_file_path: &str) {
+    app_state
+        .find_duplicates_processing
+        .duplicates
+        .write()
+        .unwrap()
+        .retain_mut(|x| {
+            if !x.is_deletable(deletable_file_path) {
+                return true;
+            }
+            x.deletable_files
+                .retain(|file| file.path != deletable_file_path);
+            !x.deletable_files.is_empty()
+        });
+}
 
-        duplicates_list
+/// Keep a deletable file of a duplicate group instead, noting why it must not be deleted
+pub(crate) fn flag_duplicate(app_state: &AppState, deletable_file_path: &str, reason: &str) {
+    let mut duplicates = app_state
+        .find_duplicates_processing
+        .duplicates
+        .write()
+        .unwrap();
+    for duplicate in duplicates.iter_mut() {
+        if let Some(index) = duplicate
+            .deletable_files
             .iter()
-            .position(|x| {
-                x.deletable_file.as_ref().is_some_and(|file| {
-                    file.path == duplicate.deletable_file.as_ref().unwrap().path
-                })
-            })
-            .unwrap()
-    };
+            .position(|file| file.path == deletable_file_path)
+        {
+            let file = duplicate.deletable_files.remove(index);
+            duplicate.other_files.push(file);
+            duplicate.verification_error = Some(reason.to_string());
+        }
+    }
+}
+
+/// Choose the kept and deletable files of all duplicate groups again, e.g. after the
+/// keeper rules changed, discarding files marked by hand. Similar images stay as they
+/// are, they are only deleted by hand.
+pub fn apply_keeper_rules(app_state: &AppState) -> Result<(), FileKrakenError> {
+    let keeper_selector = KeeperSelector::new(app_state)?;
+    let mut duplicates = app_state
+        .find_duplicates_processing
+        .duplicates
+        .write()
+        .unwrap();
+    for duplicate in duplicates
+        .iter_mut()
+        .filter(|x| x.duplicate_type != FileKrakenDuplicateType::SimilarImage)
     {
-        app_state
-            .find_duplicates_processing
-            .duplicates
-            .write()
-            .unwrap()
-            .remove(duplicate_index);
+        let files = duplicate.files().cloned().collect();
+        (duplicate.other_files, duplicate.deletable_files) = keeper_selector.split(files);
     }
+    Ok(())
+}
 
-    app_state.remove_file(true, true, &duplicate.deletable_file.as_ref().unwrap().path);
+/// Choose whether a file of a duplicate group is kept or deleted when the group is
+/// resolved.
+///
+/// Every group keeps at least one file, so marking its last kept file as deletable does
+/// nothing and returns `false`. Archive members are always kept.
+pub fn set_file_deletable(app_state: &AppState, file_path: &str, deletable: bool) -> bool {
+    let mut duplicates = app_state
+        .find_duplicates_processing
+        .duplicates
+        .write()
+        .unwrap();
+    for duplicate in duplicates.iter_mut() {
+        let (from, to) = if deletable {
+            (&mut duplicate.other_files, &mut duplicate.deletable_files)
+        } else {
+            (&mut duplicate.deletable_files, &mut duplicate.other_files)
+        };
+        if let Some(index) = from.iter().position(|file| file.path == file_path) {
+            if deletable && (from.len() < 2 || from[index].is_archive_member()) {
+                return false;
+            }
+            let file = from.remove(index);
+            to.push(file);
+            return true;
+        }
+    }
+    false
 }
@@ -0,0 +1,198 @@
+use crate::error::FileKrakenError;
+use crate::state::file::FileKrakenFile;
+use crate::state::keeper_rule::KeeperRule;
+use crate::state::location::{FileKrakenLocation, FileKrakenLocationType};
+use crate::state::AppState;
+use crate::utils::get_longest_parent_path;
+use regex::Regex;
+use std::path::Path;
+
+/// The keeper rules of a project, prepared for choosing the kept files of many groups
+pub struct KeeperSelector {
+    rules: Vec<(KeeperRule, Option<Regex>)>,
+    locations: Vec<FileKrakenLocation>,
+}
+
+impl KeeperSelector {
+    pub fn new(app_state: &AppState) -> Result<Self, FileKrakenError> {
+        let rules = app_state
+            .get_keeper_rules()?
+            .into_iter()
+            .map(|rule| {
+                let regex = match &rule {
+                    KeeperRule::PathMatches(regex) => Some(
+                        Regex::new(regex)
+                            .map_err(|err| FileKrakenError::InvalidKeeperRule(err.to_string()))?,
+                    ),
+                    _ => None,
+                };
+                Ok((rule, regex))
+            })
+            .collect::<Result<_, FileKrakenError>>()?;
+        Ok(KeeperSelector {
+            rules,
+            locations: app_state.get_locations_list_readonly().clone(),
+        })
+    }
+
+    /// Split the files of a duplicate group into the kept and the deletable files.
+    ///
+    /// All files the rules like best are kept, possibly more than one if the rules tie, and
+    /// every other file is deletable. Archive members are always kept, in addition to the
+    /// files chosen among the others.
+    pub fn split(&self, files: Vec<FileKrakenFile>) -> (Vec<FileKrakenFile>, Vec<FileKrakenFile>) {
+        let (members, files): (Vec<FileKrakenFile>, Vec<FileKrakenFile>) =
+            files.into_iter().partition(|x| x.is_archive_member());
+        let (mut kept_files, deletable_files) = self.split_files(files);
+        kept_files.extend(members);
+        (kept_files, deletable_files)
+    }
+
+    fn split_files(
+        &self,
+        files: Vec<FileKrakenFile>,
+    ) -> (Vec<FileKrakenFile>, Vec<FileKrakenFile>) {
+        let kept_indexes = self.select_kept_files(&files);
+        let (kept_files, deletable_files): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .enumerate()
+            .partition(|(i, _)| kept_indexes.contains(i));
+        (
+            kept_files.into_iter().map(|(_, file)| file).collect(),
+            deletable_files.into_iter().map(|(_, file)| file).collect(),
+        )
+    }
+
+    /// Indexes of the files to keep, all files still tied after the last rule
+    pub fn select_kept_files(&self, files: &[FileKrakenFile]) -> Vec<usize> {
+        let mut candidates: Vec<usize> = (0..files.len()).collect();
+        for (rule, regex) in self.rules.iter() {
+            if candidates.len() < 2 {
+                break;
+            }
+            let scores: Vec<i64> = candidates
+                .iter()
+                .map(|&i| self.score(rule, regex.as_ref(), &files[i]))
+                .collect();
+            let best_score = scores.iter().copied().min().unwrap_or_default();
+            candidates = candidates
+                .into_iter()
+                .zip(scores)
+                .filter(|(_, score)| *score == best_score)
+                .map(|(i, _)| i)
+                .collect();
+        }
+        candidates
+    }
+
+    /// How much a rule likes to keep a file, lower is better
+    fn score(&self, rule: &KeeperRule, regex: Option<&Regex>, file: &FileKrakenFile) -> i64 {
+        let location = || {
+            get_longest_parent_path(&file.path, self.locations.iter())
+                .and_then(|x| self.locations.iter().find(|location| location.path == x))
+        };
+        match rule {
+            KeeperRule::HighestPriority => -location().map_or(0, |x| x.priority),
+            KeeperRule::PreferredLocation => {
+                let is_preferred = location()
+                    .is_some_and(|x| x.location_type == FileKrakenLocationType::Preferred);
+                if is_preferred {
+                    0
+                } else {
+                    1
+                }
+            }
+            // an unknown creation time is stored as 0 and must not count as the oldest
+            KeeperRule::OldestCreated => match file.time_created {
+                0 => i64::MAX,
+                x => x as i64,
+            },
+            KeeperRule::ShortestPath => file.path.chars().count() as i64,
+            KeeperRule::PathMatches(_) => {
+                if regex.is_some_and(|x| x.is_match(&file.path)) {
+                    0
+                } else {
+                    1
+                }
+            }
+            KeeperRule::DeepestFolder => -(Path::new(&file.path).components().count() as i64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(path: &str, location_type: FileKrakenLocationType) -> FileKrakenLocation {
+        FileKrakenLocation {
+            path: path.to_string(),
+            location_type,
+            ..Default::default()
+        }
+    }
+
+    fn file(path: &str) -> FileKrakenFile {
+        FileKrakenFile {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn paths(files: &[FileKrakenFile]) -> Vec<&str> {
+        files.iter().map(|x| x.path.as_str()).collect()
+    }
+
+    #[test]
+    fn tied_preferred_copies_are_kept_and_the_rest_is_deletable() {
+        let selector = KeeperSelector {
+            rules: vec![(KeeperRule::PreferredLocation, None)],
+            locations: vec![
+                location("/preferred", FileKrakenLocationType::Preferred),
+                location("/normal", FileKrakenLocationType::Normal),
+            ],
+        };
+        let (kept, deletable) = selector.split(vec![
+            file("/preferred/a/photo.jpg"),
+            file("/normal/photo.jpg"),
+            file("/preferred/b/photo.jpg"),
+        ]);
+        assert_eq!(
+            paths(&kept),
+            ["/preferred/a/photo.jpg", "/preferred/b/photo.jpg"]
+        );
+        assert_eq!(paths(&deletable), ["/normal/photo.jpg"]);
+    }
+
+    #[test]
+    fn copies_in_lower_priority_locations_are_deletable() {
+        let selector = KeeperSelector {
+            rules: vec![(KeeperRule::HighestPriority, None)],
+            locations: vec![
+                FileKrakenLocation {
+                    priority: 2,
+                    ..location("/archive", FileKrakenLocationType::Normal)
+                },
+                FileKrakenLocation {
+                    priority: 1,
+                    ..location("/backup", FileKrakenLocationType::Normal)
+                },
+                location("/downloads", FileKrakenLocationType::Normal),
+            ],
+        };
+        let (kept, deletable) = selector.split(vec![
+            file("/downloads/photo.jpg"),
+            file("/archive/2020/photo.jpg"),
+            file("/backup/photo.jpg"),
+            file("/archive/copy/photo.jpg"),
+        ]);
+        assert_eq!(
+            paths(&kept),
+            ["/archive/2020/photo.jpg", "/archive/copy/photo.jpg"]
+        );
+        assert_eq!(
+            paths(&deletable),
+            ["/downloads/photo.jpg", "/backup/photo.jpg"]
+        );
+    }
+}
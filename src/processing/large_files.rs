@@ -0,0 +1,111 @@
+use crate::error::FileKrakenError;
+use crate::state::file::FileKrakenFile;
+use crate::state::AppState;
+use rusqlite::params_from_iter;
+use rusqlite::types::Value;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// A file of the large files view along with the location it belongs to
+#[derive(Debug, Clone)]
+pub struct LargeFile {
+    pub file: FileKrakenFile,
+    pub location_path: String,
+}
+
+/// Order of the large files view
+#[derive(PartialEq, Eq, Default, Debug, Clone, Copy)]
+pub enum LargeFilesOrder {
+    /// Largest first
+    #[default]
+    Size,
+    /// Least recently modified first
+    Age,
+    /// By location, largest first within each location
+    Location,
+}
+
+impl LargeFilesOrder {
+    pub const ALL: [LargeFilesOrder; 3] = [
+        LargeFilesOrder::Size,
+        LargeFilesOrder::Age,
+        LargeFilesOrder::Location,
+    ];
+}
+
+impl fmt::Display for LargeFilesOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LargeFilesOrder::Size => "size",
+                LargeFilesOrder::Age => "age",
+                LargeFilesOrder::Location => "location",
+            }
+        )
+    }
+}
+
+impl FromStr for LargeFilesOrder {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "size" => Ok(LargeFilesOrder::Size),
+            "age" => Ok(LargeFilesOrder::Age),
+            "location" => Ok(LargeFilesOrder::Location),
+            x => Err(FileKrakenError::UnknownLargeFilesOrder(x.to_string())),
+        }
+    }
+}
+
+/// Find the `limit` largest files of at least `min_size` bytes, in all locations if
+/// `location_paths` is empty, and sort them by `order`
+pub fn find_large_files(
+    app_state: &AppState,
+    min_size: u64,
+    location_paths: &[String],
+    order: LargeFilesOrder,
+    limit: usize,
+) -> Result<Vec<LargeFile>, FileKrakenError> {
+    let location_filter = if location_paths.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "AND location_path IN ({}) ",
+            vec!["?"; location_paths.len()].join(", ")
+        )
+    };
+    let mut params: Vec<Value> = vec![Value::Integer(min_size as i64)];
+    params.extend(location_paths.iter().map(|x| Value::Text(x.clone())));
+    params.push(Value::Integer(limit as i64));
+
+    // the largest files come straight from file_len_index
+    let mut large_files: Vec<LargeFile> = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .prepare(&format!(
+                "SELECT {}, location_path FROM files WHERE file_len >= ? {}\
+                ORDER BY file_len DESC LIMIT ?;",
+                FileKrakenFile::SQL_COLUMNS,
+                location_filter
+            ))?
+            .query_map(params_from_iter(params), |row| {
+                Ok(LargeFile {
+                    file: FileKrakenFile::from_row(row)?,
+                    location_path: row.get("location_path")?,
+                })
+            })?
+            .collect()
+    })?;
+
+    match order {
+        LargeFilesOrder::Size => {}
+        LargeFilesOrder::Age => large_files.sort_by_key(|x| x.file.time_modified),
+        LargeFilesOrder::Location => {
+            large_files.sort_by(|a, b| a.location_path.cmp(&b.location_path))
+        }
+    }
+    Ok(large_files)
+}
@@ -1,2 +1,13 @@
+pub mod disk_usage;
+pub mod duplicate_folders;
+pub mod empty_folders;
+pub mod file_types;
 pub mod find_duplicates;
+pub mod keeper;
+pub mod large_files;
+pub mod resolve;
 pub mod scan;
+pub mod similar_images;
+pub mod statistics;
+pub mod undo;
+pub mod verify;
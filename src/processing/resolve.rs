@@ -0,0 +1,148 @@
+use crate::error::FileKrakenError;
+use crate::processing::find_duplicates::{
+    delete_duplicate, flag_duplicate, remove_resolved_file, verify_flagging_failures,
+};
+use crate::state::duplicate::{DuplicateResolution, FileKrakenDuplicate, FileKrakenDuplicateType};
+use crate::state::file::FileKrakenFile;
+use crate::state::journal::{JournalAction, JournalEntry};
+use crate::state::AppState;
+use crate::utils::dedupe::dedupe_file;
+use crate::utils::hardlink::replace_with_hardlink;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Resolve a deletable file of a duplicate group using the project's
+/// [`DuplicateResolution`], recording it in the journal under `operation_id`
+pub fn resolve_duplicate(
+    app_state: &Arc<AppState>,
+    duplicate: &FileKrakenDuplicate,
+    deletable_file: &FileKrakenFile,
+    operation_id: i64,
+) -> Result<(), FileKrakenError> {
+    if deletable_file.is_archive_member() {
+        return Err(FileKrakenError::ArchiveMemberNotDeletable(
+            deletable_file.path.clone(),
+        ));
+    }
+    match app_state.get_duplicate_resolution()? {
+        DuplicateResolution::Delete => {
+            delete_duplicate(app_state, duplicate, deletable_file, operation_id)
+        }
+        DuplicateResolution::Hardlink => {
+            hardlink_duplicate(app_state, duplicate, deletable_file, operation_id)
+        }
+        DuplicateResolution::Reflink => {
+            reflink_duplicate(app_state, duplicate, deletable_file, operation_id)
+        }
+    }
+}
+
+/// Replace a deletable file of a duplicate group with a hard link to the kept file it
+/// was verified against
+pub fn hardlink_duplicate(
+    app_state: &Arc<AppState>,
+    duplicate: &FileKrakenDuplicate,
+    deletable_file: &FileKrakenFile,
+    operation_id: i64,
+) -> Result<(), FileKrakenError> {
+    let kept_file = verify_linkable(app_state, duplicate, deletable_file)?;
+
+    replace_with_hardlink(Path::new(&deletable_file.path), Path::new(&kept_file.path))
+        .map_err(|err| FileKrakenError::io(&deletable_file.path, err))?;
+    app_state.record_journal_entry(
+        operation_id,
+        &JournalEntry {
+            destination: Some(kept_file.path.clone()),
+            ..JournalEntry::for_file(JournalAction::Hardlinked, deletable_file)
+        },
+    )?;
+    // the path now shares the kept file's inode and timestamps
+    app_state.add_file_with_hash(
+        &deletable_file.path,
+        kept_file.hash.clone(),
+        kept_file.hash_algorithm,
+    )?;
+
+    *app_state
+        .find_duplicates_processing
+        .bytes_reclaimed
+        .write()
+        .unwrap() += deletable_file.file_len;
+    remove_resolved_file(app_state, &deletable_file.path);
+    Ok(())
+}
+
+/// Let a deletable file of a duplicate group share the extents of the kept file it was
+/// verified against.
+///
+/// If the filesystem cannot deduplicate the files, both are left untouched and the file
+/// is flagged with the reason.
+pub fn reflink_duplicate(
+    app_state: &Arc<AppState>,
+    duplicate: &FileKrakenDuplicate,
+    deletable_file: &FileKrakenFile,
+    operation_id: i64,
+) -> Result<(), FileKrakenError> {
+    let kept_file = verify_linkable(app_state, duplicate, deletable_file)?;
+
+    if let Err(err) = dedupe_file(Path::new(&kept_file.path), Path::new(&deletable_file.path)) {
+        let reason = err.to_string();
+        flag_duplicate(app_state, &deletable_file.path, &reason);
+        return Err(FileKrakenError::DedupeFailed {
+            path: deletable_file.path.clone(),
+            reason,
+        });
+    }
+    app_state.record_journal_entry(
+        operation_id,
+        &JournalEntry {
+            destination: Some(kept_file.path.clone()),
+            ..JournalEntry::for_file(JournalAction::Reflinked, deletable_file)
+        },
+    )?;
+
+    *app_state
+        .find_duplicates_processing
+        .bytes_reclaimed
+        .write()
+        .unwrap() += deletable_file.file_len;
+    remove_resolved_file(app_state, &deletable_file.path);
+    Ok(())
+}
+
+/// Verify a deletable file like before deleting it, but also require the kept file to be
+/// a regular file with the same content, as neither archive members, archives with the
+/// same members nor similar images can be linked to
+fn verify_linkable<'a>(
+    app_state: &Arc<AppState>,
+    duplicate: &'a FileKrakenDuplicate,
+    deletable_file: &FileKrakenFile,
+) -> Result<&'a FileKrakenFile, FileKrakenError> {
+    let kept_file = verify_flagging_failures(app_state, duplicate, deletable_file)?;
+    let reason = if duplicate.duplicate_type == FileKrakenDuplicateType::ArchiveContent {
+        Some(format!(
+            "{} only has the same members, it can't be linked to",
+            kept_file.path
+        ))
+    } else if duplicate.duplicate_type == FileKrakenDuplicateType::SimilarImage {
+        Some(format!(
+            "{} only looks similar, it can't be linked to",
+            kept_file.path
+        ))
+    } else if kept_file.is_archive_member() {
+        Some(format!(
+            "{} is inside an archive, it can't be linked to",
+            kept_file.path
+        ))
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        flag_duplicate(app_state, &deletable_file.path, &reason);
+        return Err(FileKrakenError::VerificationFailed {
+            path: deletable_file.path.clone(),
+            reason,
+        });
+    }
+    Ok(kept_file)
+}
@@ -1,108 +1,322 @@
-use crate::state::file::FileKrakenFileType;
-use crate::state::location::FileKrakenLocationState;
+use crate::error::FileKrakenError;
+use crate::state::archive::ArchiveMember;
+use crate::state::file::{FileChange, FileKrakenFile, FileKrakenFileType};
+use crate::state::location::{FileKrakenLocationState, FileKrakenLocationType};
 use crate::state::AppState;
-use crate::utils::dialogs::error_dialog;
+use crate::utils::archive::{archive_member_path, is_readable_archive, read_archive};
+use crate::utils::hashing::HashAlgorithm;
+use crate::utils::is_path_parent;
+use crate::utils::magic::read_mime_type;
 use jwalk::WalkDir;
 use log::error;
-use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::UNIX_EPOCH;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn scan_location_files(app_state: Arc<AppState>, location_path: &str) {
-    let current_state = app_state
+/// Outcome of a location scan
+#[derive(Default, Debug, Clone)]
+pub struct ScanReport {
+    /// Number of files found on disk
+    pub nr_files: usize,
+    /// Number of files that were not in the project before
+    pub nr_added: usize,
+    /// Number of files whose size or modification time changed since the last scan
+    pub nr_modified: usize,
+    /// Number of files that were in the project but no longer exist on disk or are ignored
+    pub nr_removed: usize,
+    /// Number of archives whose members were read
+    pub nr_archives_read: usize,
+    /// Paths that could not be read or are not valid UTF-8
+    pub failed_paths: Vec<String>,
+}
+
+/// Number of files written to the project database per transaction
+const SCAN_BATCH_SIZE: usize = 1000;
+
+pub fn scan_location_files(
+    app_state: Arc<AppState>,
+    location_path: &str,
+) -> Result<ScanReport, FileKrakenError> {
+    let location = app_state
         .get_location_clone(location_path)
-        .unwrap()
-        .location_state;
-    if current_state == FileKrakenLocationState::Scanning {
-        return error_dialog("Already scanning this location");
+        .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?;
+    if location.location_type == FileKrakenLocationType::Excluded {
+        return Err(FileKrakenError::LocationExcluded(location_path.to_string()));
     }
-    app_state.modify_location_state(true, location_path, FileKrakenLocationState::Scanning);
-
-    let mut failed_paths = Vec::new();
-    for entry in WalkDir::new(location_path) {
-        if let Ok(entry) = entry {
-            if entry.file_type.is_file() {
-                let file_type = if let Some(file_extension) =
-                    entry.path().extension().and_then(|x| x.to_str())
-                {
-                    if [".tar.xz", ".zip", ".7z"].contains(&file_extension) {
-                        FileKrakenFileType::Archive
-                    } else {
-                        FileKrakenFileType::Normal
-                    }
-                } else {
-                    FileKrakenFileType::Normal
-                };
-                let file_metadata = entry.metadata().expect(&format!(
-                    "Failed to get file metadata for file {:?}",
-                    entry.path()
-                ));
-
-                if let Some(file_path) = entry.path().to_str() {
-                    app_state.add_file(
-                        true,
-                        file_path,
-                        &file_type,
-                        file_metadata.len(),
-                        file_metadata
-                            .created()
-                            .unwrap()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap_or(std::time::Duration::new(0, 0))
-                            .as_secs(),
-                        file_metadata
-                            .modified()
-                            .unwrap()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap_or(std::time::Duration::new(0, 0))
-                            .as_secs(),
-                        None,
-                    );
-                } else {
+    if location.location_state == FileKrakenLocationState::Scanning {
+        return Err(FileKrakenError::AlreadyScanning(location_path.to_string()));
+    }
+    app_state.modify_location_state(true, location_path, FileKrakenLocationState::Scanning)?;
+
+    let result = run_scan_location_files(&app_state, location_path);
+
+    app_state.modify_location_state(
+        true,
+        location_path,
+        if result.is_ok() {
+            FileKrakenLocationState::Scanned
+        } else {
+            FileKrakenLocationState::PartialScanned
+        },
+    )?;
+    result
+}
+
+fn run_scan_location_files(
+    app_state: &Arc<AppState>,
+    location_path: &str,
+) -> Result<ScanReport, FileKrakenError> {
+    let ignore_matcher = Arc::new(app_state.get_ignore_matcher(location_path)?);
+    let excluded_paths: Vec<PathBuf> = app_state
+        .get_locations_list_readonly()
+        .iter()
+        .filter(|x| x.location_type == FileKrakenLocationType::Excluded)
+        .map(|x| PathBuf::from(&x.path))
+        .collect();
+
+    let walk_ignore_matcher = ignore_matcher.clone();
+    let walk_dir = WalkDir::new(location_path).process_read_dir(
+        move |_depth, _path, _read_dir_state, children| {
+            // prune excluded locations and ignored entries before descending into them
+            children.retain(|entry| {
+                entry.as_ref().map_or(true, |entry| {
+                    let path = entry.path();
+                    let is_dir = entry.file_type.is_dir();
+                    let is_excluded = is_dir && excluded_paths.contains(&path);
+                    !is_excluded && !walk_ignore_matcher.is_ignored(&path, is_dir)
+                })
+            });
+        },
+    );
+
+    let known_types = get_known_file_types(app_state, location_path);
+
+    let mut report = ScanReport::default();
+    let mut batch = Vec::with_capacity(SCAN_BATCH_SIZE);
+    let mut folder_paths = vec![];
+    let mut archive_paths = vec![];
+    let mut seen_paths = HashSet::new();
+    let mut unreadable_paths = vec![];
+    for entry in walk_dir {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                error!("Failed to read {:?}: {}", err.path(), err);
+                unreadable_paths.extend(err.path().and_then(|x| x.to_str()).map(String::from));
+                continue;
+            }
+        };
+        // the location itself is not one of its folders
+        if entry.file_type.is_dir() && entry.depth > 0 {
+            if let Some(folder_path) = entry.path().to_str() {
+                folder_paths.push(folder_path.to_string());
+            }
+        } else if entry.file_type.is_file() {
+            if let Some(file_path) = entry.path().to_str() {
+                seen_paths.insert(file_path.to_string());
+            }
+            let file_metadata = match entry.metadata() {
+                Ok(x) => x,
+                Err(err) => {
                     error!(
-                        "Failed to get file path for file {:?}",
-                        entry.path().to_string_lossy().as_ref()
+                        "Failed to get file metadata for file {:?}: {}",
+                        entry.path(),
+                        err
                     );
-                    failed_paths.push(entry.path().to_string_lossy().to_string());
+                    report
+                        .failed_paths
+                        .push(entry.path().to_string_lossy().to_string());
+                    continue;
                 }
+            };
+
+            if let Some(file_path) = entry.path().to_str() {
+                let file_len = file_metadata.len();
+                let time_modified = unix_secs(file_metadata.modified());
+                // only new and changed content is read to detect its type
+                let (file_type, mime_type) = match known_types.get(file_path) {
+                    Some((known_len, known_modified, file_type, Some(mime_type)))
+                        if *known_len == file_len && *known_modified == time_modified =>
+                    {
+                        (file_type.clone(), Some(mime_type.clone()))
+                    }
+                    _ => match read_mime_type(&entry.path()) {
+                        Ok(mime_type) => (
+                            FileKrakenFileType::from_mime_type(mime_type),
+                            Some(mime_type.to_string()),
+                        ),
+                        Err(err) => {
+                            error!("Failed to read file type of {:?}: {}", entry.path(), err);
+                            (FileKrakenFileType::from_path(&entry.path()), None)
+                        }
+                    },
+                };
+                if mime_type.as_deref().is_some_and(is_readable_archive) {
+                    archive_paths.push(file_path.to_string());
+                }
+                batch.push(
+                    FileKrakenFile {
+                        path: file_path.to_string(),
+                        file_type,
+                        mime_type,
+                        file_len,
+                        time_created: unix_secs(file_metadata.created()),
+                        time_modified,
+                        ..Default::default()
+                    }
+                    .with_inode(&file_metadata),
+                );
+                if batch.len() >= SCAN_BATCH_SIZE {
+                    add_scanned_files(app_state, &mut report, std::mem::take(&mut batch))?;
+                    app_state.report_progress(&format!(
+                        "Scanning {} ... {} files",
+                        location_path, report.nr_files
+                    ));
+                }
+            } else {
+                error!(
+                    "Failed to get file path for file {:?}",
+                    entry.path().to_string_lossy().as_ref()
+                );
+                report
+                    .failed_paths
+                    .push(entry.path().to_string_lossy().to_string());
             }
         }
     }
+    add_scanned_files(app_state, &mut report, batch)?;
+    app_state.set_location_folders(location_path, &folder_paths)?;
 
-    if !failed_paths.is_empty() {
-        if failed_paths.len() > 10 {
-            error_dialog(&format!(
-                "Failed to get file path for {} files. Example: {}",
-                failed_paths.len(),
-                failed_paths[0]
-            ));
-        } else {
-            for failed_path in failed_paths {
-                error_dialog(&format!("Failed to get file path for file {}", failed_path));
+    // files that were not found are gone or ignored by now, unless they are below a folder
+    // that couldn't be read
+    let stored_paths: Vec<String> = app_state.with_sqlite(|sqlite| {
+        let mut files_query = sqlite.prepare("SELECT path FROM files WHERE location_path = ?")?;
+        let files = files_query
+            .query_map([location_path], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(files)
+    })?;
+    let removed_paths: Vec<String> = stored_paths
+        .into_iter()
+        .filter(|path| !seen_paths.contains(path))
+        .filter(|path| {
+            ignore_matcher.is_file_ignored(Path::new(path))
+                || !unreadable_paths
+                    .iter()
+                    .any(|x| x == path || is_path_parent(path, x))
+        })
+        .collect();
+    app_state.remove_files(true, &removed_paths)?;
+    report.nr_removed += removed_paths.len();
+
+    // changed archives lost their members when they were updated above
+    if app_state.get_scan_archives()? {
+        let hash_algorithm = app_state.get_hash_algorithm()?;
+        let read_archives = app_state.get_read_archives(hash_algorithm)?;
+        for archive_path in archive_paths.iter().filter(|x| !read_archives.contains(*x)) {
+            app_state.report_progress(&format!("Reading archive {}", archive_path));
+            match read_archive_members(archive_path, hash_algorithm) {
+                Ok(members) => {
+                    app_state.set_archive_members(archive_path, &members)?;
+                    report.nr_archives_read += 1;
+                }
+                Err(err) => {
+                    error!("Failed to read archive {}: {}", archive_path, err);
+                    report.failed_paths.push(archive_path.clone());
+                }
             }
         }
     }
 
-    // check if files were removed
-    let files: Vec<String> = {
-        let sqlite_lock = app_state.sqlite.lock().unwrap();
-        let mut files_query = sqlite_lock
-            .as_ref()
-            .unwrap()
-            .prepare("SELECT path FROM files WHERE location_path = ?")
-            .unwrap();
-        files_query
-            .query_map(&[&location_path], |row| row.get(0))
-            .unwrap()
-            .map(|x| x.unwrap())
-            .collect()
-    };
-    for file in files {
-        // check filesystem
-        if !std::path::Path::new(&file).exists() {
-            app_state.remove_file(true, false, &file);
+    Ok(report)
+}
+
+/// Hash all members of an archive, a name stored more than once refers to its last entry
+/// like when extracting
+pub(crate) fn read_archive_members(
+    archive_path: &str,
+    hash_algorithm: HashAlgorithm,
+) -> io::Result<Vec<ArchiveMember>> {
+    let mut members: Vec<ArchiveMember> = vec![];
+    let mut member_indexes: HashMap<String, usize> = HashMap::new();
+    read_archive(Path::new(archive_path), |entry, content| {
+        let mut hasher = hash_algorithm.hasher();
+        let file_len = io::copy(content, &mut hasher)?;
+        let path = archive_member_path(archive_path, &entry.name);
+        let member = ArchiveMember {
+            path: path.clone(),
+            archive_path: archive_path.to_string(),
+            file_len,
+            time_modified: entry.time_modified,
+            hash: hasher.finalize_hex(),
+            hash_algorithm,
+        };
+        match member_indexes.get(&path) {
+            Some(index) => members[*index] = member,
+            None => {
+                member_indexes.insert(path, members.len());
+                members.push(member);
+            }
+        }
+        Ok(())
+    })?;
+    Ok(members)
+}
+
+/// Size, modification time and detected type of the files already known below a location,
+/// including the files of nested locations
+fn get_known_file_types(
+    app_state: &AppState,
+    location_path: &str,
+) -> HashMap<String, (u64, u64, FileKrakenFileType, Option<String>)> {
+    let location_paths: Vec<String> = app_state
+        .get_locations_list_readonly()
+        .iter()
+        .filter(|x| x.path == location_path || is_path_parent(&x.path, location_path))
+        .map(|x| x.path.clone())
+        .collect();
+    let mut known_types = HashMap::new();
+    for location_files in location_paths
+        .iter()
+        .filter_map(|x| app_state.get_files_by_location(x))
+    {
+        for file in location_files.read().unwrap().values() {
+            known_types.insert(
+                file.path.clone(),
+                (
+                    file.file_len,
+                    file.time_modified,
+                    file.file_type.clone(),
+                    file.mime_type.clone(),
+                ),
+            );
         }
     }
+    known_types
+}
+
+fn add_scanned_files(
+    app_state: &Arc<AppState>,
+    report: &mut ScanReport,
+    files: Vec<FileKrakenFile>,
+) -> Result<(), FileKrakenError> {
+    for change in app_state.add_files(true, files)? {
+        report.nr_files += 1;
+        match change {
+            FileChange::Added => report.nr_added += 1,
+            FileChange::Modified => report.nr_modified += 1,
+            FileChange::Unchanged => {}
+        }
+    }
+    Ok(())
+}
 
-    app_state.modify_location_state(true, location_path, FileKrakenLocationState::Scanned);
+/// Seconds since the unix epoch, or 0 if the platform does not provide the timestamp
+pub(crate) fn unix_secs(time: io::Result<SystemTime>) -> u64 {
+    time.ok()
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
 }
@@ -0,0 +1,236 @@
+use crate::error::FileKrakenError;
+use crate::processing::find_duplicates::{collapse_hard_links, set_processing_message};
+use crate::state::duplicate::{FileKrakenDuplicate, FileKrakenDuplicateType};
+use crate::state::file::FileKrakenFile;
+use crate::state::AppState;
+use crate::utils::image_hash::{is_hashable_image, ImageHashAlgorithm};
+use log::error;
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Group images whose perceptual hashes differ in at most `threshold` bits, e.g. resized or
+/// re-encoded copies of the same picture.
+///
+/// Similar images differ as files, so all files of a group are kept until some are marked
+/// as deletable by hand. Files that are already deletable duplicates in `exact_duplicates`
+/// are left out.
+pub(crate) fn find_similar_images(
+    app_state: &Arc<AppState>,
+    exact_duplicates: &[FileKrakenDuplicate],
+    algorithm: ImageHashAlgorithm,
+    threshold: u32,
+) -> Result<Vec<FileKrakenDuplicate>, FileKrakenError> {
+    let deletable_paths: HashSet<&str> = exact_duplicates
+        .iter()
+        .flat_map(|x| x.deletable_files.iter())
+        .map(|x| x.path.as_str())
+        .collect();
+    let images: Vec<FileKrakenFile> = get_image_files(app_state)?
+        .into_iter()
+        .filter(|x| !deletable_paths.contains(x.path.as_str()))
+        .collect();
+    let hashed_images = hash_images(app_state, collapse_hard_links(images), algorithm)?;
+
+    // union-find over the pairs of images that are close enough, looked up in a BK-tree
+    // instead of comparing all pairs
+    let mut tree = BkTree::default();
+    for (i, (_, hash)) in hashed_images.iter().enumerate() {
+        tree.insert(*hash, i);
+    }
+    let mut parents: Vec<usize> = (0..hashed_images.len()).collect();
+    for (i, (_, hash)) in hashed_images.iter().enumerate() {
+        if i % 1000 == 0 {
+            set_processing_message(
+                app_state,
+                format!(
+                    "Similar images | {:.2}% | Comparing {} images",
+                    i as f64 * 100.0 / hashed_images.len() as f64,
+                    hashed_images.len()
+                ),
+            );
+        }
+        for j in tree.find(*hash, threshold) {
+            let root_i = find_root(&mut parents, i);
+            let root_j = find_root(&mut parents, j);
+            parents[root_i.max(root_j)] = root_i.min(root_j);
+        }
+    }
+    let mut groups: HashMap<usize, Vec<FileKrakenFile>> = HashMap::new();
+    for (i, (file, _)) in hashed_images.into_iter().enumerate() {
+        let root = find_root(&mut parents, i);
+        groups.entry(root).or_default().push(file);
+    }
+
+    Ok(groups
+        .into_values()
+        .filter(|files| files.len() > 1)
+        // copies with the same content are already reported as exact duplicates
+        .filter(|files| {
+            files
+                .iter()
+                .any(|x| x.hash.is_none() || x.hash != files[0].hash)
+        })
+        .map(|files| FileKrakenDuplicate {
+            other_files: files,
+            deletable_files: vec![],
+            duplicate_type: FileKrakenDuplicateType::SimilarImage,
+            verification_error: None,
+        })
+        .collect())
+}
+
+/// Files of the project detected as images that can be decoded
+fn get_image_files(app_state: &AppState) -> Result<Vec<FileKrakenFile>, FileKrakenError> {
+    let files: Vec<FileKrakenFile> = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .prepare(&format!(
+                "SELECT {} FROM files WHERE mime_type LIKE 'image/%' ORDER BY path",
+                FileKrakenFile::SQL_COLUMNS
+            ))?
+            .query_map([], FileKrakenFile::from_row)?
+            .collect()
+    })?;
+    Ok(files
+        .into_iter()
+        .filter(|x| x.mime_type.as_deref().is_some_and(is_hashable_image))
+        .collect())
+}
+
+/// Calculate the perceptual hashes of images on multiple threads, leaving out images that
+/// can't be read or decoded
+fn hash_images(
+    app_state: &Arc<AppState>,
+    mut images: Vec<FileKrakenFile>,
+    algorithm: ImageHashAlgorithm,
+) -> Result<Vec<(FileKrakenFile, u64)>, FileKrakenError> {
+    let hashed_images: Arc<RwLock<Vec<(FileKrakenFile, u64)>>> = Arc::default();
+    let nr_images = images.len();
+    let nr_images_hashed = Arc::new(RwLock::new(0usize));
+    let mut threads = vec![];
+    for images in images
+        .chunks_mut(max(1, nr_images / 4))
+        .map(|x| x.to_owned())
+    {
+        let app_state = app_state.clone();
+        let hashed_images = hashed_images.clone();
+        let nr_images_hashed = nr_images_hashed.clone();
+        threads.push(std::thread::spawn(
+            move || -> Result<(), FileKrakenError> {
+                for image in images {
+                    let progress =
+                        *nr_images_hashed.read().unwrap() as f64 * 100.0 / nr_images as f64;
+                    set_processing_message(
+                        &app_state,
+                        format!(
+                            "Similar images | {:.2}% | Calculating image hashes of {} images",
+                            progress, nr_images
+                        ),
+                    );
+                    *nr_images_hashed.write().unwrap() += 1;
+                    let hash = match app_state.calculate_image_hash(&image.path, algorithm) {
+                        Ok(Some(hash)) => hash,
+                        Ok(None) => continue,
+                        Err(err @ FileKrakenError::Io { .. }) => {
+                            error!("Skipping image: {}", err);
+                            continue;
+                        }
+                        Err(err) => return Err(err),
+                    };
+                    if let Ok(hash) = u64::from_str_radix(&hash, 16) {
+                        hashed_images.write().unwrap().push((image, hash));
+                    }
+                }
+                Ok(())
+            },
+        ));
+    }
+    for thread in threads {
+        thread
+            .join()
+            .expect("Failed to join image hashing thread")?;
+    }
+
+    let mut hashed_images = std::mem::take(&mut *hashed_images.write().unwrap());
+    hashed_images.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+    Ok(hashed_images)
+}
+
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Node of a [`BkTree`]
+struct BkNode {
+    hash: u64,
+    /// Indexes of the images with this hash
+    indexes: Vec<usize>,
+    /// Child nodes by the distance of their hash to this one
+    children: HashMap<u32, usize>,
+}
+
+/// Metric tree over perceptual hashes with the number of differing bits as distance, the
+/// triangle inequality rules out most subtrees when looking for close hashes
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, index: usize) {
+        let new_node = BkNode {
+            hash,
+            indexes: vec![index],
+            children: HashMap::new(),
+        };
+        if self.nodes.is_empty() {
+            self.nodes.push(new_node);
+            return;
+        }
+        let mut node = 0;
+        loop {
+            let distance = (self.nodes[node].hash ^ hash).count_ones();
+            if distance == 0 {
+                self.nodes[node].indexes.push(index);
+                return;
+            }
+            match self.nodes[node].children.get(&distance) {
+                Some(&child) => node = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes[node].children.insert(distance, child);
+                    self.nodes.push(new_node);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Indexes of the images whose hashes differ from `hash` in at most `threshold` bits
+    fn find(&self, hash: u64, threshold: u32) -> Vec<usize> {
+        let mut found = vec![];
+        let mut stack: Vec<usize> = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let distance = (node.hash ^ hash).count_ones();
+            if distance <= threshold {
+                found.extend(&node.indexes);
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(x, _)| x.abs_diff(distance) <= threshold)
+                    .map(|(_, child)| *child),
+            );
+        }
+        found
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::FileKrakenError;
+use crate::processing::scan::unix_secs;
+use crate::state::file::FileCategory;
+use crate::state::AppState;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Buckets of the age histogram: label and the maximum age in days, `None` for no limit
+pub const AGE_BUCKETS: [(&str, Option<u64>); 7] = [
+    ("< 1 month", Some(30)),
+    ("1-6 months", Some(182)),
+    ("6-12 months", Some(365)),
+    ("1-2 years", Some(2 * 365)),
+    ("2-5 years", Some(5 * 365)),
+    ("5-10 years", Some(10 * 365)),
+    ("> 10 years", None),
+];
+
+/// Label of files without a modification time in the age histogram
+const UNKNOWN_AGE: &str = "unknown";
+
+/// Number and size of the files per lowercase extension without the dot. The extension is
+/// taken from the file name, so dots in folder names are skipped, and dotfiles like
+/// `.bashrc` have none.
+const SQL_FILE_EXTENSION: &str = "WITH names AS (\
+        SELECT file_len, substr(path, length(rtrim(path, replace(path, '/', ''))) + 1) AS name \
+        FROM files\
+    ) \
+    SELECT CASE WHEN instr(name, '.') > 1 \
+        THEN lower(substr(name, length(rtrim(name, replace(name, '.', ''))) + 1)) \
+        ELSE '' END AS extension, COUNT(*), COALESCE(SUM(file_len), 0) \
+    FROM names GROUP BY extension ORDER BY 3 DESC;";
+
+/// Number and total size of the files of one group, e.g. one extension
+#[derive(Debug, Clone)]
+pub struct StatisticsRow {
+    pub label: String,
+    pub nr_files: u64,
+    pub size: u64,
+}
+
+/// Breakdown of the files of the project, each group sorted largest first except the ages
+#[derive(Debug, Clone, Default)]
+pub struct FileStatistics {
+    pub nr_files: u64,
+    pub size: u64,
+    /// Lowercase extensions without the dot, files without one are labeled with an empty
+    /// string
+    pub by_extension: Vec<StatisticsRow>,
+    pub by_category: Vec<(FileCategory, StatisticsRow)>,
+    /// MIME types detected from the content, files not read yet are labeled with an empty
+    /// string
+    pub by_mime_type: Vec<StatisticsRow>,
+    pub by_location: Vec<StatisticsRow>,
+    /// One row per [`AGE_BUCKETS`] entry, youngest first, and one for unknown ages at the end
+    pub by_age: Vec<StatisticsRow>,
+}
+
+impl FileStatistics {
+    /// All groups as CSV with the columns group, label, files and bytes
+    pub fn to_csv(&self) -> String {
+        let total = StatisticsRow {
+            label: String::new(),
+            nr_files: self.nr_files,
+            size: self.size,
+        };
+        let rows = [("total", &total)]
+            .into_iter()
+            .chain(self.by_category.iter().map(|(_, x)| ("category", x)))
+            .chain(self.by_extension.iter().map(|x| ("extension", x)))
+            .chain(self.by_mime_type.iter().map(|x| ("mime_type", x)))
+            .chain(self.by_location.iter().map(|x| ("location", x)))
+            .chain(self.by_age.iter().map(|x| ("age", x)));
+
+        let mut csv = String::from("group,label,files,bytes\n");
+        for (group, row) in rows {
+            csv += &format!(
+                "{},{},{},{}\n",
+                group,
+                csv_field(&row.label),
+                row.nr_files,
+                row.size
+            );
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Count the files of the project per extension, category, content type, location and age
+pub fn get_file_statistics(app_state: &AppState) -> Result<FileStatistics, FileKrakenError> {
+    let row_of = |row: &rusqlite::Row| {
+        Ok(StatisticsRow {
+            label: row.get(0)?,
+            nr_files: row.get(1)?,
+            size: row.get(2)?,
+        })
+    };
+    let now = unix_secs(Ok(SystemTime::now()));
+    let age_bucket = AGE_BUCKETS
+        .iter()
+        .map(|(label, max_days)| match max_days {
+            Some(max_days) => format!(
+                "WHEN ?1 - time_modified < {} THEN '{}' ",
+                max_days * 24 * 60 * 60,
+                label
+            ),
+            None => format!("ELSE '{}' ", label),
+        })
+        .collect::<String>();
+
+    let (nr_files, size, by_extension, by_mime_type, by_location, ages) =
+        app_state.with_sqlite(|sqlite| {
+            let (nr_files, size) = sqlite.query_row(
+                "SELECT COUNT(*), COALESCE(SUM(file_len), 0) FROM files;",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            let by_extension: Vec<StatisticsRow> = sqlite
+                .prepare(SQL_FILE_EXTENSION)?
+                .query_map([], row_of)?
+                .collect::<Result<_, _>>()?;
+            let by_mime_type: Vec<StatisticsRow> = sqlite
+            .prepare(
+                "SELECT COALESCE(mime_type, ''), COUNT(*), COALESCE(SUM(file_len), 0) FROM files \
+                GROUP BY 1 ORDER BY 3 DESC;",
+            )?
+            .query_map([], row_of)?
+            .collect::<Result<_, _>>()?;
+            let by_location: Vec<StatisticsRow> = sqlite
+                .prepare(
+                    "SELECT location_path, COUNT(*), COALESCE(SUM(file_len), 0) FROM files \
+                GROUP BY location_path ORDER BY 3 DESC;",
+                )?
+                .query_map([], row_of)?
+                .collect::<Result<_, _>>()?;
+            let ages: Vec<StatisticsRow> = sqlite
+                .prepare(&format!(
+                    "SELECT CASE WHEN time_modified = 0 THEN '{}' {}END AS age, \
+                COUNT(*), COALESCE(SUM(file_len), 0) FROM files GROUP BY age;",
+                    UNKNOWN_AGE, age_bucket
+                ))?
+                .query_map([now as i64], row_of)?
+                .collect::<Result<_, _>>()?;
+            Ok((
+                nr_files,
+                size,
+                by_extension,
+                by_mime_type,
+                by_location,
+                ages,
+            ))
+        })?;
+
+    // categories are derived from the extensions
+    let mut by_category: HashMap<FileCategory, StatisticsRow> = HashMap::new();
+    for row in by_extension.iter() {
+        let category = FileCategory::from_extension(&row.label);
+        let category_row = by_category
+            .entry(category)
+            .or_insert_with(|| StatisticsRow {
+                label: category.to_string(),
+                nr_files: 0,
+                size: 0,
+            });
+        category_row.nr_files += row.nr_files;
+        category_row.size += row.size;
+    }
+    let mut by_category: Vec<(FileCategory, StatisticsRow)> = by_category.into_iter().collect();
+    by_category.sort_by_key(|(_, x)| std::cmp::Reverse(x.size));
+
+    // every bucket is listed, even without files
+    let by_age = AGE_BUCKETS
+        .iter()
+        .map(|(label, _)| *label)
+        .chain([UNKNOWN_AGE])
+        .map(|label| {
+            ages.iter()
+                .find(|x| x.label == label)
+                .cloned()
+                .unwrap_or(StatisticsRow {
+                    label: label.to_string(),
+                    nr_files: 0,
+                    size: 0,
+                })
+        })
+        .collect();
+
+    Ok(FileStatistics {
+        nr_files,
+        size,
+        by_extension,
+        by_category,
+        by_mime_type,
+        by_location,
+        by_age,
+    })
+}
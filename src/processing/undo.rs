@@ -0,0 +1,162 @@
+use crate::error::FileKrakenError;
+use crate::state::journal::{JournalAction, JournalEntry, JournalOperation};
+use crate::state::location::FileKrakenLocationState;
+use crate::state::AppState;
+use crate::utils::hardlink::replace_with_copy;
+use crate::utils::trash::{restore_from_trash, TrashedFile};
+use rusqlite::OptionalExtension;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Result of undoing an operation of the journal
+#[derive(Debug, Clone)]
+pub struct UndoReport {
+    pub operation: JournalOperation,
+    pub nr_reverted: usize,
+    /// Why single entries of the operation could not be reverted
+    pub not_reverted: Vec<String>,
+    /// Whether the operation was marked as undone, which only happens if anything could be
+    /// reverted
+    pub undone: bool,
+}
+
+/// Revert the latest operation of the journal that was not undone yet.
+///
+/// Trashed and quarantined files are moved back, hard links are replaced with copies,
+/// removed folders are created again and removed locations are added again with their
+/// priority and ignore patterns, to be scanned again. Reflinked files are independent files
+/// already and need no reverting, permanently deleted files are only reported.
+///
+/// The operation stays undoable if none of its entries could be reverted, e.g. if all of
+/// its files were deleted permanently. Returns `None` if there is nothing to undo.
+pub fn undo_last_operation(app_state: &AppState) -> Result<Option<UndoReport>, FileKrakenError> {
+    let Some(operation) = get_last_undoable_operation(app_state)? else {
+        return Ok(None);
+    };
+
+    let entries = app_state.get_journal_entries(operation.id)?;
+    let mut nr_reverted = 0;
+    let mut not_reverted = vec![];
+    for entry in entries.iter().rev() {
+        match revert_entry(app_state, entry) {
+            Ok(()) => nr_reverted += 1,
+            Err(err) => not_reverted.push(format!("{}: {}", entry.original_path, err)),
+        }
+    }
+    let undone = nr_reverted > 0 || entries.is_empty();
+    if undone {
+        app_state.set_operation_undone(operation.id)?;
+    }
+
+    Ok(Some(UndoReport {
+        operation,
+        nr_reverted,
+        not_reverted,
+        undone,
+    }))
+}
+
+/// The operation [`undo_last_operation`] would revert
+pub fn get_last_undoable_operation(
+    app_state: &AppState,
+) -> Result<Option<JournalOperation>, FileKrakenError> {
+    app_state.with_sqlite(|sqlite| {
+        sqlite
+            .query_row(
+                &format!(
+                    "SELECT {} FROM operations WHERE undone = 0 ORDER BY id DESC LIMIT 1;",
+                    JournalOperation::SQL_COLUMNS
+                ),
+                [],
+                JournalOperation::from_row,
+            )
+            .optional()
+    })
+}
+
+fn revert_entry(app_state: &AppState, entry: &JournalEntry) -> Result<(), String> {
+    match entry.action {
+        JournalAction::Deleted => Err("deleted permanently".to_string()),
+        JournalAction::Trashed => {
+            let (Some(destination), Some(info_path)) = (&entry.destination, &entry.trash_info_path)
+            else {
+                return Err("trash location was not recorded".to_string());
+            };
+            let trashed_file = TrashedFile {
+                path: PathBuf::from(destination),
+                info_path: PathBuf::from(info_path),
+            };
+            restore_from_trash(&trashed_file, Path::new(&entry.original_path))
+                .map_err(|err| format!("failed to restore from trash: {}", err))?;
+            app_state
+                .add_file_with_hash(
+                    &entry.original_path,
+                    entry.hash.clone(),
+                    entry.hash_algorithm,
+                )
+                .map_err(|err| err.to_string())
+        }
+        JournalAction::Quarantined => {
+            let quarantine_id: Option<i64> = app_state
+                .with_sqlite(|sqlite| {
+                    sqlite
+                        .query_row(
+                            "SELECT id FROM quarantine WHERE quarantine_path = ?1;",
+                            [&entry.destination],
+                            |x| x.get(0),
+                        )
+                        .optional()
+                })
+                .map_err(|err| err.to_string())?;
+            let quarantine_id =
+                quarantine_id.ok_or_else(|| "no longer in quarantine".to_string())?;
+            app_state
+                .restore_quarantined_file(quarantine_id)
+                .map_err(|err| err.to_string())
+        }
+        JournalAction::Hardlinked => {
+            // the linked files are identical, so a copy restores the separate file
+            replace_with_copy(Path::new(&entry.original_path))
+                .map_err(|err| format!("failed to replace hard link with a copy: {}", err))?;
+            app_state
+                .add_file_with_hash(
+                    &entry.original_path,
+                    entry.hash.clone(),
+                    entry.hash_algorithm,
+                )
+                .map_err(|err| err.to_string())
+        }
+        // the file kept its own inode and contents, only its extents are shared
+        JournalAction::Reflinked => Ok(()),
+        JournalAction::FolderRemoved => {
+            fs::create_dir_all(&entry.original_path)
+                .map_err(|err| format!("failed to create folder: {}", err))?;
+            app_state
+                .add_folder(&entry.original_path)
+                .map_err(|err| err.to_string())
+        }
+        JournalAction::LocationRemoved => {
+            app_state
+                .add_location(
+                    true,
+                    &entry.original_path,
+                    &entry.location_type.clone().unwrap_or_default(),
+                    &FileKrakenLocationState::Unscanned,
+                )
+                .map_err(|err| err.to_string())?;
+            if let Some(priority) = entry.location_priority {
+                app_state
+                    .set_location_priority(true, &entry.original_path, priority)
+                    .map_err(|err| format!("failed to restore priority: {}", err))?;
+            }
+            for pattern in entry.ignore_patterns.iter() {
+                app_state
+                    .add_ignore_pattern(Some(&entry.original_path), pattern)
+                    .map_err(|err| {
+                        format!("failed to restore ignore pattern {}: {}", pattern, err)
+                    })?;
+            }
+            Ok(())
+        }
+    }
+}
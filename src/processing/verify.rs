@@ -0,0 +1,284 @@
+use crate::error::FileKrakenError;
+use crate::processing::scan::{read_archive_members, unix_secs};
+use crate::state::archive::ArchiveMember;
+use crate::state::duplicate::{FileKrakenDuplicate, FileKrakenDuplicateType};
+use crate::state::file::FileKrakenFile;
+use crate::state::AppState;
+use crate::utils::archive::read_archive;
+use rusqlite::OptionalExtension;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+const COMPARE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Make sure a deletable file of a duplicate group can be removed without losing data,
+/// returning the kept file it is identical to.
+///
+/// Stored hashes may be stale, so right before deleting, the files are checked against
+/// their database rows and the deletable file is compared byte for byte with a kept file.
+/// Kept archive members are read from their archive, and archives with the same content
+/// are compared by the names and hashes of their members. Similar images are only checked
+/// to be unchanged.
+pub fn verify_duplicate<'a>(
+    app_state: &AppState,
+    duplicate: &'a FileKrakenDuplicate,
+    deletable_file: &FileKrakenFile,
+) -> Result<&'a FileKrakenFile, FileKrakenError> {
+    let failed = |reason: String| FileKrakenError::VerificationFailed {
+        path: deletable_file.path.clone(),
+        reason,
+    };
+
+    if let Some(reason) = check_unchanged(app_state, deletable_file)? {
+        return Err(failed(reason));
+    }
+
+    // files outside of archives are the cheapest to compare
+    let kept_files = duplicate
+        .other_files
+        .iter()
+        .filter(|x| !x.is_archive_member())
+        .chain(
+            duplicate
+                .other_files
+                .iter()
+                .filter(|x| x.is_archive_member()),
+        );
+    let mut reasons = vec![];
+    for kept_file in kept_files {
+        let unchanged = if kept_file.is_archive_member() {
+            check_member_unchanged(app_state, kept_file)?
+        } else {
+            check_unchanged(app_state, kept_file)?
+        };
+        if let Some(reason) = unchanged {
+            reasons.push(reason);
+            continue;
+        }
+        let identical = match duplicate.duplicate_type {
+            FileKrakenDuplicateType::ArchiveContent => {
+                archive_contents_are_identical(app_state, &deletable_file.path, &kept_file.path)
+            }
+            // similar images differ by design, it's enough that both are still the images
+            // that were compared
+            FileKrakenDuplicateType::SimilarImage => Ok(true),
+            FileKrakenDuplicateType::ExactMatch if kept_file.is_archive_member() => {
+                member_is_identical(app_state, &kept_file.path, &deletable_file.path)
+            }
+            FileKrakenDuplicateType::ExactMatch => {
+                files_are_identical(&deletable_file.path, &kept_file.path)
+            }
+        };
+        match identical {
+            Ok(true) => return Ok(kept_file),
+            Ok(false) => reasons.push(format!("content differs from {}", kept_file.path)),
+            Err(err) => reasons.push(format!(
+                "failed to compare with {}: {}",
+                kept_file.path, err
+            )),
+        }
+    }
+    Err(failed(if reasons.is_empty() {
+        "no file to keep".to_string()
+    } else {
+        reasons.join(", ")
+    }))
+}
+
+/// Compare a file of a duplicate group with its database row and the file on disk,
+/// returning why it can no longer be trusted
+fn check_unchanged(
+    app_state: &AppState,
+    file: &FileKrakenFile,
+) -> Result<Option<String>, FileKrakenError> {
+    let row = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .query_row(
+                &format!(
+                    "SELECT {} FROM files WHERE path = ?1;",
+                    FileKrakenFile::SQL_COLUMNS
+                ),
+                [&file.path],
+                FileKrakenFile::from_row,
+            )
+            .optional()
+    })?;
+    let Some(row) = row else {
+        return Ok(Some(format!(
+            "{} is no longer part of the project",
+            file.path
+        )));
+    };
+    if row.file_len != file.file_len
+        || row.time_modified != file.time_modified
+        || row.hash != file.hash
+    {
+        return Ok(Some(format!(
+            "{} was rescanned since the duplicate search",
+            file.path
+        )));
+    }
+
+    let metadata = match fs::metadata(&file.path) {
+        Ok(metadata) => metadata,
+        Err(err) => return Ok(Some(format!("{}: {}", file.path, err))),
+    };
+    if metadata.len() != row.file_len || unix_secs(metadata.modified()) != row.time_modified {
+        return Ok(Some(format!(
+            "{} changed on disk since it was scanned",
+            file.path
+        )));
+    }
+    Ok(None)
+}
+
+/// Check that the archive of a kept member is unchanged since it was scanned and still
+/// contains the member, returning why it can no longer be trusted
+fn check_member_unchanged(
+    app_state: &AppState,
+    member: &FileKrakenFile,
+) -> Result<Option<String>, FileKrakenError> {
+    let row = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .query_row(
+                &format!(
+                    "SELECT {} FROM archive_members WHERE path = ?1;",
+                    ArchiveMember::SQL_COLUMNS
+                ),
+                [&member.path],
+                ArchiveMember::from_row,
+            )
+            .optional()
+    })?;
+    let Some(row) = row else {
+        return Ok(Some(format!(
+            "{} is no longer part of the project",
+            member.path
+        )));
+    };
+    if Some(&row.hash) != member.hash.as_ref() {
+        return Ok(Some(format!(
+            "{} was rescanned since the duplicate search",
+            member.path
+        )));
+    }
+    let archive = app_state.with_sqlite(|sqlite| {
+        sqlite
+            .query_row(
+                &format!(
+                    "SELECT {} FROM files WHERE path = ?1;",
+                    FileKrakenFile::SQL_COLUMNS
+                ),
+                [&row.archive_path],
+                FileKrakenFile::from_row,
+            )
+            .optional()
+    })?;
+    match archive {
+        Some(archive) => check_unchanged(app_state, &archive),
+        None => Ok(Some(format!(
+            "{} is no longer part of the project",
+            row.archive_path
+        ))),
+    }
+}
+
+/// Compare a member of an archive with a file, of members stored more than once the last
+/// one counts
+fn member_is_identical(
+    app_state: &AppState,
+    member_path: &str,
+    file_path: &str,
+) -> io::Result<bool> {
+    let Some((archive_path, member_name)) = app_state
+        .split_archive_member_path(member_path)
+        .map_err(|err| io::Error::other(err.to_string()))?
+    else {
+        return Ok(false);
+    };
+    let mut identical = None;
+    read_archive(Path::new(archive_path), |entry, content| {
+        if entry.name.trim_start_matches('/') == member_name {
+            identical = Some(readers_are_identical(
+                content,
+                &mut fs::File::open(file_path)?,
+            )?);
+        }
+        Ok(())
+    })?;
+    identical.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is missing from {}", member_name, archive_path),
+        )
+    })
+}
+
+/// Compare the names and contents of the members of two archives
+fn archive_contents_are_identical(
+    app_state: &AppState,
+    path_a: &str,
+    path_b: &str,
+) -> io::Result<bool> {
+    let hash_algorithm = app_state
+        .get_hash_algorithm()
+        .map_err(|err| io::Error::other(err.to_string()))?;
+    let contents = |path: &str| -> io::Result<Vec<(String, u64, String)>> {
+        let mut contents: Vec<(String, u64, String)> = read_archive_members(path, hash_algorithm)?
+            .into_iter()
+            .map(|member| {
+                (
+                    member.name().to_string(),
+                    member.file_len,
+                    member.hash.clone(),
+                )
+            })
+            .collect();
+        contents.sort();
+        Ok(contents)
+    };
+    let contents_a = contents(path_a)?;
+    Ok(!contents_a.is_empty() && contents_a == contents(path_b)?)
+}
+
+/// Compare the full contents of two files
+pub fn files_are_identical(path_a: &str, path_b: &str) -> io::Result<bool> {
+    let mut file_a = fs::File::open(path_a)?;
+    let mut file_b = fs::File::open(path_b)?;
+    if file_a.metadata()?.len() != file_b.metadata()?.len() {
+        return Ok(false);
+    }
+    readers_are_identical(&mut file_a, &mut file_b)
+}
+
+/// Compare two streams up to their ends
+fn readers_are_identical(reader_a: &mut dyn Read, reader_b: &mut dyn Read) -> io::Result<bool> {
+    let mut buffer_a = vec![0u8; COMPARE_BUFFER_SIZE];
+    let mut buffer_b = vec![0u8; COMPARE_BUFFER_SIZE];
+    loop {
+        let nr_read = read_full(reader_a, &mut buffer_a)?;
+        if nr_read != read_full(reader_b, &mut buffer_b)? {
+            return Ok(false);
+        }
+        if nr_read == 0 {
+            return Ok(true);
+        }
+        if buffer_a[..nr_read] != buffer_b[..nr_read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Fill the buffer as far as possible, only returning less at the end of the stream
+fn read_full(reader: &mut dyn Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut nr_read = 0;
+    while nr_read < buffer.len() {
+        match reader.read(&mut buffer[nr_read..])? {
+            0 => break,
+            x => nr_read += x,
+        }
+    }
+    Ok(nr_read)
+}
@@ -1,66 +1,53 @@
+use crate::error::FileKrakenError;
 use crate::processing::find_duplicates::FindDuplicatesState;
-use crate::state::file::{FileKrakenFile, FileKrakenFileType};
+use crate::processing::scan::unix_secs;
+use crate::state::archive::ArchiveMember;
+use crate::state::deletion::DeletionMode;
+use crate::state::duplicate::DuplicateResolution;
+use crate::state::file::{FileChange, FileKrakenFile};
+use crate::state::journal::{JournalAction, JournalEntry, JournalOperation};
+use crate::state::keeper_rule::KeeperRule;
 use crate::state::location::{FileKrakenLocation, FileKrakenLocationState, FileKrakenLocationType};
-use crate::utils::get_longest_parent_path;
-use crate::utils::hashing::hash_file;
-use std::collections::HashMap;
+use crate::state::migrations;
+use crate::state::quarantine::QuarantinedFile;
+use crate::utils::archive::{is_readable_archive, split_archive_member_path};
+use crate::utils::hashing::{hash_file, hash_file_partial, HashAlgorithm};
+use crate::utils::ignore_patterns::{IgnoreMatcher, IgnorePattern};
+use crate::utils::image_hash::{
+    image_hash, read_image, ImageHashAlgorithm, DEFAULT_SIMILAR_IMAGE_THRESHOLD, IMAGE_HASH_BITS,
+};
+use crate::utils::trash;
+use crate::utils::{get_longest_parent_path, move_file};
+use log::warn;
+use rusqlite::{params, OptionalExtension};
+use std::collections::{HashMap, HashSet};
 use std::ops::DerefMut;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::SystemTime;
+use std::{fs, io};
+
+type FilesByPath = Arc<RwLock<HashMap<String, FileKrakenFile>>>;
+type ProgressListener = Box<dyn Fn(&str) + Send + Sync>;
 
 #[derive(Default)]
 pub struct AppState {
     pub find_duplicates_processing: Arc<FindDuplicatesState>,
-    pub sqlite: Arc<Mutex<Option<rusqlite::Connection>>>,
+    pub(crate) sqlite: Arc<Mutex<Option<rusqlite::Connection>>>,
     locations_list: Arc<RwLock<Vec<FileKrakenLocation>>>,
-    files_by_location_by_path:
-        Arc<RwLock<HashMap<String, Arc<RwLock<HashMap<String, FileKrakenFile>>>>>>,
+    files_by_location_by_path: Arc<RwLock<HashMap<String, FilesByPath>>>,
+    progress_listener: RwLock<Option<ProgressListener>>,
 }
 
 impl AppState {
-    pub fn connect_sqlite(&self, path: &str) -> Result<(), rusqlite::Error> {
-        let connection = rusqlite::Connection::open(path)?;
-        connection.execute(
-            "CREATE TABLE IF NOT EXISTS locations (
-                path TEXT PRIMARY KEY,
-                location_type TEXT NOT NULL,
-                location_state TEXT NOT NULL
-            );",
-            [],
-        )?;
-        connection.execute(
-            "CREATE TABLE IF NOT EXISTS files (
-                path TEXT PRIMARY KEY,
-                location_path TEXT NOT NULL,
-                file_type TEXT NOT NULL,
-                file_len INTEGER NOT NULL,
-                time_created INTEGER NOT NULL,
-                time_modified INTEGER NOT NULL,
-                hash_256 TEXT,
-
-                FOREIGN KEY(location_path) REFERENCES locations(path)
-            );",
-            [],
-        )?;
-        connection.execute(
-            "CREATE INDEX IF NOT EXISTS file_location_index 
-                ON files(location_path);",
-            [],
-        )?;
-        connection.execute(
-            "CREATE INDEX IF NOT EXISTS file_len_index
-                ON files(file_len);",
-            [],
-        )?;
-        connection.execute(
-            "CREATE INDEX IF NOT EXISTS file_hash_index
-                ON files(hash_256);",
-            [],
-        )?;
+    pub fn connect_sqlite(&self, path: &str) -> Result<(), FileKrakenError> {
+        let mut connection = rusqlite::Connection::open(path)?;
+        migrations::migrate(&mut connection, path)?;
 
         // load locations from sqlite
         {
-            let mut select_locations =
-                connection.prepare("SELECT path, location_type, location_state FROM locations")?;
+            let mut select_locations = connection
+                .prepare("SELECT path, location_type, location_state, priority FROM locations")?;
             let mut locations_query = select_locations.query([])?;
 
             while let Some(row) = locations_query.next()? {
@@ -77,84 +64,580 @@ impl AppState {
                     "scanned" => FileKrakenLocationState::Scanned,
                     _ => FileKrakenLocationState::Unscanned,
                 };
-                self.add_location(false, &location_path, &location_type, &location_state);
+                self.add_location(false, &location_path, &location_type, &location_state)?;
+                self.set_location_priority(false, &location_path, row.get(3)?)?;
             }
         }
 
         // load files from sqlite
         {
-            let mut select_files = connection.prepare("SELECT path, file_type, file_len, time_created, time_modified, hash_256 FROM files")?;
-            let mut files_query = select_files.query([])?;
-
-            while let Some(row) = files_query.next()? {
-                let file_path: String = row.get(0)?;
-                let file_type = match row.get::<usize, String>(1)?.as_str() {
-                    "normal" => FileKrakenFileType::Normal,
-                    "archive" => FileKrakenFileType::Archive,
-                    x => {
-                        panic!("unknown file type {}", x)
+            let mut select_files = connection.prepare(&format!(
+                "SELECT {} FROM files",
+                FileKrakenFile::SQL_COLUMNS
+            ))?;
+            let files = select_files
+                .query_map([], FileKrakenFile::from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            self.add_files(false, files)?;
+        }
+
+        let unscanned_locations: Vec<String> = self
+            .get_locations_list_readonly()
+            .iter()
+            .filter(|x| x.location_state == FileKrakenLocationState::Unscanned)
+            .map(|x| x.path.clone())
+            .collect();
+        for location_path in unscanned_locations {
+            self.clear_location_files(false, &location_path)?;
+        }
+
+        *self.sqlite.lock().unwrap().deref_mut() = Some(connection);
+        Ok(())
+    }
+
+    /// Run `f` against the connected project database.
+    pub(crate) fn with_sqlite<T>(
+        &self,
+        f: impl FnOnce(&rusqlite::Connection) -> rusqlite::Result<T>,
+    ) -> Result<T, FileKrakenError> {
+        let sqlite_lock = self.sqlite.lock().unwrap();
+        let connection = sqlite_lock.as_ref().ok_or(FileKrakenError::NotConnected)?;
+        Ok(f(connection)?)
+    }
+
+    pub fn calculate_file_hash(
+        &self,
+        file_path: &str,
+        algorithm: HashAlgorithm,
+    ) -> Result<String, FileKrakenError> {
+        self.calculate_cached_hash(file_path, "hash", "hash_algorithm", algorithm, hash_file)
+    }
+
+    pub fn calculate_file_partial_hash(
+        &self,
+        file_path: &str,
+        algorithm: HashAlgorithm,
+    ) -> Result<String, FileKrakenError> {
+        self.calculate_cached_hash(
+            file_path,
+            "partial_hash",
+            "partial_hash_algorithm",
+            algorithm,
+            hash_file_partial,
+        )
+    }
+
+    /// Perceptual hash of an image file, `None` if the image can't be decoded.
+    ///
+    /// Images that fail to decode are remembered with an empty hash, so they aren't read
+    /// again until they change. Files that can't be read are returned as errors and read
+    /// again next time.
+    pub fn calculate_image_hash(
+        &self,
+        file_path: &str,
+        algorithm: ImageHashAlgorithm,
+    ) -> Result<Option<String>, FileKrakenError> {
+        let (hash, hash_algorithm): (Option<String>, Option<ImageHashAlgorithm>) = self
+            .with_sqlite(|sqlite| {
+                sqlite.query_row(
+                    "SELECT image_hash, image_hash_algorithm FROM files WHERE path = ?1;",
+                    [file_path],
+                    |x| Ok((x.get(0)?, x.get(1)?)),
+                )
+            })?;
+        let hash = match hash {
+            Some(hash) if hash_algorithm == Some(algorithm) => hash,
+            _ => {
+                let hash = match read_image(Path::new(file_path)) {
+                    Ok(image) => image_hash(&image, algorithm),
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::InvalidData | io::ErrorKind::Unsupported
+                        ) =>
+                    {
+                        warn!("Failed to decode image {}: {}", file_path, err);
+                        String::new()
                     }
+                    Err(err) => return Err(FileKrakenError::io(file_path, err)),
                 };
+                self.with_sqlite(|sqlite| {
+                    sqlite.execute(
+                        "UPDATE files SET image_hash = ?1, image_hash_algorithm = ?2 \
+                        WHERE path = ?3;",
+                        [&hash, &algorithm.to_string(), file_path],
+                    )
+                })?;
+                hash
+            }
+        };
+        Ok(Some(hash).filter(|x| !x.is_empty()))
+    }
 
-                self.add_file(
-                    false,
-                    &file_path,
-                    &file_type,
-                    row.get(2)?,
-                    row.get(3)?,
-                    row.get(4)?,
-                    row.get(5)?,
+    /// Read a hash from `column` of the file's row, calculating and storing it if it is
+    /// missing or was calculated with a different algorithm
+    fn calculate_cached_hash(
+        &self,
+        file_path: &str,
+        column: &str,
+        algorithm_column: &str,
+        algorithm: HashAlgorithm,
+        hash_fn: fn(&str, HashAlgorithm) -> std::io::Result<String>,
+    ) -> Result<String, FileKrakenError> {
+        // get file to check if its already hashed
+        let (hash, hash_algorithm): (Option<String>, Option<HashAlgorithm>) =
+            self.with_sqlite(|sqlite| {
+                sqlite.query_row(
+                    &format!(
+                        "SELECT {}, {} FROM files WHERE path = ?1;",
+                        column, algorithm_column
+                    ),
+                    [file_path],
+                    |x| Ok((x.get(0)?, x.get(1)?)),
                 )
+            })?;
+
+        match hash {
+            Some(hash) if hash_algorithm == Some(algorithm) => Ok(hash),
+            _ => {
+                // calculate hash
+                let hash = hash_fn(file_path, algorithm)
+                    .map_err(|err| FileKrakenError::io(file_path, err))?;
+
+                // update hash in sqlite
+                self.with_sqlite(|sqlite| {
+                    sqlite.execute(
+                        &format!(
+                            "UPDATE files SET {} = ?1, {} = ?2 WHERE path = ?3;",
+                            column, algorithm_column
+                        ),
+                        [&hash, &algorithm.to_string(), file_path],
+                    )
+                })?;
+
+                Ok(hash)
             }
         }
+    }
+
+    /// Hash algorithm used for new hashes of this project
+    pub fn get_hash_algorithm(&self) -> Result<HashAlgorithm, FileKrakenError> {
+        Ok(self
+            .get_setting("hash_algorithm")?
+            .map(|x| x.parse())
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    pub fn set_hash_algorithm(&self, algorithm: HashAlgorithm) -> Result<(), FileKrakenError> {
+        self.set_setting("hash_algorithm", &algorithm.to_string())
+    }
+
+    /// How files deleted from disk are disposed of in this project
+    pub fn get_deletion_mode(&self) -> Result<DeletionMode, FileKrakenError> {
+        Ok(self
+            .get_setting("deletion_mode")?
+            .map(|x| x.parse())
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    pub fn set_deletion_mode(&self, deletion_mode: DeletionMode) -> Result<(), FileKrakenError> {
+        self.set_setting("deletion_mode", &deletion_mode.to_string())
+    }
+
+    /// Directory that quarantined files are moved into, `<project file>.quarantine` unless
+    /// configured otherwise
+    pub fn get_quarantine_dir(&self) -> Result<String, FileKrakenError> {
+        match self.get_setting("quarantine_dir")? {
+            Some(quarantine_dir) => Ok(quarantine_dir),
+            None => self.with_sqlite(|sqlite| {
+                Ok(format!("{}.quarantine", sqlite.path().unwrap_or_default()))
+            }),
+        }
+    }
+
+    pub fn set_quarantine_dir(&self, quarantine_dir: &str) -> Result<(), FileKrakenError> {
+        self.set_setting("quarantine_dir", quarantine_dir)
+    }
+
+    pub fn get_quarantined_files(&self) -> Result<Vec<QuarantinedFile>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite
+                .prepare(&format!(
+                    "SELECT {} FROM quarantine ORDER BY time_quarantined DESC, id DESC;",
+                    QuarantinedFile::SQL_COLUMNS
+                ))?
+                .query_map([], QuarantinedFile::from_row)?
+                .collect()
+        })
+    }
+
+    /// Move a file into the quarantine directory, keeping its full path below it, and
+    /// return where it was moved to
+    fn quarantine_file(&self, file: &FileKrakenFile) -> Result<String, FileKrakenError> {
+        let file_path = file.path.as_str();
+        let relative_path: PathBuf = Path::new(file_path)
+            .components()
+            .filter(|x| matches!(x, Component::Normal(_)))
+            .collect();
+        let base_path = Path::new(&self.get_quarantine_dir()?).join(relative_path);
+        let mut quarantine_path = base_path.clone();
+        let mut i = 1;
+        while quarantine_path.symlink_metadata().is_ok() {
+            i += 1;
+            quarantine_path = PathBuf::from(format!("{}.{}", base_path.display(), i));
+        }
+        let quarantine_path = quarantine_path.to_string_lossy().to_string();
+
+        move_file(Path::new(file_path), Path::new(&quarantine_path))
+            .map_err(|err| FileKrakenError::io(file_path, err))?;
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                "INSERT INTO quarantine (\
+                original_path, \
+                quarantine_path, \
+                file_len, \
+                hash, \
+                hash_algorithm, \
+                time_quarantined\
+            ) VALUES (?, ?, ?, ?, ?, ?);",
+                params![
+                    file_path,
+                    quarantine_path,
+                    file.file_len,
+                    file.hash,
+                    file.hash_algorithm.map(|x| x.to_string()),
+                    unix_secs(Ok(SystemTime::now())),
+                ],
+            )
+        })?;
+        Ok(quarantine_path)
+    }
+
+    /// Move a quarantined file back to its original path and add it to the project again
+    pub fn restore_quarantined_file(&self, id: i64) -> Result<(), FileKrakenError> {
+        let quarantined_file = self.with_sqlite(|sqlite| {
+            sqlite.query_row(
+                &format!(
+                    "SELECT {} FROM quarantine WHERE id = ?1;",
+                    QuarantinedFile::SQL_COLUMNS
+                ),
+                [id],
+                QuarantinedFile::from_row,
+            )
+        })?;
+        let original_path = &quarantined_file.original_path;
+
+        move_file(
+            Path::new(&quarantined_file.quarantine_path),
+            Path::new(original_path),
+        )
+        .map_err(|err| FileKrakenError::io(original_path, err))?;
+        self.with_sqlite(|sqlite| sqlite.execute("DELETE FROM quarantine WHERE id = ?1;", [id]))?;
+        remove_empty_parent_dirs(
+            &quarantined_file.quarantine_path,
+            &self.get_quarantine_dir()?,
+        );
+
+        self.add_file_with_hash(
+            original_path,
+            quarantined_file.hash,
+            quarantined_file.hash_algorithm,
+        )
+    }
+
+    /// Add a file whose content hash is already known to the project, if it is part of a
+    /// location, e.g. after moving it back to its original path
+    pub(crate) fn add_file_with_hash(
+        &self,
+        file_path: &str,
+        hash: Option<String>,
+        hash_algorithm: Option<HashAlgorithm>,
+    ) -> Result<(), FileKrakenError> {
+        let is_in_project = {
+            let locations = self.get_locations_list_readonly();
+            get_longest_parent_path(file_path, locations.iter())
+                .and_then(|x| locations.iter().find(|location| location.path == x))
+                .is_some_and(|x| x.location_type != FileKrakenLocationType::Excluded)
+        };
+        if !is_in_project {
+            return Ok(());
+        }
 
-        for i in 0..self.locations_list.read().unwrap().len() {
-            let lock = self.locations_list.read().unwrap();
-            let location = lock.get(i).unwrap();
-            if location.location_state == FileKrakenLocationState::Unscanned {
-                self.clear_location_files(false, &location.path);
+        // moves keep the modification time, so the stored hash is still valid
+        let metadata =
+            fs::metadata(file_path).map_err(|err| FileKrakenError::io(file_path, err))?;
+        self.add_file(
+            true,
+            FileKrakenFile {
+                path: file_path.to_string(),
+                file_len: metadata.len(),
+                time_created: unix_secs(metadata.created()),
+                time_modified: unix_secs(metadata.modified()),
+                hash,
+                hash_algorithm,
+                ..Default::default()
             }
+            .with_inode(&metadata),
+        )?;
+        Ok(())
+    }
+
+    /// Permanently delete files that have been in quarantine for at least `min_age_days`,
+    /// returning how many were deleted
+    pub fn purge_quarantine(&self, min_age_days: u64) -> Result<usize, FileKrakenError> {
+        let quarantine_dir = self.get_quarantine_dir()?;
+        let cutoff = unix_secs(Ok(SystemTime::now())).saturating_sub(min_age_days * 24 * 60 * 60);
+        let purged_files: Vec<QuarantinedFile> = self
+            .get_quarantined_files()?
+            .into_iter()
+            .filter(|x| x.time_quarantined <= cutoff)
+            .collect();
+        if purged_files.is_empty() {
+            return Ok(0);
         }
 
-        *self.sqlite.lock().unwrap().deref_mut() = Some(connection);
+        let operation_id = self.begin_operation(&format!(
+            "Purge quarantine older than {} days",
+            min_age_days
+        ))?;
+        let mut nr_purged = 0;
+        for quarantined_file in purged_files {
+            match fs::remove_file(&quarantined_file.quarantine_path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                    return Err(FileKrakenError::io(&quarantined_file.quarantine_path, err));
+                }
+                _ => {}
+            }
+            self.with_sqlite(|sqlite| {
+                sqlite.execute(
+                    "DELETE FROM quarantine WHERE id = ?1;",
+                    [quarantined_file.id],
+                )
+            })?;
+            remove_empty_parent_dirs(&quarantined_file.quarantine_path, &quarantine_dir);
+            self.record_journal_entry(
+                operation_id,
+                &JournalEntry {
+                    file_len: Some(quarantined_file.file_len),
+                    hash: quarantined_file.hash,
+                    hash_algorithm: quarantined_file.hash_algorithm,
+                    ..JournalEntry::new(JournalAction::Deleted, &quarantined_file.quarantine_path)
+                },
+            )?;
+            nr_purged += 1;
+        }
+        Ok(nr_purged)
+    }
+
+    /// Start a new operation of the journal, returning its id to record entries under
+    pub fn begin_operation(&self, description: &str) -> Result<i64, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                "INSERT INTO operations (description, time) VALUES (?1, ?2);",
+                params![description, unix_secs(Ok(SystemTime::now()))],
+            )?;
+            Ok(sqlite.last_insert_rowid())
+        })
+    }
+
+    pub(crate) fn record_journal_entry(
+        &self,
+        operation_id: i64,
+        entry: &JournalEntry,
+    ) -> Result<(), FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                &format!(
+                    "INSERT INTO journal (operation_id, time, {}) \
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+                    JournalEntry::SQL_COLUMNS
+                ),
+                params![
+                    operation_id,
+                    unix_secs(Ok(SystemTime::now())),
+                    entry.action.to_string(),
+                    entry.original_path,
+                    entry.file_len,
+                    entry.hash,
+                    entry.hash_algorithm.map(|x| x.to_string()),
+                    entry.destination,
+                    entry.trash_info_path,
+                    entry.location_type.as_ref().map(|x| x.to_string()),
+                    entry.location_priority,
+                    (!entry.ignore_patterns.is_empty()).then(|| entry.ignore_patterns.join("\n")),
+                ],
+            )
+        })?;
         Ok(())
     }
 
-    pub fn calculate_file_hash(&self, file_path: &str) -> String {
-        // get file to check if its already hashed
-        let hash: String = self
-            .sqlite
-            .lock()
-            .unwrap()
-            .as_ref()
-            .expect("sqlite connection not set")
-            .query_row(
-                "SELECT hash_256 FROM files WHERE path = ?1;",
-                [file_path],
-                |x| x.get(0),
+    /// Operations of the journal, newest first
+    pub fn get_operations(&self, limit: usize) -> Result<Vec<JournalOperation>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite
+                .prepare(&format!(
+                    "SELECT {} FROM operations ORDER BY id DESC LIMIT ?1;",
+                    JournalOperation::SQL_COLUMNS
+                ))?
+                .query_map([limit as i64], JournalOperation::from_row)?
+                .collect()
+        })
+    }
+
+    /// Entries of an operation in the order they were recorded
+    pub fn get_journal_entries(
+        &self,
+        operation_id: i64,
+    ) -> Result<Vec<JournalEntry>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite
+                .prepare(&format!(
+                    "SELECT {} FROM journal WHERE operation_id = ?1 ORDER BY id;",
+                    JournalEntry::SQL_COLUMNS
+                ))?
+                .query_map([operation_id], JournalEntry::from_row)?
+                .collect()
+        })
+    }
+
+    pub(crate) fn set_operation_undone(&self, operation_id: i64) -> Result<(), FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                "UPDATE operations SET undone = 1 WHERE id = ?1;",
+                [operation_id],
             )
-            .unwrap();
+        })?;
+        Ok(())
+    }
 
-        match hash.as_str() {
-            "NULL" => {
-                // calculate hash
-                let hash = hash_file(&file_path);
+    /// How the duplicates of this project are resolved
+    pub fn get_duplicate_resolution(&self) -> Result<DuplicateResolution, FileKrakenError> {
+        Ok(self
+            .get_setting("duplicate_resolution")?
+            .map(|x| x.parse())
+            .transpose()?
+            .unwrap_or_default())
+    }
 
-                // update hash in sqlite
-                self.sqlite
-                    .lock()
-                    .unwrap()
-                    .as_ref()
-                    .expect("sqlite connection not set")
-                    .execute(
-                        "UPDATE files SET hash_256 = ?1 WHERE path = ?2;",
-                        [&hash, file_path],
-                    )
-                    .unwrap();
+    pub fn set_duplicate_resolution(
+        &self,
+        resolution: DuplicateResolution,
+    ) -> Result<(), FileKrakenError> {
+        self.set_setting("duplicate_resolution", &resolution.to_string())
+    }
 
-                hash
+    /// Rules choosing the kept file of a duplicate group, in the order they are applied
+    pub fn get_keeper_rules(&self) -> Result<Vec<KeeperRule>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            let mut select_rules =
+                sqlite.prepare("SELECT rule FROM keeper_rules ORDER BY position;")?;
+            let rules = select_rules
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            Ok(rules)
+        })
+    }
+
+    pub fn set_keeper_rules(&self, rules: &[KeeperRule]) -> Result<(), FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            let transaction = sqlite.unchecked_transaction()?;
+            transaction.execute("DELETE FROM keeper_rules;", [])?;
+            for (position, rule) in rules.iter().enumerate() {
+                transaction.execute(
+                    "INSERT INTO keeper_rules (position, rule) VALUES (?1, ?2);",
+                    params![position, rule.to_string()],
+                )?;
             }
-            x => x.to_string(),
+            transaction.commit()
+        })
+    }
+
+    /// Whether scanning reads the members of zip and tar archives
+    pub fn get_scan_archives(&self) -> Result<bool, FileKrakenError> {
+        Ok(self.get_setting("scan_archives")?.as_deref() == Some("true"))
+    }
+
+    /// Enable or disable reading archives while scanning, disabling forgets all members read
+    /// so far
+    pub fn set_scan_archives(&self, scan_archives: bool) -> Result<(), FileKrakenError> {
+        self.set_setting("scan_archives", &scan_archives.to_string())?;
+        if !scan_archives {
+            self.with_sqlite(|sqlite| sqlite.execute("DELETE FROM archive_members;", []))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the duplicate search also groups images that look alike
+    pub fn get_similar_images(&self) -> Result<bool, FileKrakenError> {
+        Ok(self.get_setting("similar_images")?.as_deref() == Some("true"))
+    }
+
+    pub fn set_similar_images(&self, similar_images: bool) -> Result<(), FileKrakenError> {
+        self.set_setting("similar_images", &similar_images.to_string())
+    }
+
+    /// Perceptual hash algorithm used to find similar images
+    pub fn get_image_hash_algorithm(&self) -> Result<ImageHashAlgorithm, FileKrakenError> {
+        Ok(self
+            .get_setting("image_hash_algorithm")?
+            .map(|x| x.parse())
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    pub fn set_image_hash_algorithm(
+        &self,
+        algorithm: ImageHashAlgorithm,
+    ) -> Result<(), FileKrakenError> {
+        self.set_setting("image_hash_algorithm", &algorithm.to_string())
+    }
+
+    /// Maximum number of bits the perceptual hashes of similar images may differ in
+    pub fn get_similar_image_threshold(&self) -> Result<u32, FileKrakenError> {
+        Ok(self
+            .get_setting("similar_image_threshold")?
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(DEFAULT_SIMILAR_IMAGE_THRESHOLD))
+    }
+
+    pub fn set_similar_image_threshold(&self, threshold: u32) -> Result<(), FileKrakenError> {
+        self.set_setting(
+            "similar_image_threshold",
+            &threshold.min(IMAGE_HASH_BITS).to_string(),
+        )
+    }
+
+    pub(crate) fn get_setting(&self, key: &str) -> Result<Option<String>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite
+                .query_row("SELECT value FROM settings WHERE key = ?1;", [key], |x| {
+                    x.get(0)
+                })
+                .optional()
+        })
+    }
+
+    pub(crate) fn set_setting(&self, key: &str, value: &str) -> Result<(), FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2) \
+                ON CONFLICT(key) DO UPDATE SET value = excluded.value;",
+                [key, value],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Register a callback receiving progress messages of long-running operations
+    pub fn set_progress_listener(&self, listener: impl Fn(&str) + Send + Sync + 'static) {
+        *self.progress_listener.write().unwrap() = Some(Box::new(listener));
+    }
+
+    pub(crate) fn report_progress(&self, message: &str) {
+        if let Some(listener) = self.progress_listener.read().unwrap().as_ref() {
+            listener(message);
         }
     }
 
@@ -162,16 +645,45 @@ impl AppState {
         self.sqlite.lock().unwrap().is_some()
     }
 
-    pub fn remove_location(&self, persist_to_db: bool, location_path: &str) {
-        self.locations_list
-            .write()
-            .unwrap()
-            .iter_mut()
-            .find(|x| x.path == location_path)
-            .expect(&format!("location {} not found", location_path))
-            .location_state = FileKrakenLocationState::Deleting;
+    pub fn remove_location(
+        &self,
+        persist_to_db: bool,
+        location_path: &str,
+    ) -> Result<(), FileKrakenError> {
+        let (location_type, location_priority) = {
+            let mut locations_list = self.locations_list.write().unwrap();
+            let location = locations_list
+                .iter_mut()
+                .find(|x| x.path == location_path)
+                .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?;
+            location.location_state = FileKrakenLocationState::Deleting;
+            (location.location_type.clone(), location.priority)
+        };
 
-        self.clear_location_files(persist_to_db, location_path);
+        self.clear_location_files(persist_to_db, location_path)?;
+
+        if persist_to_db {
+            // kept in the journal to restore them when the removal is undone
+            let ignore_patterns = self.get_ignore_patterns(Some(location_path))?;
+            self.with_sqlite(|sqlite| {
+                sqlite.execute(
+                    "DELETE FROM ignore_patterns WHERE location_path = ?1;",
+                    [location_path],
+                )?;
+                sqlite.execute("DELETE FROM locations WHERE path = ?1;", [location_path])
+            })?;
+            let operation_id =
+                self.begin_operation(&format!("Remove location {}", location_path))?;
+            self.record_journal_entry(
+                operation_id,
+                &JournalEntry {
+                    location_type: Some(location_type),
+                    location_priority: Some(location_priority),
+                    ignore_patterns,
+                    ..JournalEntry::new(JournalAction::LocationRemoved, location_path)
+                },
+            )?;
+        }
 
         self.files_by_location_by_path
             .write()
@@ -180,166 +692,187 @@ impl AppState {
 
         let mut locations_list = self.locations_list.write().unwrap();
         locations_list.retain(|x| x.path != location_path);
+        Ok(())
     }
 
-    pub fn remove_file(&self, persist_to_db: bool, persist_to_disk: bool, file_path: &str) {
-        if persist_to_disk {
-            match std::fs::remove_file(file_path) {
-                Ok(_) => {}
-                Err(_) => {
-                    rfd::MessageDialog::new()
-                        .set_title("Error")
-                        .set_description("Failed to delete file")
-                        .show();
-                    return;
+    /// Delete a file from disk using the project's [`DeletionMode`], record it in the
+    /// journal under `operation_id` and remove it from the project
+    pub fn delete_file(&self, operation_id: i64, file_path: &str) -> Result<(), FileKrakenError> {
+        if self.is_archive_member(file_path)? {
+            return Err(FileKrakenError::ArchiveMemberNotDeletable(
+                file_path.to_string(),
+            ));
+        }
+        let file = self.get_stored_file(file_path)?;
+        let entry = match self.get_deletion_mode()? {
+            DeletionMode::Permanent => {
+                fs::remove_file(file_path).map_err(|err| FileKrakenError::io(file_path, err))?;
+                JournalEntry::for_file(JournalAction::Deleted, &file)
+            }
+            DeletionMode::Trash => {
+                let trashed_file = trash::move_to_trash(Path::new(file_path))
+                    .map_err(|err| FileKrakenError::io(file_path, err))?;
+                JournalEntry {
+                    destination: Some(trashed_file.path.to_string_lossy().to_string()),
+                    trash_info_path: Some(trashed_file.info_path.to_string_lossy().to_string()),
+                    ..JournalEntry::for_file(JournalAction::Trashed, &file)
                 }
             }
+            DeletionMode::Quarantine => JournalEntry {
+                destination: Some(self.quarantine_file(&file)?),
+                ..JournalEntry::for_file(JournalAction::Quarantined, &file)
+            },
+        };
+        self.record_journal_entry(operation_id, &entry)?;
+        self.remove_file(true, file_path)
+    }
+
+    /// The stored row of a file, or only its size if it is not part of the project
+    fn get_stored_file(&self, file_path: &str) -> Result<FileKrakenFile, FileKrakenError> {
+        let file = self.with_sqlite(|sqlite| {
+            sqlite
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM files WHERE path = ?1;",
+                        FileKrakenFile::SQL_COLUMNS
+                    ),
+                    [file_path],
+                    FileKrakenFile::from_row,
+                )
+                .optional()
+        })?;
+        match file {
+            Some(file) => Ok(file),
+            None => Ok(FileKrakenFile {
+                path: file_path.to_string(),
+                file_len: fs::metadata(file_path)
+                    .map_err(|err| FileKrakenError::io(file_path, err))?
+                    .len(),
+                ..Default::default()
+            }),
         }
+    }
 
+    /// Remove a file from the project, leaving it on disk
+    pub fn remove_file(&self, persist_to_db: bool, file_path: &str) -> Result<(), FileKrakenError> {
+        self.remove_files(persist_to_db, &[file_path.to_string()])
+    }
+
+    /// Remove files from the project, deleting their rows in a single transaction
+    pub fn remove_files(
+        &self,
+        persist_to_db: bool,
+        file_paths: &[String],
+    ) -> Result<(), FileKrakenError> {
         if persist_to_db {
-            let sqlite_lock = self.sqlite.lock().unwrap();
-            let mut delete_file = sqlite_lock
-                .as_ref()
-                .unwrap()
-                .prepare("DELETE FROM files WHERE path = ?1")
-                .unwrap();
-            delete_file.execute([file_path]).unwrap();
+            self.with_sqlite(|sqlite| {
+                let transaction = sqlite.unchecked_transaction()?;
+                {
+                    let mut delete_members = transaction
+                        .prepare("DELETE FROM archive_members WHERE archive_path = ?1")?;
+                    let mut delete_file =
+                        transaction.prepare("DELETE FROM files WHERE path = ?1")?;
+                    for file_path in file_paths {
+                        delete_members.execute([file_path])?;
+                        delete_file.execute([file_path])?;
+                    }
+                }
+                transaction.commit()
+            })?;
         }
 
-        let file_parent_location_path =
-            get_longest_parent_path(&file_path, self.get_locations_list_readonly().iter())
-                .expect(&format!("no parent location found for file {}", file_path));
+        for file_path in file_paths {
+            let file_parent_location_path =
+                get_longest_parent_path(file_path, self.get_locations_list_readonly().iter())
+                    .ok_or_else(|| FileKrakenError::NoParentLocation(file_path.to_string()))?;
 
-        self.files_by_location_by_path
-            .read()
-            .unwrap()
-            .get(&file_parent_location_path)
-            .unwrap()
-            .write()
-            .unwrap()
-            .remove(file_path);
+            if let Some(location_files) = self.get_files_by_location(&file_parent_location_path) {
+                location_files.write().unwrap().remove(file_path);
+            }
+        }
+        Ok(())
     }
 
     pub fn add_file(
         &self,
         persist_to_db: bool,
-        file_path: &str,
-        file_type: &FileKrakenFileType,
-        file_len: u64,
-        time_created: u64,
-        time_modified: u64,
-        hash: Option<String>,
-    ) {
-        let parent_location =
-            get_longest_parent_path(file_path, self.get_locations_list_readonly().iter())
-                .expect(&format!("no parent location found for file {}", file_path));
-
-        self.add_file_to_location(
-            persist_to_db,
-            parent_location.as_ref(),
-            file_path,
-            file_type,
-            file_len,
-            time_created,
-            time_modified,
-            hash,
-        )
+        file: FileKrakenFile,
+    ) -> Result<FileChange, FileKrakenError> {
+        Ok(self.add_files(persist_to_db, vec![file])?[0])
     }
 
-    pub fn add_file_to_location(
+    /// Add or update files, persisting them in a single transaction.
+    ///
+    /// Files already in the project whose size and modification time are unchanged keep
+    /// their stored hash and are not written again. Changed files are updated and their
+    /// hash is invalidated.
+    pub fn add_files(
         &self,
         persist_to_db: bool,
-        location_path: &str,
-        file_path: &str,
-        file_type: &FileKrakenFileType,
-        file_len: u64,
-        time_created: u64,
-        time_modified: u64,
-        hash: Option<String>,
-    ) {
-        let file = FileKrakenFile {
-            path: file_path.to_string(),
-            file_type: file_type.clone(),
-            file_len,
-            time_created,
-            time_modified,
-            hash,
-        };
+        files: Vec<FileKrakenFile>,
+    ) -> Result<Vec<FileChange>, FileKrakenError> {
+        let mut files_with_location = Vec::with_capacity(files.len());
+        {
+            let locations = self.get_locations_list_readonly();
+            for file in files {
+                let location_path = get_longest_parent_path(&file.path, locations.iter())
+                    .ok_or_else(|| FileKrakenError::NoParentLocation(file.path.clone()))?;
+                files_with_location.push((location_path, file));
+            }
+        }
 
+        let mut changes = vec![FileChange::Added; files_with_location.len()];
+        let mut previous_locations = vec![None; files_with_location.len()];
         if persist_to_db {
-            if let Some((_, existing_location)) = {
-                self.sqlite
-                    .lock()
-                    .unwrap()
-                    .as_ref()
-                    .unwrap()
-                    .query_row(
-                        "SELECT path, location_path FROM files WHERE path = ?1;",
-                        [file_path],
-                        |x| Ok((x.get::<_, String>(0)?.to_string(), x.get::<_, String>(1)?)),
-                    )
-                    .ok()
-            } {
-                if existing_location != location_path {
-                    self.remove_file(true, false, &file_path);
+            self.with_sqlite(|sqlite| {
+                let transaction = sqlite.unchecked_transaction()?;
+                for (i, (location_path, file)) in files_with_location.iter_mut().enumerate() {
+                    (changes[i], previous_locations[i]) =
+                        persist_file(&transaction, location_path, file)?;
                 }
-            }
+                transaction.commit()
+            })?;
+        }
 
-            self.sqlite
-                .lock()
-                .unwrap()
-                .as_mut()
-                .unwrap()
-                .execute(
-                    "INSERT INTO files (\
-                    path, \
-                    location_path, \
-                    file_type,\
-                    file_len,\
-                    time_created,\
-                    time_modified,\
-                    hash_256\
-                ) VALUES (?, ?, ?, ?, ?, ?, ?) ON CONFLICT(path) DO NOTHING;",
-                    [
-                        file_path,
-                        location_path,
-                        "normal",
-                        &file_len.to_string(),
-                        &time_created.to_string(),
-                        &time_modified.to_string(),
-                        &(match &file.hash {
-                            Some(x) => x.to_string(),
-                            None => "NULL".to_string(),
-                        }),
-                    ],
-                )
-                .unwrap();
+        for ((location_path, file), previous_location) in
+            files_with_location.into_iter().zip(previous_locations)
+        {
+            if let Some(previous_location) = previous_location.filter(|x| *x != location_path) {
+                if let Some(location_files) = self.get_files_by_location(&previous_location) {
+                    location_files.write().unwrap().remove(&file.path);
+                }
+            }
+            self.add_file_to_location(persist_to_db, &location_path, file)?;
         }
+        Ok(changes)
+    }
 
+    fn add_file_to_location(
+        &self,
+        persist_to_db: bool,
+        location_path: &str,
+        file: FileKrakenFile,
+    ) -> Result<(), FileKrakenError> {
         let location_state = self
             .get_location_clone(location_path)
-            .unwrap()
+            .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?
             .location_state;
         if location_state == FileKrakenLocationState::Unscanned {
             self.modify_location_state(
                 persist_to_db,
                 location_path,
                 FileKrakenLocationState::PartialScanned,
-            );
+            )?;
         }
 
-        let location_files = {
-            self.files_by_location_by_path
-                .read()
-                .unwrap()
-                .get(location_path)
-                .unwrap()
-                .clone()
-        };
+        let location_files = self
+            .get_files_by_location(location_path)
+            .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?;
         location_files
             .write()
             .unwrap()
-            .insert(file_path.to_string(), file);
+            .insert(file.path.clone(), file);
+        Ok(())
     }
 
     pub fn get_location_clone(&self, location_path: &str) -> Option<FileKrakenLocation> {
@@ -348,37 +881,43 @@ impl AppState {
             .unwrap()
             .iter()
             .find(|x| x.path == location_path)
-            .map(|x| x.clone())
+            .cloned()
     }
 
     pub fn get_locations_list_readonly(&self) -> RwLockReadGuard<'_, Vec<FileKrakenLocation>> {
         self.locations_list.read().unwrap()
     }
 
-    pub fn modify_location_type(&self, location_path: &str, location_type: FileKrakenLocationType) {
+    pub fn modify_location_type(
+        &self,
+        location_path: &str,
+        location_type: FileKrakenLocationType,
+    ) -> Result<(), FileKrakenError> {
         // do nothing if type is the same
         let current_location_type = self
-            .locations_list
-            .read()
-            .unwrap()
-            .iter()
-            .find(|x| x.path == location_path)
-            .map(|x| x.location_type.clone())
-            .unwrap();
+            .get_location_clone(location_path)
+            .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?
+            .location_type;
         if current_location_type == location_type {
-            return;
+            return Ok(());
         }
 
-        self.sqlite
-            .lock()
-            .unwrap()
-            .as_mut()
-            .expect("sqlite connection not set")
-            .execute(
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
                 "UPDATE locations SET location_type = ? WHERE path = ?;",
                 [&location_type.to_string(), location_path],
             )
-            .unwrap();
+        })?;
+
+        // files of excluded locations are dropped, and a formerly excluded location
+        // has to be scanned again
+        if location_type == FileKrakenLocationType::Excluded
+            || current_location_type == FileKrakenLocationType::Excluded
+        {
+            self.clear_location_files(true, location_path)?;
+            self.modify_location_state(true, location_path, FileKrakenLocationState::Unscanned)?;
+        }
+
         let mut locations_list = self.locations_list.write().unwrap();
         for location in locations_list.iter_mut() {
             if location.path == location_path {
@@ -386,7 +925,7 @@ impl AppState {
                 break;
             }
         }
-        // TODO update affected locations
+        Ok(())
     }
 
     pub fn modify_location_state(
@@ -394,31 +933,23 @@ impl AppState {
         persist_to_db: bool,
         location_path: &str,
         location_state: FileKrakenLocationState,
-    ) {
+    ) -> Result<(), FileKrakenError> {
         // do nothing if state is the same
         let current_location_state = self
-            .locations_list
-            .read()
-            .unwrap()
-            .iter()
-            .find(|x| x.path == location_path)
-            .map(|x| x.location_state.clone())
-            .unwrap();
+            .get_location_clone(location_path)
+            .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?
+            .location_state;
         if current_location_state == location_state {
-            return;
+            return Ok(());
         }
 
         if persist_to_db {
-            self.sqlite
-                .lock()
-                .unwrap()
-                .as_mut()
-                .expect("sqlite connection not set")
-                .execute(
+            self.with_sqlite(|sqlite| {
+                sqlite.execute(
                     "UPDATE locations SET location_state = ? WHERE path = ?;",
                     [&location_state.to_string(), location_path],
                 )
-                .unwrap();
+            })?;
         }
 
         let mut locations_list = self.locations_list.write().unwrap();
@@ -429,29 +960,36 @@ impl AppState {
             }
         }
         // TODO update affected locations
+        Ok(())
     }
 
-    pub fn clear_location_files(&self, persist_to_db: bool, location_path: &str) {
+    pub fn clear_location_files(
+        &self,
+        persist_to_db: bool,
+        location_path: &str,
+    ) -> Result<(), FileKrakenError> {
         if persist_to_db {
-            self.sqlite
-                .lock()
-                .unwrap()
-                .as_ref()
-                .expect("Sql connection not set")
-                .execute(
+            self.with_sqlite(|sqlite| {
+                sqlite.execute(
+                    "DELETE FROM folders WHERE location_path = ?;",
+                    [location_path],
+                )?;
+                sqlite.execute(
+                    "DELETE FROM archive_members WHERE archive_path IN \
+                    (SELECT path FROM files WHERE location_path = ?);",
+                    [location_path],
+                )?;
+                sqlite.execute(
                     "DELETE FROM files WHERE location_path = ?;",
                     [location_path],
                 )
-                .unwrap();
+            })?;
         }
 
-        let files_by_location = self.files_by_location_by_path.read().unwrap();
-        files_by_location
-            .get(location_path)
-            .unwrap()
-            .write()
-            .unwrap()
-            .clear();
+        if let Some(location_files) = self.get_files_by_location(location_path) {
+            location_files.write().unwrap().clear();
+        }
+        Ok(())
     }
 
     pub fn add_location(
@@ -460,22 +998,16 @@ impl AppState {
         location_path: &str,
         location_type: &FileKrakenLocationType,
         location_state: &FileKrakenLocationState,
-    ) {
-        assert!(self
-            .locations_list
-            .read()
-            .unwrap()
-            .iter()
-            .find(|x| x.path == location_path)
-            .is_none());
+    ) -> Result<(), FileKrakenError> {
+        if self.get_location_clone(location_path).is_some() {
+            return Err(FileKrakenError::LocationAlreadyExists(
+                location_path.to_string(),
+            ));
+        }
 
         if persist_to_db {
-            self.sqlite
-                .lock()
-                .unwrap()
-                .as_ref()
-                .expect("Sql connection not set")
-                .execute(
+            self.with_sqlite(|sqlite| {
+                sqlite.execute(
                     "INSERT INTO locations (path, location_type, location_state) VALUES (?, ?, ?);",
                     [
                         location_path,
@@ -483,7 +1015,7 @@ impl AppState {
                         &location_state.to_string(),
                     ],
                 )
-                .unwrap();
+            })?;
         }
 
         self.files_by_location_by_path.write().unwrap().insert(
@@ -494,7 +1026,7 @@ impl AppState {
             get_longest_parent_path(location_path, self.get_locations_list_readonly().iter());
 
         if let Some(path) = &parent_location_path {
-            self.clear_location_files(persist_to_db, path)
+            self.clear_location_files(persist_to_db, path)?;
         }
 
         self.locations_list
@@ -505,13 +1037,328 @@ impl AppState {
                 location_type: location_type.clone(),
                 location_state: location_state.clone(),
                 parent_location_path,
+                priority: 0,
             });
+        Ok(())
     }
 
-    pub fn get_files_by_location(
+    pub fn set_location_priority(
         &self,
-        location: &str,
-    ) -> Option<Arc<RwLock<HashMap<String, FileKrakenFile>>>> {
+        persist_to_db: bool,
+        location_path: &str,
+        priority: i64,
+    ) -> Result<(), FileKrakenError> {
+        self.get_location_clone(location_path)
+            .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?;
+        if persist_to_db {
+            self.with_sqlite(|sqlite| {
+                sqlite.execute(
+                    "UPDATE locations SET priority = ?1 WHERE path = ?2;",
+                    params![priority, location_path],
+                )
+            })?;
+        }
+
+        let mut locations_list = self.locations_list.write().unwrap();
+        for location in locations_list.iter_mut() {
+            if location.path == location_path {
+                location.priority = priority;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Give the locations descending priorities in the order of `location_paths`, the first
+    /// location gets the highest
+    pub fn set_location_order(&self, location_paths: &[String]) -> Result<(), FileKrakenError> {
+        for (i, location_path) in location_paths.iter().enumerate() {
+            self.set_location_priority(true, location_path, (location_paths.len() - i) as i64)?;
+        }
+        Ok(())
+    }
+
+    /// Ignore patterns of a location, or of the whole project if `location_path` is `None`
+    pub fn get_ignore_patterns(
+        &self,
+        location_path: Option<&str>,
+    ) -> Result<Vec<String>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            let mut select_patterns = sqlite.prepare(
+                "SELECT pattern FROM ignore_patterns WHERE location_path = ?1 ORDER BY pattern;",
+            )?;
+            let patterns = select_patterns
+                .query_map([location_path.unwrap_or_default()], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            Ok(patterns)
+        })
+    }
+
+    pub fn add_ignore_pattern(
+        &self,
+        location_path: Option<&str>,
+        pattern: &str,
+    ) -> Result<(), FileKrakenError> {
+        IgnorePattern::new(pattern)?;
+        if let Some(location_path) = location_path {
+            self.get_location_clone(location_path)
+                .ok_or_else(|| FileKrakenError::LocationNotFound(location_path.to_string()))?;
+        }
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                "INSERT INTO ignore_patterns (location_path, pattern) VALUES (?1, ?2) \
+                ON CONFLICT DO NOTHING;",
+                [location_path.unwrap_or_default(), pattern],
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn remove_ignore_pattern(
+        &self,
+        location_path: Option<&str>,
+        pattern: &str,
+    ) -> Result<(), FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                "DELETE FROM ignore_patterns WHERE location_path = ?1 AND pattern = ?2;",
+                [location_path.unwrap_or_default(), pattern],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Build the matcher used when scanning `location_path`: project patterns are rooted
+    /// at the scanned location, location patterns at their own location
+    pub fn get_ignore_matcher(
+        &self,
+        location_path: &str,
+    ) -> Result<IgnoreMatcher, FileKrakenError> {
+        let patterns: Vec<(String, String)> = self.with_sqlite(|sqlite| {
+            let mut select_patterns =
+                sqlite.prepare("SELECT location_path, pattern FROM ignore_patterns;")?;
+            let patterns = select_patterns
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<_, _>>()?;
+            Ok(patterns)
+        })?;
+
+        let mut matcher = IgnoreMatcher::default();
+        for (root, pattern) in patterns {
+            if root.is_empty() {
+                matcher.add(location_path, &pattern)?;
+            } else {
+                matcher.add(&root, &pattern)?;
+            }
+        }
+        Ok(matcher)
+    }
+
+    /// Total size of the files of a location, or of the whole project if `None`, counting
+    /// all hard links to the same inode once
+    pub fn get_disk_usage(&self, location_path: Option<&str>) -> Result<u64, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.query_row(
+                "SELECT COALESCE(SUM(file_len), 0) FROM (\
+                    SELECT MAX(file_len) file_len FROM files \
+                    WHERE ?1 IS NULL OR location_path = ?1 \
+                    GROUP BY COALESCE(device || ':' || inode, path)\
+                );",
+                [location_path],
+                |x| x.get(0),
+            )
+        })
+    }
+
+    /// Store the folders found by scanning a location, forgetting those of the location
+    /// that no longer exist
+    pub(crate) fn set_location_folders(
+        &self,
+        location_path: &str,
+        folder_paths: &[String],
+    ) -> Result<(), FileKrakenError> {
+        // nested locations own the folders below them, like their files
+        let folders: Vec<(&String, String)> = {
+            let locations = self.get_locations_list_readonly();
+            folder_paths
+                .iter()
+                .map(|path| {
+                    let folder_location = get_longest_parent_path(path, locations.iter())
+                        .unwrap_or_else(|| location_path.to_string());
+                    (path, folder_location)
+                })
+                .collect()
+        };
+        let scanned_paths: HashSet<&str> = folder_paths.iter().map(|x| x.as_str()).collect();
+        self.with_sqlite(|sqlite| {
+            let transaction = sqlite.unchecked_transaction()?;
+            let stored_paths: Vec<String> = transaction
+                .prepare("SELECT path FROM folders WHERE location_path = ?1;")?
+                .query_map([location_path], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            for path in stored_paths {
+                if !scanned_paths.contains(path.as_str()) {
+                    transaction.execute("DELETE FROM folders WHERE path = ?1;", [path])?;
+                }
+            }
+            {
+                let mut insert_folder = transaction.prepare(
+                    "INSERT OR REPLACE INTO folders (path, location_path) VALUES (?1, ?2);",
+                )?;
+                for (path, folder_location) in folders.iter() {
+                    insert_folder.execute([path, folder_location])?;
+                }
+            }
+            transaction.commit()
+        })
+    }
+
+    /// All folders of the project with the location they belong to
+    pub fn get_folders(&self) -> Result<Vec<(String, String)>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite
+                .prepare("SELECT path, location_path FROM folders;")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect()
+        })
+    }
+
+    /// Add a folder to the project if it is part of a location, e.g. after creating it again
+    pub(crate) fn add_folder(&self, folder_path: &str) -> Result<(), FileKrakenError> {
+        let location_path = {
+            let locations = self.get_locations_list_readonly();
+            get_longest_parent_path(folder_path, locations.iter())
+                .and_then(|x| locations.iter().find(|location| location.path == x))
+                .filter(|x| x.location_type != FileKrakenLocationType::Excluded)
+                .map(|x| x.path.clone())
+        };
+        let Some(location_path) = location_path.filter(|x| x != folder_path) else {
+            return Ok(());
+        };
+        self.with_sqlite(|sqlite| {
+            sqlite.execute(
+                "INSERT OR REPLACE INTO folders (path, location_path) VALUES (?1, ?2);",
+                [folder_path, &location_path],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Remove a folder from the project, leaving it on disk
+    pub(crate) fn remove_folder(&self, folder_path: &str) -> Result<(), FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.execute("DELETE FROM folders WHERE path = ?1;", [folder_path])
+        })?;
+        Ok(())
+    }
+
+    /// Replace the stored members of an archive with those read from it
+    pub(crate) fn set_archive_members(
+        &self,
+        archive_path: &str,
+        members: &[ArchiveMember],
+    ) -> Result<(), FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            let transaction = sqlite.unchecked_transaction()?;
+            transaction.execute(
+                "DELETE FROM archive_members WHERE archive_path = ?1;",
+                [archive_path],
+            )?;
+            {
+                let mut insert_member = transaction.prepare(&format!(
+                    "INSERT OR REPLACE INTO archive_members ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+                    ArchiveMember::SQL_COLUMNS
+                ))?;
+                for member in members {
+                    insert_member.execute(params![
+                        member.path,
+                        member.archive_path,
+                        member.file_len,
+                        member.time_modified,
+                        member.hash,
+                        member.hash_algorithm.to_string(),
+                    ])?;
+                }
+            }
+            transaction.commit()
+        })
+    }
+
+    /// The members of an archive read while scanning, sorted by path
+    pub fn get_archive_members(
+        &self,
+        archive_path: &str,
+    ) -> Result<Vec<ArchiveMember>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite
+                .prepare(&format!(
+                    "SELECT {} FROM archive_members WHERE archive_path = ?1 ORDER BY path;",
+                    ArchiveMember::SQL_COLUMNS
+                ))?
+                .query_map([archive_path], ArchiveMember::from_row)?
+                .collect()
+        })
+    }
+
+    /// Archives whose members were hashed with `algorithm`
+    pub(crate) fn get_read_archives(
+        &self,
+        algorithm: HashAlgorithm,
+    ) -> Result<HashSet<String>, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite
+                .prepare(
+                    "SELECT DISTINCT archive_path FROM archive_members WHERE hash_algorithm = ?1;",
+                )?
+                .query_map([algorithm.to_string()], |row| row.get(0))?
+                .collect()
+        })
+    }
+
+    /// Whether a path is the virtual path of an archive member rather than a file
+    pub fn is_archive_member(&self, path: &str) -> Result<bool, FileKrakenError> {
+        self.with_sqlite(|sqlite| {
+            sqlite.query_row(
+                "SELECT COUNT(*) > 0 FROM archive_members WHERE path = ?1;",
+                [path],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Split a virtual member path into the archive path and the member name, at the
+    /// separator following a readable archive of the project
+    pub fn split_archive_member_path<'a>(
+        &self,
+        path: &'a str,
+    ) -> Result<Option<(&'a str, &'a str)>, FileKrakenError> {
+        let mut error = None;
+        let split = split_archive_member_path(path, |archive_path| {
+            let mime_type: Result<Option<Option<String>>, FileKrakenError> =
+                self.with_sqlite(|sqlite| {
+                    sqlite
+                        .query_row(
+                            "SELECT mime_type FROM files WHERE path = ?1;",
+                            [archive_path],
+                            |row| row.get(0),
+                        )
+                        .optional()
+                });
+            match mime_type {
+                Ok(mime_type) => mime_type.flatten().is_some_and(|x| is_readable_archive(&x)),
+                Err(err) => {
+                    error.get_or_insert(err);
+                    false
+                }
+            }
+        });
+        match error {
+            Some(err) => Err(err),
+            None => Ok(split),
+        }
+    }
+
+    pub fn get_files_by_location(&self, location: &str) -> Option<FilesByPath> {
         self.files_by_location_by_path
             // get readonly access to the by-location hashmap
             .read()
@@ -519,6 +1366,173 @@ impl AppState {
             // look for the by-filepath hashmap for the given location
             .get(location)
             // clone the Arc reference of the Hashmap if it exists
-            .map(|x| x.clone())
+            .cloned()
+    }
+}
+
+/// Remove the directories left empty by moving a file out of the quarantine directory
+fn remove_empty_parent_dirs(file_path: &str, quarantine_dir: &str) {
+    for dir in Path::new(file_path).ancestors().skip(1) {
+        if !dir.starts_with(quarantine_dir) || dir == Path::new(quarantine_dir) {
+            break;
+        }
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Insert or update a single file row, returning how it changed and the location it
+/// was previously stored under
+fn persist_file(
+    sqlite: &rusqlite::Connection,
+    location_path: &str,
+    file: &mut FileKrakenFile,
+) -> rusqlite::Result<(FileChange, Option<String>)> {
+    let existing = sqlite
+        .query_row(
+            &format!(
+                "SELECT location_path, {} FROM files WHERE path = ?1;",
+                FileKrakenFile::SQL_COLUMNS
+            ),
+            [&file.path],
+            |x| Ok((x.get::<_, String>(0)?, FileKrakenFile::from_row(x)?)),
+        )
+        .optional()?;
+
+    // partial hashes are only a cache of the stored content and recalculated on demand
+    let result = match existing {
+        None => {
+            file.partial_hash = None;
+            sqlite.execute(
+                "INSERT INTO files (\
+                path, \
+                location_path, \
+                file_type,\
+                mime_type,\
+                file_len,\
+                time_created,\
+                time_modified,\
+                hash,\
+                hash_algorithm,\
+                device,\
+                inode,\
+                nr_links\
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+                params![
+                    file.path,
+                    location_path,
+                    file.file_type.to_string(),
+                    file.mime_type,
+                    file.file_len,
+                    file.time_created,
+                    file.time_modified,
+                    file.hash,
+                    file.hash_algorithm.map(|x| x.to_string()),
+                    file.device.map(|x| x as i64),
+                    file.inode.map(|x| x as i64),
+                    file.nr_links.map(|x| x as i64),
+                ],
+            )?;
+            (FileChange::Added, None)
+        }
+        Some((existing_location, existing))
+            if existing.file_len == file.file_len
+                && existing.time_modified == file.time_modified =>
+        {
+            if existing_location != location_path {
+                sqlite.execute(
+                    "UPDATE files SET location_path = ?1 WHERE path = ?2;",
+                    [location_path, &file.path],
+                )?;
+            }
+            // keep the hashes calculated for the unchanged content
+            if file.hash.is_none() {
+                file.hash = existing.hash;
+                file.hash_algorithm = existing.hash_algorithm;
+            }
+            if file.partial_hash.is_none() {
+                file.partial_hash = existing.partial_hash;
+            }
+            if file.mime_type.is_none() {
+                (file.file_type, file.mime_type) = (existing.file_type, existing.mime_type);
+            } else if (&file.file_type, &file.mime_type)
+                != (&existing.file_type, &existing.mime_type)
+            {
+                sqlite.execute(
+                    "UPDATE files SET file_type = ?1, mime_type = ?2 WHERE path = ?3;",
+                    params![file.file_type.to_string(), file.mime_type, file.path],
+                )?;
+            }
+            // a file replaced by a hard link or a copy may keep its size and modification time
+            if file.inode_id().is_none() {
+                (file.device, file.inode, file.nr_links) =
+                    (existing.device, existing.inode, existing.nr_links);
+            } else if (file.device, file.inode, file.nr_links)
+                != (existing.device, existing.inode, existing.nr_links)
+            {
+                sqlite.execute(
+                    "UPDATE files SET device = ?1, inode = ?2, nr_links = ?3 WHERE path = ?4;",
+                    params![
+                        file.device.map(|x| x as i64),
+                        file.inode.map(|x| x as i64),
+                        file.nr_links.map(|x| x as i64),
+                        file.path,
+                    ],
+                )?;
+            }
+            (FileChange::Unchanged, Some(existing_location))
+        }
+        Some((existing_location, _)) => {
+            file.partial_hash = None;
+            // the members of a changed archive are read again
+            sqlite.execute(
+                "DELETE FROM archive_members WHERE archive_path = ?1;",
+                [&file.path],
+            )?;
+            sqlite.execute(
+                "UPDATE files SET \
+                location_path = ?1, \
+                file_type = ?2, \
+                mime_type = ?3, \
+                file_len = ?4, \
+                time_created = ?5, \
+                time_modified = ?6, \
+                hash = ?7, \
+                hash_algorithm = ?8, \
+                partial_hash = NULL, \
+                partial_hash_algorithm = NULL, \
+                image_hash = NULL, \
+                image_hash_algorithm = NULL, \
+                device = ?9, \
+                inode = ?10, \
+                nr_links = ?11 \
+                WHERE path = ?12;",
+                params![
+                    location_path,
+                    file.file_type.to_string(),
+                    file.mime_type,
+                    file.file_len,
+                    file.time_created,
+                    file.time_modified,
+                    file.hash,
+                    file.hash_algorithm.map(|x| x.to_string()),
+                    file.device.map(|x| x as i64),
+                    file.inode.map(|x| x as i64),
+                    file.nr_links.map(|x| x as i64),
+                    file.path,
+                ],
+            )?;
+            (FileChange::Modified, Some(existing_location))
+        }
+    };
+
+    // the other hard links to the inode share its link count
+    if let (Some((device, inode)), Some(nr_links)) = (file.inode_id(), file.nr_links) {
+        sqlite.execute(
+            "UPDATE files SET nr_links = ?3 WHERE device = ?1 AND inode = ?2 AND nr_links IS NOT ?3;",
+            params![device as i64, inode as i64, nr_links as i64],
+        )?;
     }
+    Ok(result)
 }
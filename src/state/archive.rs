@@ -0,0 +1,53 @@
+use crate::state::file::{FileKrakenFile, FileKrakenFileType};
+use crate::utils::archive::ARCHIVE_MEMBER_SEPARATOR;
+use crate::utils::hashing::HashAlgorithm;
+
+/// A file inside an archive of the project, hashed while scanning
+#[derive(Debug, Clone)]
+pub struct ArchiveMember {
+    /// Virtual path like `backup.zip!/dir/file.jpg`
+    pub path: String,
+    pub archive_path: String,
+    pub file_len: u64,
+    pub time_modified: u64,
+    pub hash: String,
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl ArchiveMember {
+    /// Columns of the `archive_members` table read by [`ArchiveMember::from_row`]
+    pub(crate) const SQL_COLUMNS: &'static str =
+        "path, archive_path, file_len, time_modified, hash, hash_algorithm";
+
+    pub(crate) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(ArchiveMember {
+            path: row.get("path")?,
+            archive_path: row.get("archive_path")?,
+            file_len: row.get("file_len")?,
+            time_modified: row.get("time_modified")?,
+            hash: row.get("hash")?,
+            hash_algorithm: row.get("hash_algorithm")?,
+        })
+    }
+
+    /// Name of the member inside its archive
+    pub fn name(&self) -> &str {
+        self.path
+            .strip_prefix(&self.archive_path)
+            .and_then(|x| x.strip_prefix(ARCHIVE_MEMBER_SEPARATOR))
+            .unwrap_or_default()
+    }
+
+    /// The member as a virtual file, to compare it with the files of the project
+    pub fn to_file(&self) -> FileKrakenFile {
+        FileKrakenFile {
+            path: self.path.clone(),
+            file_type: FileKrakenFileType::ArchiveMember,
+            file_len: self.file_len,
+            time_modified: self.time_modified,
+            hash: Some(self.hash.clone()),
+            hash_algorithm: Some(self.hash_algorithm),
+            ..Default::default()
+        }
+    }
+}
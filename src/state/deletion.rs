@@ -0,0 +1,62 @@
+use crate::error::FileKrakenError;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// What happens to files deleted from disk, configured per project
+#[derive(PartialEq, Eq, Default, Debug, Clone, Copy)]
+pub enum DeletionMode {
+    /// Unlink the file, it cannot be recovered
+    #[default]
+    Permanent,
+    /// Move the file into the freedesktop.org trash
+    Trash,
+    /// Move the file into the quarantine directory of the project, from where it can be
+    /// restored until the quarantine is purged
+    Quarantine,
+}
+
+impl DeletionMode {
+    pub const ALL: [DeletionMode; 3] = [
+        DeletionMode::Permanent,
+        DeletionMode::Trash,
+        DeletionMode::Quarantine,
+    ];
+}
+
+impl fmt::Display for DeletionMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DeletionMode::Permanent => "permanent",
+                DeletionMode::Trash => "trash",
+                DeletionMode::Quarantine => "quarantine",
+            }
+        )
+    }
+}
+
+impl FromStr for DeletionMode {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "permanent" => Ok(DeletionMode::Permanent),
+            "trash" => Ok(DeletionMode::Trash),
+            "quarantine" => Ok(DeletionMode::Quarantine),
+            x => Err(FileKrakenError::UnknownDeletionMode(x.to_string())),
+        }
+    }
+}
+
+impl FromSql for DeletionMode {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
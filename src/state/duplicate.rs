@@ -1,15 +1,97 @@
+use crate::error::FileKrakenError;
 use crate::state::file::FileKrakenFile;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
 
 #[derive(Default, Debug, Clone)]
 pub struct FileKrakenDuplicate {
-    /// The file that will be deleted in favor of the `other_files`
-    pub deletable_file: Option<FileKrakenFile>,
+    /// The files that will be deleted in favor of the `other_files`
+    pub deletable_files: Vec<FileKrakenFile>,
+    /// The files that are kept, never empty
     pub other_files: Vec<FileKrakenFile>,
     pub duplicate_type: FileKrakenDuplicateType,
+    /// Why resolving was aborted, e.g. if the files turned out to differ from their stored
+    /// hashes or the filesystem could not deduplicate them
+    pub verification_error: Option<String>,
+}
+
+impl FileKrakenDuplicate {
+    /// All files of the group, kept files first
+    pub fn files(&self) -> impl Iterator<Item = &FileKrakenFile> {
+        self.other_files.iter().chain(self.deletable_files.iter())
+    }
+
+    pub fn is_deletable(&self, file_path: &str) -> bool {
+        self.deletable_files.iter().any(|x| x.path == file_path)
+    }
 }
 
 #[derive(PartialEq, Default, Debug, Clone)]
 pub enum FileKrakenDuplicateType {
+    /// Files with the same content, possibly inside archives
     #[default]
     ExactMatch,
+    /// Archives whose members have the same names and contents, but differ as files
+    ArchiveContent,
+    /// Images that look alike by their perceptual hashes, e.g. resized or re-encoded copies
+    SimilarImage,
+}
+
+/// How the deletable file of a duplicate group is gotten rid of, configured per project
+#[derive(PartialEq, Eq, Default, Debug, Clone, Copy)]
+pub enum DuplicateResolution {
+    /// Delete the file using the project's deletion mode
+    #[default]
+    Delete,
+    /// Replace the file with a hard link to the kept file, keeping its path
+    Hardlink,
+    /// Let the file share the extents of the kept file on copy-on-write filesystems like
+    /// btrfs and XFS, keeping both files independent
+    Reflink,
+}
+
+impl DuplicateResolution {
+    pub const ALL: [DuplicateResolution; 3] = [
+        DuplicateResolution::Delete,
+        DuplicateResolution::Hardlink,
+        DuplicateResolution::Reflink,
+    ];
+}
+
+impl fmt::Display for DuplicateResolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DuplicateResolution::Delete => "delete",
+                DuplicateResolution::Hardlink => "hardlink",
+                DuplicateResolution::Reflink => "reflink",
+            }
+        )
+    }
+}
+
+impl FromStr for DuplicateResolution {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(DuplicateResolution::Delete),
+            "hardlink" => Ok(DuplicateResolution::Hardlink),
+            "reflink" => Ok(DuplicateResolution::Reflink),
+            x => Err(FileKrakenError::UnknownDuplicateResolution(x.to_string())),
+        }
+    }
+}
+
+impl FromSql for DuplicateResolution {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
 }
@@ -1,11 +1,105 @@
+use crate::error::FileKrakenError;
+use crate::utils::hashing::HashAlgorithm;
+use crate::utils::magic::{is_extension_mismatch, mime_type_of_extension};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
 #[derive(Default, Debug, Clone)]
 pub struct FileKrakenFile {
     pub path: String,
     pub file_type: FileKrakenFileType,
+    /// Type detected from the first bytes of the content, `None` if it was not read yet
+    pub mime_type: Option<String>,
     pub file_len: u64,
     pub time_created: u64,
     pub time_modified: u64,
     pub hash: Option<String>,
+    /// Algorithm that produced `hash`
+    pub hash_algorithm: Option<HashAlgorithm>,
+    /// Hash of only the first and last bytes, used to rule out most candidates cheaply
+    pub partial_hash: Option<String>,
+    /// Device and inode number of the file's data, shared by all hard links to it
+    pub device: Option<u64>,
+    pub inode: Option<u64>,
+    /// Number of hard links to the inode when the file was last seen
+    pub nr_links: Option<u64>,
+}
+
+impl FileKrakenFile {
+    /// Columns of the `files` table read by [`FileKrakenFile::from_row`]
+    pub(crate) const SQL_COLUMNS: &'static str =
+        "path, file_type, mime_type, file_len, time_created, time_modified, hash, \
+        hash_algorithm, partial_hash, device, inode, nr_links";
+
+    pub(crate) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(FileKrakenFile {
+            path: row.get("path")?,
+            file_type: row.get("file_type")?,
+            mime_type: row.get("mime_type")?,
+            file_len: row.get("file_len")?,
+            time_created: row.get("time_created")?,
+            time_modified: row.get("time_modified")?,
+            hash: row.get("hash")?,
+            hash_algorithm: row.get("hash_algorithm")?,
+            partial_hash: row.get("partial_hash")?,
+            // stored as signed integers, inode numbers may use all 64 bits
+            device: row.get::<_, Option<i64>>("device")?.map(|x| x as u64),
+            inode: row.get::<_, Option<i64>>("inode")?.map(|x| x as u64),
+            nr_links: row.get::<_, Option<i64>>("nr_links")?.map(|x| x as u64),
+        })
+    }
+
+    /// Take the device, inode number and link count from the metadata of the file
+    pub(crate) fn with_inode(mut self, metadata: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            self.device = Some(metadata.dev());
+            self.inode = Some(metadata.ino());
+            self.nr_links = Some(metadata.nlink());
+        }
+        #[cfg(not(unix))]
+        let _ = metadata;
+        self
+    }
+
+    /// Device and inode number, equal for all hard links to the same data
+    pub fn inode_id(&self) -> Option<(u64, u64)> {
+        self.device.zip(self.inode)
+    }
+
+    /// Whether this is a virtual file inside an archive
+    pub fn is_archive_member(&self) -> bool {
+        self.file_type == FileKrakenFileType::ArchiveMember
+    }
+
+    /// Lowercase extension of the file name without the dot
+    pub fn extension(&self) -> Option<String> {
+        Path::new(&self.path)
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_ascii_lowercase())
+    }
+
+    /// Whether the detected content type contradicts the extension, e.g. a PNG image named
+    /// `.jpg`
+    pub fn has_extension_mismatch(&self) -> bool {
+        self.extension()
+            .zip(self.mime_type.as_deref())
+            .is_some_and(|(extension, mime_type)| is_extension_mismatch(&extension, mime_type))
+    }
+}
+
+/// How a scanned file compares to its row in the project database
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FileChange {
+    Added,
+    Modified,
+    Unchanged,
 }
 
 #[derive(PartialEq, Default, Debug, Clone)]
@@ -13,4 +107,160 @@ pub enum FileKrakenFileType {
     #[default]
     Normal,
     Archive,
+    Image,
+    Video,
+    Audio,
+    Document,
+    /// A virtual file inside an archive, it can't be deleted on its own
+    ArchiveMember,
+}
+
+impl FileKrakenFileType {
+    /// Type of a detected MIME type
+    pub fn from_mime_type(mime_type: &str) -> Self {
+        match mime_type {
+            "application/zip"
+            | "application/gzip"
+            | "application/x-xz"
+            | "application/x-bzip2"
+            | "application/zstd"
+            | "application/x-7z-compressed"
+            | "application/vnd.rar"
+            | "application/x-tar" => FileKrakenFileType::Archive,
+            "application/pdf" => FileKrakenFileType::Document,
+            x if x.starts_with("image/") => FileKrakenFileType::Image,
+            x if x.starts_with("video/") => FileKrakenFileType::Video,
+            x if x.starts_with("audio/") => FileKrakenFileType::Audio,
+            _ => FileKrakenFileType::Normal,
+        }
+    }
+
+    /// Type guessed from the extension of a path, for files whose content can't be read
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|x| x.to_str())
+            .and_then(mime_type_of_extension)
+            .map(FileKrakenFileType::from_mime_type)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for FileKrakenFileType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FileKrakenFileType::Normal => "normal",
+                FileKrakenFileType::Archive => "archive",
+                FileKrakenFileType::Image => "image",
+                FileKrakenFileType::Video => "video",
+                FileKrakenFileType::Audio => "audio",
+                FileKrakenFileType::Document => "document",
+                FileKrakenFileType::ArchiveMember => "archive_member",
+            }
+        )
+    }
+}
+
+impl FromStr for FileKrakenFileType {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(FileKrakenFileType::Normal),
+            "archive" => Ok(FileKrakenFileType::Archive),
+            "image" => Ok(FileKrakenFileType::Image),
+            "video" => Ok(FileKrakenFileType::Video),
+            "audio" => Ok(FileKrakenFileType::Audio),
+            "document" => Ok(FileKrakenFileType::Document),
+            "archive_member" => Ok(FileKrakenFileType::ArchiveMember),
+            x => Err(FileKrakenError::UnknownFileType(x.to_string())),
+        }
+    }
+}
+
+impl FromSql for FileKrakenFileType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+/// Rough kind of content of a file, guessed from its extension
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum FileCategory {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+    Code,
+    Other,
+}
+
+impl FileCategory {
+    pub const ALL: [FileCategory; 7] = [
+        FileCategory::Image,
+        FileCategory::Video,
+        FileCategory::Audio,
+        FileCategory::Document,
+        FileCategory::Archive,
+        FileCategory::Code,
+        FileCategory::Other,
+    ];
+
+    pub fn from_path(path: &str) -> Self {
+        FileCategory::from_extension(
+            Path::new(path)
+                .extension()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Category of a file extension without the dot, e.g. `jpg`
+    pub fn from_extension(extension: &str) -> Self {
+        match extension.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tif" | "tiff" | "webp" | "heic" | "svg"
+            | "raw" | "cr2" | "nef" | "arw" | "dng" => FileCategory::Image,
+            "mp4" | "mkv" | "avi" | "mov" | "wmv" | "webm" | "m4v" | "mpg" | "mpeg" | "flv" => {
+                FileCategory::Video
+            }
+            "mp3" | "flac" | "wav" | "ogg" | "m4a" | "aac" | "opus" | "wma" => FileCategory::Audio,
+            "pdf" | "doc" | "docx" | "odt" | "xls" | "xlsx" | "ods" | "ppt" | "pptx" | "odp"
+            | "txt" | "md" | "rtf" | "epub" => FileCategory::Document,
+            "zip" | "7z" | "rar" | "tar" | "gz" | "xz" | "bz2" | "zst" | "iso" | "dmg" => {
+                FileCategory::Archive
+            }
+            "rs" | "c" | "h" | "cpp" | "hpp" | "py" | "js" | "ts" | "java" | "go" | "sh"
+            | "html" | "css" | "json" | "toml" | "yaml" | "yml" | "xml" => FileCategory::Code,
+            _ => FileCategory::Other,
+        }
+    }
+
+    /// Position in [`FileCategory::ALL`]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for FileCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FileCategory::Image => "image",
+                FileCategory::Video => "video",
+                FileCategory::Audio => "audio",
+                FileCategory::Document => "document",
+                FileCategory::Archive => "archive",
+                FileCategory::Code => "code",
+                FileCategory::Other => "other",
+            }
+        )
+    }
 }
@@ -0,0 +1,162 @@
+use crate::error::FileKrakenError;
+use crate::state::file::FileKrakenFile;
+use crate::state::location::FileKrakenLocationType;
+use crate::utils::hashing::HashAlgorithm;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// A user action recorded in the operations journal, e.g. deleting all eligible duplicates
+#[derive(Debug, Clone)]
+pub struct JournalOperation {
+    pub id: i64,
+    pub description: String,
+    /// Seconds since the unix epoch
+    pub time: u64,
+    pub undone: bool,
+}
+
+impl JournalOperation {
+    /// Columns of the `operations` table read by [`JournalOperation::from_row`]
+    pub(crate) const SQL_COLUMNS: &'static str = "id, description, time, undone";
+
+    pub(crate) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(JournalOperation {
+            id: row.get("id")?,
+            description: row.get("description")?,
+            time: row.get("time")?,
+            undone: row.get("undone")?,
+        })
+    }
+}
+
+/// A single file or location changed by an operation
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub action: JournalAction,
+    pub original_path: String,
+    pub file_len: Option<u64>,
+    pub hash: Option<String>,
+    pub hash_algorithm: Option<HashAlgorithm>,
+    /// Where the file was moved to, or the file it was linked to
+    pub destination: Option<String>,
+    /// `.trashinfo` file of a trashed file
+    pub trash_info_path: Option<String>,
+    /// Type of a removed location
+    pub location_type: Option<FileKrakenLocationType>,
+    /// Priority of a removed location
+    pub location_priority: Option<i64>,
+    /// Ignore patterns of a removed location
+    pub ignore_patterns: Vec<String>,
+}
+
+impl JournalEntry {
+    pub(crate) const SQL_COLUMNS: &'static str = "action, original_path, file_len, hash, \
+        hash_algorithm, destination, trash_info_path, location_type, location_priority, \
+        ignore_patterns";
+
+    pub fn new(action: JournalAction, original_path: &str) -> Self {
+        JournalEntry {
+            action,
+            original_path: original_path.to_string(),
+            file_len: None,
+            hash: None,
+            hash_algorithm: None,
+            destination: None,
+            trash_info_path: None,
+            location_type: None,
+            location_priority: None,
+            ignore_patterns: vec![],
+        }
+    }
+
+    /// Entry for a file of the project, recording its size and hash
+    pub fn for_file(action: JournalAction, file: &FileKrakenFile) -> Self {
+        JournalEntry {
+            file_len: Some(file.file_len),
+            hash: file.hash.clone(),
+            hash_algorithm: file.hash_algorithm,
+            ..JournalEntry::new(action, &file.path)
+        }
+    }
+
+    pub(crate) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(JournalEntry {
+            action: row.get("action")?,
+            original_path: row.get("original_path")?,
+            file_len: row.get("file_len")?,
+            hash: row.get("hash")?,
+            hash_algorithm: row.get("hash_algorithm")?,
+            destination: row.get("destination")?,
+            trash_info_path: row.get("trash_info_path")?,
+            location_type: row.get("location_type")?,
+            location_priority: row.get("location_priority")?,
+            // one pattern per line
+            ignore_patterns: row
+                .get::<_, Option<String>>("ignore_patterns")?
+                .map(|x| x.lines().map(String::from).collect())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum JournalAction {
+    /// Deleted permanently, cannot be undone
+    Deleted,
+    Trashed,
+    Quarantined,
+    /// Replaced with a hard link to the file in `destination`
+    Hardlinked,
+    /// Shares the extents of the file in `destination`, its contents were left untouched
+    Reflinked,
+    /// A location was removed from the project, its files were left untouched
+    LocationRemoved,
+    /// An empty folder was removed from disk
+    FolderRemoved,
+}
+
+impl fmt::Display for JournalAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                JournalAction::Deleted => "deleted",
+                JournalAction::Trashed => "trashed",
+                JournalAction::Quarantined => "quarantined",
+                JournalAction::Hardlinked => "hardlinked",
+                JournalAction::Reflinked => "reflinked",
+                JournalAction::LocationRemoved => "location_removed",
+                JournalAction::FolderRemoved => "folder_removed",
+            }
+        )
+    }
+}
+
+impl FromStr for JournalAction {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deleted" => Ok(JournalAction::Deleted),
+            "trashed" => Ok(JournalAction::Trashed),
+            "quarantined" => Ok(JournalAction::Quarantined),
+            "hardlinked" => Ok(JournalAction::Hardlinked),
+            "reflinked" => Ok(JournalAction::Reflinked),
+            "location_removed" => Ok(JournalAction::LocationRemoved),
+            "folder_removed" => Ok(JournalAction::FolderRemoved),
+            x => Err(FileKrakenError::UnknownJournalAction(x.to_string())),
+        }
+    }
+}
+
+impl FromSql for JournalAction {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
@@ -0,0 +1,100 @@
+use crate::error::FileKrakenError;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// Criterion for choosing which file of a duplicate group is kept.
+///
+/// A project has an ordered list of rules: each rule narrows the files of a group down to
+/// those it likes best, later rules only break the ties of earlier ones. All files left
+/// after the last rule are kept and every other file of the group is deletable, so a group
+/// whose files are all tied is not eligible for deletion.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum KeeperRule {
+    /// Keep files in the locations with the highest priority
+    HighestPriority,
+    /// Keep files in preferred locations
+    PreferredLocation,
+    /// Keep the file created first
+    OldestCreated,
+    /// Keep the file with the shortest path
+    ShortestPath,
+    /// Keep files whose path matches the regex
+    PathMatches(String),
+    /// Keep the file nested deepest in folders
+    DeepestFolder,
+}
+
+impl KeeperRule {
+    /// An example of every kind of rule, e.g. to offer them in a selection
+    pub fn examples() -> [KeeperRule; 6] {
+        [
+            KeeperRule::HighestPriority,
+            KeeperRule::PreferredLocation,
+            KeeperRule::OldestCreated,
+            KeeperRule::ShortestPath,
+            KeeperRule::PathMatches(String::new()),
+            KeeperRule::DeepestFolder,
+        ]
+    }
+
+    /// Human readable description of the rule
+    pub fn description(&self) -> String {
+        match self {
+            KeeperRule::HighestPriority => "Prefer locations with a higher priority".to_string(),
+            KeeperRule::PreferredLocation => "Prefer files in preferred locations".to_string(),
+            KeeperRule::OldestCreated => "Prefer the oldest file".to_string(),
+            KeeperRule::ShortestPath => "Prefer the shortest path".to_string(),
+            KeeperRule::PathMatches(regex) => format!("Prefer paths matching {}", regex),
+            KeeperRule::DeepestFolder => "Prefer the deepest folder".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for KeeperRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeeperRule::HighestPriority => write!(f, "highest_priority"),
+            KeeperRule::PreferredLocation => write!(f, "preferred_location"),
+            KeeperRule::OldestCreated => write!(f, "oldest_created"),
+            KeeperRule::ShortestPath => write!(f, "shortest_path"),
+            KeeperRule::PathMatches(regex) => write!(f, "path_matches:{}", regex),
+            KeeperRule::DeepestFolder => write!(f, "deepest_folder"),
+        }
+    }
+}
+
+impl FromStr for KeeperRule {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "highest_priority" => Ok(KeeperRule::HighestPriority),
+            "preferred_location" => Ok(KeeperRule::PreferredLocation),
+            "oldest_created" => Ok(KeeperRule::OldestCreated),
+            "shortest_path" => Ok(KeeperRule::ShortestPath),
+            "deepest_folder" => Ok(KeeperRule::DeepestFolder),
+            x => match x.strip_prefix("path_matches:") {
+                Some(regex) => {
+                    regex::Regex::new(regex)
+                        .map_err(|err| FileKrakenError::InvalidKeeperRule(err.to_string()))?;
+                    Ok(KeeperRule::PathMatches(regex.to_string()))
+                }
+                None => Err(FileKrakenError::InvalidKeeperRule(format!(
+                    "unknown rule {}",
+                    x
+                ))),
+            },
+        }
+    }
+}
+
+impl FromSql for KeeperRule {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
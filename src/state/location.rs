@@ -1,5 +1,8 @@
+use crate::error::FileKrakenError;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
 use std::fmt;
 use std::fmt::Formatter;
+use std::str::FromStr;
 
 #[derive(PartialEq, Default, Debug, Clone)]
 pub struct FileKrakenLocation {
@@ -7,6 +10,8 @@ pub struct FileKrakenLocation {
     pub location_type: FileKrakenLocationType,
     pub location_state: FileKrakenLocationState,
     pub parent_location_path: Option<String>,
+    /// Files in locations with a higher priority are kept when duplicates are resolved
+    pub priority: i64,
 }
 
 #[derive(PartialEq, Default, Debug, Clone)]
@@ -55,3 +60,25 @@ impl fmt::Display for FileKrakenLocationType {
         )
     }
 }
+
+impl FromStr for FileKrakenLocationType {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(FileKrakenLocationType::Normal),
+            "preferred" => Ok(FileKrakenLocationType::Preferred),
+            "excluded" => Ok(FileKrakenLocationType::Excluded),
+            x => Err(FileKrakenError::UnknownLocationType(x.to_string())),
+        }
+    }
+}
+
+impl FromSql for FileKrakenLocationType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
@@ -0,0 +1,187 @@
+use crate::error::FileKrakenError;
+use log::info;
+use std::path::Path;
+
+/// Ordered schema migrations of the project file.
+///
+/// `MIGRATIONS[i]` upgrades a project from schema version `i` to `i + 1`. Projects
+/// created before versioning was introduced have version 0. Never edit an existing
+/// entry, always append a new one.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE IF NOT EXISTS locations (
+        path TEXT PRIMARY KEY,
+        location_type TEXT NOT NULL,
+        location_state TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        path TEXT PRIMARY KEY,
+        location_path TEXT NOT NULL,
+        file_type TEXT NOT NULL,
+        file_len INTEGER NOT NULL,
+        time_created INTEGER NOT NULL,
+        time_modified INTEGER NOT NULL,
+        hash_256 TEXT,
+
+        FOREIGN KEY(location_path) REFERENCES locations(path)
+    );
+    CREATE INDEX IF NOT EXISTS file_location_index ON files(location_path);
+    CREATE INDEX IF NOT EXISTS file_len_index ON files(file_len);
+    CREATE INDEX IF NOT EXISTS file_hash_index ON files(hash_256);",
+    // 2: missing hashes used to be stored as the string 'NULL'
+    "UPDATE files SET hash_256 = NULL WHERE hash_256 = 'NULL';",
+    // 3: ignore patterns, an empty location_path applies to the whole project
+    "CREATE TABLE ignore_patterns (
+        location_path TEXT NOT NULL DEFAULT '',
+        pattern TEXT NOT NULL,
+
+        PRIMARY KEY(location_path, pattern)
+    );",
+    // 4: hash of the first and last bytes of a file
+    "ALTER TABLE files ADD COLUMN partial_hash_256 TEXT;",
+    // 5: project settings and the algorithm each stored hash was calculated with,
+    //    hashes of older projects are all SHA-256
+    "CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    ALTER TABLE files ADD COLUMN hash_algorithm TEXT;
+    ALTER TABLE files ADD COLUMN partial_hash_algorithm TEXT;
+    UPDATE files SET hash_algorithm = 'sha256' WHERE hash_256 IS NOT NULL;
+    UPDATE files SET partial_hash_algorithm = 'sha256' WHERE partial_hash_256 IS NOT NULL;",
+    // 6: files moved into the quarantine directory instead of being deleted
+    "CREATE TABLE quarantine (
+        id INTEGER PRIMARY KEY,
+        original_path TEXT NOT NULL,
+        quarantine_path TEXT NOT NULL,
+        file_len INTEGER NOT NULL,
+        hash_256 TEXT,
+        hash_algorithm TEXT,
+        time_quarantined INTEGER NOT NULL
+    );",
+    // 7: journal of destructive operations, each changing any number of files
+    "CREATE TABLE operations (
+        id INTEGER PRIMARY KEY,
+        description TEXT NOT NULL,
+        time INTEGER NOT NULL,
+        undone INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE journal (
+        id INTEGER PRIMARY KEY,
+        operation_id INTEGER NOT NULL,
+        time INTEGER NOT NULL,
+        action TEXT NOT NULL,
+        original_path TEXT NOT NULL,
+        file_len INTEGER,
+        hash_256 TEXT,
+        hash_algorithm TEXT,
+        destination TEXT,
+        trash_info_path TEXT,
+        location_type TEXT,
+
+        FOREIGN KEY(operation_id) REFERENCES operations(id)
+    );
+    CREATE INDEX journal_operation_index ON journal(operation_id);",
+    // 8: device and inode numbers to recognize hard links, filled in by the next scan
+    "ALTER TABLE files ADD COLUMN device INTEGER;
+    ALTER TABLE files ADD COLUMN inode INTEGER;
+    ALTER TABLE files ADD COLUMN nr_links INTEGER;
+    CREATE INDEX file_inode_index ON files(device, inode);",
+    // 9: ordered rules choosing the kept file of a duplicate group, starting out with the
+    //    rule that used to be built in
+    "CREATE TABLE keeper_rules (
+        position INTEGER PRIMARY KEY,
+        rule TEXT NOT NULL
+    );
+    INSERT INTO keeper_rules (position, rule) VALUES (0, 'preferred_location');",
+    // 10: location priorities, applied before the existing keeper rules so that ordering
+    //     the locations takes effect, all priorities are equal until then
+    "ALTER TABLE locations ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+    UPDATE keeper_rules SET position = -position - 1;
+    UPDATE keeper_rules SET position = -position;
+    INSERT INTO keeper_rules (position, rule) VALUES (0, 'highest_priority');",
+    // 11: folders found while scanning, to find the empty ones
+    "CREATE TABLE folders (
+        path TEXT PRIMARY KEY,
+        location_path TEXT NOT NULL
+    );
+    CREATE INDEX folder_location_index ON folders (location_path);",
+    // 12: type detected from the content, existing files are detected on their next scan
+    "ALTER TABLE files ADD COLUMN mime_type TEXT;",
+    // 13: files inside archives, read while scanning if enabled
+    "CREATE TABLE archive_members (
+        path TEXT PRIMARY KEY,
+        archive_path TEXT NOT NULL,
+        file_len INTEGER NOT NULL,
+        time_modified INTEGER NOT NULL,
+        hash_256 TEXT NOT NULL,
+        hash_algorithm TEXT NOT NULL
+    );
+    CREATE INDEX archive_member_archive_index ON archive_members (archive_path);
+    CREATE INDEX archive_member_len_index ON archive_members (file_len);",
+    // 14: perceptual hashes of images, empty for images that can't be decoded
+    "ALTER TABLE files ADD COLUMN image_hash TEXT;
+    ALTER TABLE files ADD COLUMN image_hash_algorithm TEXT;",
+    // 15: priority and ignore patterns of removed locations, restored when undoing the removal
+    "ALTER TABLE journal ADD COLUMN location_priority INTEGER;
+    ALTER TABLE journal ADD COLUMN ignore_patterns TEXT;",
+    // 16: hash columns hold hashes of any algorithm, so they lose their `_256` suffix
+    "ALTER TABLE files RENAME COLUMN hash_256 TO hash;
+    ALTER TABLE files RENAME COLUMN partial_hash_256 TO partial_hash;
+    ALTER TABLE quarantine RENAME COLUMN hash_256 TO hash;
+    ALTER TABLE journal RENAME COLUMN hash_256 TO hash;
+    ALTER TABLE archive_members RENAME COLUMN hash_256 TO hash;",
+];
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Bring the project database at `path` up to [`SCHEMA_VERSION`].
+///
+/// Refuses projects written by a newer version of File Kraken. Before migrating a
+/// project that already contains data, a copy of the file is saved next to it.
+pub fn migrate(connection: &mut rusqlite::Connection, path: &str) -> Result<(), FileKrakenError> {
+    let version: i64 = connection.pragma_query_value(None, "user_version", |x| x.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(FileKrakenError::ProjectVersionTooNew {
+            version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let has_data: bool = connection.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table';",
+        [],
+        |x| x.get(0),
+    )?;
+    if has_data {
+        let backup_path = backup_project(path, version)?;
+        info!(
+            "Migrating project {} from version {} to {}, backup saved to {}",
+            path, version, SCHEMA_VERSION, backup_path
+        );
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", i as i64 + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+/// Copy the project file to `<path>.v<version>.bak`, never overwriting an older backup
+fn backup_project(path: &str, version: i64) -> Result<String, FileKrakenError> {
+    let mut backup_path = format!("{}.v{}.bak", path, version);
+    let mut i = 1;
+    while Path::new(&backup_path).exists() {
+        backup_path = format!("{}.v{}.{}.bak", path, version, i);
+        i += 1;
+    }
+    std::fs::copy(path, &backup_path).map_err(|err| FileKrakenError::io(&backup_path, err))?;
+    Ok(backup_path)
+}
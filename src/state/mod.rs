@@ -1,7 +1,14 @@
 mod app_state;
+pub mod archive;
+pub mod deletion;
 pub mod file;
+pub mod journal;
+pub mod keeper_rule;
 pub mod location;
+mod migrations;
+pub mod quarantine;
 
 pub mod duplicate;
 
 pub use app_state::AppState;
+pub use migrations::SCHEMA_VERSION;
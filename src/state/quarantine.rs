@@ -0,0 +1,33 @@
+use crate::utils::hashing::HashAlgorithm;
+
+/// A file moved into the quarantine directory of the project instead of being deleted
+#[derive(Debug, Clone)]
+pub struct QuarantinedFile {
+    pub id: i64,
+    pub original_path: String,
+    /// Where the file is kept inside the quarantine directory
+    pub quarantine_path: String,
+    pub file_len: u64,
+    pub hash: Option<String>,
+    pub hash_algorithm: Option<HashAlgorithm>,
+    /// Seconds since the unix epoch
+    pub time_quarantined: u64,
+}
+
+impl QuarantinedFile {
+    /// Columns of the `quarantine` table read by [`QuarantinedFile::from_row`]
+    pub(crate) const SQL_COLUMNS: &'static str =
+        "id, original_path, quarantine_path, file_len, hash, hash_algorithm, time_quarantined";
+
+    pub(crate) fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(QuarantinedFile {
+            id: row.get("id")?,
+            original_path: row.get("original_path")?,
+            quarantine_path: row.get("quarantine_path")?,
+            file_len: row.get("file_len")?,
+            hash: row.get("hash")?,
+            hash_algorithm: row.get("hash_algorithm")?,
+            time_quarantined: row.get("time_quarantined")?,
+        })
+    }
+}
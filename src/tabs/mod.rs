@@ -1,10 +1,9 @@
-
-pub mod tab_locations;
 pub mod tab_files;
+pub mod tab_locations;
 
 #[derive(Default, PartialEq)]
 pub enum FileKrakenMainTabs {
     #[default]
     Locations,
-    Files
-}
\ No newline at end of file
+    Files,
+}
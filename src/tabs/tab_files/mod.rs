@@ -1,36 +1,114 @@
 use crate::FileKrakenApp;
 use egui::RichText;
 
+mod tab_files_disk_usage;
+mod tab_files_duplicate_folders;
 mod tab_files_duplicates;
+mod tab_files_empty_folders;
+mod tab_files_large_files;
 mod tab_files_overview;
+mod tab_files_quarantine;
+
+pub use tab_files_disk_usage::DiskUsageTabState;
+pub use tab_files_duplicate_folders::DuplicateFoldersTabState;
+pub use tab_files_duplicates::DuplicatesTabState;
+pub use tab_files_empty_folders::EmptyFoldersTabState;
+pub use tab_files_large_files::LargeFilesTabState;
+pub use tab_files_overview::OverviewTabState;
+pub use tab_files_quarantine::QuarantineTabState;
 
 #[derive(Default, PartialEq)]
 pub enum FileKrakenFileTabs {
     #[default]
     Overview,
     Duplicates,
+    DuplicateFolders,
+    EmptyFolders,
+    LargeFiles,
+    DiskUsage,
+    Quarantine,
 }
 
 impl FileKrakenApp {
     pub fn files_tab(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
             ui.horizontal(|ui| {
-                ui.selectable_value(
-                    &mut self.current_files_tab,
-                    FileKrakenFileTabs::Overview,
-                    RichText::new("Overview"),
-                );
+                if ui
+                    .selectable_value(
+                        &mut self.current_files_tab,
+                        FileKrakenFileTabs::Overview,
+                        RichText::new("Overview"),
+                    )
+                    .clicked()
+                {
+                    self.tab_state_overview.reload();
+                }
                 ui.selectable_value(
                     &mut self.current_files_tab,
                     FileKrakenFileTabs::Duplicates,
                     RichText::new("Duplicates"),
                 );
+                if ui
+                    .selectable_value(
+                        &mut self.current_files_tab,
+                        FileKrakenFileTabs::DuplicateFolders,
+                        RichText::new("Duplicate folders"),
+                    )
+                    .clicked()
+                {
+                    self.tab_state_duplicate_folders.reload();
+                }
+                if ui
+                    .selectable_value(
+                        &mut self.current_files_tab,
+                        FileKrakenFileTabs::EmptyFolders,
+                        RichText::new("Empty folders"),
+                    )
+                    .clicked()
+                {
+                    self.tab_state_empty_folders.reload();
+                }
+                if ui
+                    .selectable_value(
+                        &mut self.current_files_tab,
+                        FileKrakenFileTabs::LargeFiles,
+                        RichText::new("Large files"),
+                    )
+                    .clicked()
+                {
+                    self.tab_state_large_files.reload();
+                }
+                if ui
+                    .selectable_value(
+                        &mut self.current_files_tab,
+                        FileKrakenFileTabs::DiskUsage,
+                        RichText::new("Disk usage"),
+                    )
+                    .clicked()
+                {
+                    self.tab_state_disk_usage.reload();
+                }
+                if ui
+                    .selectable_value(
+                        &mut self.current_files_tab,
+                        FileKrakenFileTabs::Quarantine,
+                        RichText::new("Quarantine"),
+                    )
+                    .clicked()
+                {
+                    self.tab_state_quarantine.reload();
+                }
             });
         });
         ui.separator();
         match self.current_files_tab {
             FileKrakenFileTabs::Overview => self.files_tab_overview(ui),
             FileKrakenFileTabs::Duplicates => self.files_tab_duplicates(ui),
+            FileKrakenFileTabs::DuplicateFolders => self.files_tab_duplicate_folders(ui),
+            FileKrakenFileTabs::EmptyFolders => self.files_tab_empty_folders(ui),
+            FileKrakenFileTabs::LargeFiles => self.files_tab_large_files(ui),
+            FileKrakenFileTabs::DiskUsage => self.files_tab_disk_usage(ui),
+            FileKrakenFileTabs::Quarantine => self.files_tab_quarantine(ui),
         }
     }
 }
@@ -0,0 +1,394 @@
+use crate::ui::dialogs::error_dialog;
+use crate::ui::treemap::squarify;
+use crate::ui::ui_elements::colored_box;
+use crate::FileKrakenApp;
+use egui::{Align2, Color32, FontId, Sense, Stroke, Ui, Vec2};
+use file_kraken::processing::disk_usage::{get_disk_usage_tree, DiskUsageFolder};
+use file_kraken::state::file::FileCategory;
+use file_kraken::state::location::FileKrakenLocationType;
+use file_kraken::utils::format_file_size;
+use file_kraken::FileKrakenError;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+/// Number of rectangles drawn at most, smaller items are combined into one
+const MAX_TREEMAP_ITEMS: usize = 500;
+
+#[derive(Default, PartialEq, Clone, Copy)]
+enum TreemapColors {
+    #[default]
+    Category,
+    Duplicates,
+}
+
+#[derive(Default)]
+pub struct DiskUsageTabState {
+    location_path: Option<String>,
+    /// Aggregated in the background when a location is chosen
+    tree: Option<DiskUsageFolder>,
+    loading: Option<Receiver<Result<DiskUsageFolder, FileKrakenError>>>,
+    /// Subfolder indices from the location down to the shown folder
+    folder_indices: Vec<usize>,
+    colors: TreemapColors,
+}
+
+impl DiskUsageTabState {
+    /// Aggregate the files of the location again the next time the tab is shown
+    pub fn reload(&mut self) {
+        self.tree = None;
+        self.loading = None;
+    }
+}
+
+/// A rectangle of the treemap
+enum TreemapItem<'a> {
+    /// Index in the subfolders of the shown folder
+    Folder(usize, &'a DiskUsageFolder),
+    File {
+        path: &'a str,
+        size: u64,
+        category: FileCategory,
+        is_duplicate: bool,
+    },
+    /// Items too small to be listed on their own
+    Others { size: u64, nr_items: u64 },
+}
+
+impl TreemapItem<'_> {
+    fn size(&self) -> u64 {
+        match self {
+            TreemapItem::Folder(_, folder) => folder.size,
+            TreemapItem::File { size, .. } | TreemapItem::Others { size, .. } => *size,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            TreemapItem::Folder(_, folder) => format!("📁 {}", file_name(&folder.path)),
+            TreemapItem::File { path, .. } => file_name(path),
+            TreemapItem::Others { nr_items, .. } => format!("{} smaller items", nr_items),
+        }
+    }
+
+    fn color(&self, colors: TreemapColors) -> Color32 {
+        match (self, colors) {
+            (TreemapItem::Folder(_, folder), TreemapColors::Category) => {
+                category_color(folder.main_category())
+            }
+            (TreemapItem::Folder(_, folder), TreemapColors::Duplicates) => {
+                duplicate_color(folder.duplicate_size as f32 / folder.size.max(1) as f32)
+            }
+            (TreemapItem::File { category, .. }, TreemapColors::Category) => {
+                category_color(*category)
+            }
+            (TreemapItem::File { is_duplicate, .. }, TreemapColors::Duplicates) => {
+                duplicate_color(if *is_duplicate { 1.0 } else { 0.0 })
+            }
+            (TreemapItem::Others { .. }, _) => Color32::GRAY,
+        }
+    }
+}
+
+impl FileKrakenApp {
+    pub fn files_tab_disk_usage(&mut self, ui: &mut Ui) {
+        self.poll_disk_usage_tree(ui);
+
+        ui.vertical(|ui| {
+            colored_box(ui, Color32::LIGHT_GRAY, |ui| {
+                ui.label("Disk usage");
+            });
+            colored_box(ui, Color32::TRANSPARENT, |ui| {
+                let tab_state = &mut self.tab_state_disk_usage;
+                ui.horizontal(|ui| {
+                    ui.label("Location");
+                    let selected_text = tab_state
+                        .location_path
+                        .clone()
+                        .unwrap_or("Choose a location".to_string());
+                    egui::ComboBox::from_id_source("disk_usage_location")
+                        .selected_text(selected_text)
+                        .show_ui(ui, |ui| {
+                            for location in self.app_state.get_locations_list_readonly().iter() {
+                                if location.location_type == FileKrakenLocationType::Excluded {
+                                    continue;
+                                }
+                                if ui
+                                    .selectable_label(
+                                        tab_state.location_path.as_ref() == Some(&location.path),
+                                        &location.path,
+                                    )
+                                    .clicked()
+                                {
+                                    tab_state.location_path = Some(location.path.clone());
+                                    tab_state.folder_indices.clear();
+                                    tab_state.reload();
+                                }
+                            }
+                        });
+                    if ui.button("Refresh").clicked() {
+                        tab_state.reload();
+                    }
+                    ui.add_space(10.0);
+                    ui.label("Colour by");
+                    ui.selectable_value(&mut tab_state.colors, TreemapColors::Category, "Type");
+                    ui.selectable_value(
+                        &mut tab_state.colors,
+                        TreemapColors::Duplicates,
+                        "Duplicates",
+                    )
+                    .on_hover_text("Share of files deletable in the latest duplicate search");
+                });
+                legend(ui, tab_state.colors);
+
+                if tab_state.loading.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Adding up file sizes ...");
+                    });
+                    return;
+                }
+                let Some(tree) = tab_state.tree.as_ref() else {
+                    return;
+                };
+                // the tree may have changed since the indices were chosen
+                if tree.descendant(&tab_state.folder_indices).is_none() {
+                    tab_state.folder_indices.clear();
+                }
+
+                ui.horizontal_wrapped(|ui| {
+                    let mut folder = tree;
+                    let mut new_depth = None;
+                    if ui.link(&tree.path).clicked() {
+                        new_depth = Some(0);
+                    }
+                    for (depth, &i) in tab_state.folder_indices.iter().enumerate() {
+                        folder = &folder.subfolders[i];
+                        ui.label("›");
+                        if ui.link(file_name(&folder.path)).clicked() {
+                            new_depth = Some(depth + 1);
+                        }
+                    }
+                    ui.label(format!(
+                        "  {} in {} files",
+                        format_file_size(folder.size),
+                        folder.nr_files
+                    ));
+                    if let Some(depth) = new_depth {
+                        tab_state.folder_indices.truncate(depth);
+                    }
+                });
+
+                if let Some(folder) = tree.descendant(&tab_state.folder_indices) {
+                    if let Some(i) = treemap(ui, folder, tab_state.colors) {
+                        tab_state.folder_indices.push(i);
+                    }
+                }
+            });
+        });
+    }
+
+    /// Start aggregating the chosen location if needed and pick up the result
+    fn poll_disk_usage_tree(&mut self, ui: &mut Ui) {
+        let tab_state = &mut self.tab_state_disk_usage;
+        if let Some(receiver) = tab_state.loading.as_ref() {
+            match receiver.try_recv() {
+                Ok(Ok(tree)) => {
+                    tab_state.tree = Some(tree);
+                    tab_state.loading = None;
+                }
+                Ok(Err(err)) => {
+                    tab_state.loading = None;
+                    tab_state.location_path = None;
+                    error_dialog(&format!("Failed to add up file sizes. Error: {}", err));
+                }
+                Err(mpsc::TryRecvError::Empty) => ui.ctx().request_repaint(),
+                Err(mpsc::TryRecvError::Disconnected) => tab_state.loading = None,
+            }
+            return;
+        }
+        if tab_state.tree.is_some() {
+            return;
+        }
+        let Some(location_path) = tab_state.location_path.clone() else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        let app_state = self.app_state.clone();
+        thread::spawn(move || {
+            let _ = sender.send(get_disk_usage_tree(&app_state, &location_path));
+        });
+        tab_state.loading = Some(receiver);
+    }
+}
+
+/// Draw the contents of a folder, returning the index of a subfolder that was clicked
+fn treemap(ui: &mut Ui, folder: &DiskUsageFolder, colors: TreemapColors) -> Option<usize> {
+    let mut items: Vec<TreemapItem> = folder
+        .subfolders
+        .iter()
+        .enumerate()
+        .map(|(i, x)| TreemapItem::Folder(i, x))
+        .chain(folder.files.iter().map(|x| TreemapItem::File {
+            path: &x.path,
+            size: x.size,
+            category: x.category,
+            is_duplicate: x.is_duplicate,
+        }))
+        .filter(|x| x.size() > 0)
+        .collect();
+    items.sort_by_key(|x| std::cmp::Reverse(x.size()));
+    let mut others_size = folder.other_files_size;
+    let mut nr_others = folder.nr_other_files;
+    if items.len() > MAX_TREEMAP_ITEMS {
+        for item in items.drain(MAX_TREEMAP_ITEMS..) {
+            others_size += item.size();
+            nr_others += 1;
+        }
+    }
+    if others_size > 0 {
+        let others = TreemapItem::Others {
+            size: others_size,
+            nr_items: nr_others,
+        };
+        let position = items.partition_point(|x| x.size() >= others_size);
+        items.insert(position, others);
+    }
+
+    let (response, painter) = ui.allocate_painter(
+        Vec2::new(ui.available_width(), ui.available_height().max(200.0)),
+        Sense::click(),
+    );
+    let rects = squarify(
+        &items.iter().map(|x| x.size()).collect::<Vec<_>>(),
+        response.rect,
+    );
+    let hover_pos = response.hover_pos();
+    let mut hovered = None;
+    for (i, (item, rect)) in items.iter().zip(rects.iter()).enumerate() {
+        let is_hovered = hover_pos.is_some_and(|x| rect.contains(x));
+        if is_hovered {
+            hovered = Some(i);
+        }
+        let stroke = if is_hovered {
+            Stroke::new(2.0, Color32::WHITE)
+        } else {
+            Stroke::new(1.0, Color32::from_gray(40))
+        };
+        painter.rect(rect.shrink(0.5), 2.0, item.color(colors), stroke);
+        if rect.width() > 60.0 && rect.height() > 32.0 {
+            painter.with_clip_rect(rect.shrink(2.0)).text(
+                rect.left_top() + Vec2::new(4.0, 3.0),
+                Align2::LEFT_TOP,
+                format!("{}\n{}", item.label(), format_file_size(item.size())),
+                FontId::proportional(12.0),
+                Color32::BLACK,
+            );
+        }
+    }
+
+    let hovered_item = hovered.map(|i| (&items[i], rects[i]));
+    let clicked = response.clicked();
+    if let Some((item, _)) = hovered_item {
+        response.on_hover_ui_at_pointer(|ui| {
+            match item {
+                TreemapItem::Folder(_, folder) => {
+                    ui.label(&folder.path);
+                    ui.label(format!(
+                        "{} in {} files",
+                        format_file_size(folder.size),
+                        folder.nr_files
+                    ));
+                    if folder.duplicate_size > 0 {
+                        ui.label(format!(
+                            "{} deletable duplicates",
+                            format_file_size(folder.duplicate_size)
+                        ));
+                    }
+                }
+                TreemapItem::File {
+                    path, is_duplicate, ..
+                } => {
+                    ui.label(*path);
+                    ui.label(format_file_size(item.size()));
+                    if *is_duplicate {
+                        ui.label("Deletable duplicate");
+                    }
+                }
+                TreemapItem::Others { .. } => {
+                    ui.label(format!(
+                        "{}, {}",
+                        item.label(),
+                        format_file_size(item.size())
+                    ));
+                }
+            };
+        });
+    }
+    match hovered_item {
+        Some((TreemapItem::Folder(i, _), _)) if clicked => Some(*i),
+        _ => None,
+    }
+}
+
+fn legend(ui: &mut Ui, colors: TreemapColors) {
+    ui.horizontal(|ui| {
+        let entry = |ui: &mut Ui, color: Color32, text: &str| {
+            let (rect, _) = ui.allocate_exact_size(Vec2::splat(12.0), Sense::hover());
+            ui.painter().rect_filled(rect, 2.0, color);
+            ui.label(text);
+            ui.add_space(6.0);
+        };
+        match colors {
+            TreemapColors::Category => {
+                for category in FileCategory::ALL {
+                    entry(ui, category_color(category), category_text(category));
+                }
+            }
+            TreemapColors::Duplicates => {
+                entry(ui, duplicate_color(0.0), "No duplicates");
+                entry(ui, duplicate_color(0.5), "Half duplicates");
+                entry(ui, duplicate_color(1.0), "Only duplicates");
+            }
+        }
+    });
+}
+
+fn category_color(category: FileCategory) -> Color32 {
+    match category {
+        FileCategory::Image => Color32::from_rgb(120, 190, 120),
+        FileCategory::Video => Color32::from_rgb(220, 130, 90),
+        FileCategory::Audio => Color32::from_rgb(200, 160, 220),
+        FileCategory::Document => Color32::from_rgb(120, 170, 220),
+        FileCategory::Archive => Color32::from_rgb(220, 200, 100),
+        FileCategory::Code => Color32::from_rgb(110, 200, 200),
+        FileCategory::Other => Color32::from_rgb(180, 180, 180),
+    }
+}
+
+fn category_text(category: FileCategory) -> &'static str {
+    match category {
+        FileCategory::Image => "Images",
+        FileCategory::Video => "Videos",
+        FileCategory::Audio => "Audio",
+        FileCategory::Document => "Documents",
+        FileCategory::Archive => "Archives",
+        FileCategory::Code => "Code",
+        FileCategory::Other => "Other",
+    }
+}
+
+/// From blue for no duplicates to red for only duplicates
+fn duplicate_color(duplicate_share: f32) -> Color32 {
+    let share = duplicate_share.clamp(0.0, 1.0);
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * share) as u8;
+    Color32::from_rgb(mix(130, 230), mix(170, 80), mix(210, 80))
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or(path.to_string())
+}
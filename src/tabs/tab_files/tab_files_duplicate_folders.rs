@@ -0,0 +1,217 @@
+use crate::ui::dialogs::error_dialog;
+use crate::ui::ui_elements::{colored_box, unselectable_label};
+use crate::FileKrakenApp;
+use egui::{Color32, RichText, Ui};
+use file_kraken::processing::duplicate_folders::{
+    find_duplicate_folders, resolve_duplicate_folder, DuplicateFolderGroup, DuplicateFolders,
+};
+use file_kraken::utils::format_file_size;
+use rfd::MessageDialogResult;
+
+pub struct DuplicateFoldersTabState {
+    /// Searched on demand, `None` after the files of the project changed
+    duplicate_folders: Option<DuplicateFolders>,
+    compare_names: bool,
+    min_similarity: u32,
+}
+
+impl Default for DuplicateFoldersTabState {
+    fn default() -> Self {
+        DuplicateFoldersTabState {
+            duplicate_folders: None,
+            compare_names: true,
+            min_similarity: 50,
+        }
+    }
+}
+
+impl DuplicateFoldersTabState {
+    /// Search the duplicate folders again the next time the tab is shown
+    pub fn reload(&mut self) {
+        self.duplicate_folders = None;
+    }
+}
+
+impl FileKrakenApp {
+    pub fn files_tab_duplicate_folders(&mut self, ui: &mut Ui) {
+        if self.tab_state_duplicate_folders.duplicate_folders.is_none() {
+            let tab_state = &mut self.tab_state_duplicate_folders;
+            match find_duplicate_folders(
+                &self.app_state,
+                tab_state.compare_names,
+                tab_state.min_similarity as f64,
+            ) {
+                Ok(duplicate_folders) => tab_state.duplicate_folders = Some(duplicate_folders),
+                Err(err) => {
+                    tab_state.duplicate_folders = Some(DuplicateFolders::default());
+                    error_dialog(&format!("Failed to find duplicate folders. Error: {}", err));
+                }
+            }
+        }
+
+        ui.vertical(|ui| {
+            colored_box(ui, Color32::LIGHT_GRAY, |ui| {
+                ui.label("Duplicate folders");
+            });
+            colored_box(ui, Color32::TRANSPARENT, |ui| {
+                ui.label(
+                    "Folders are compared using the file hashes of the last duplicate search.",
+                );
+                ui.horizontal(|ui| {
+                    let tab_state = &mut self.tab_state_duplicate_folders;
+                    let mut changed = ui.button("Refresh").clicked();
+                    ui.add_space(10.0);
+                    changed |= ui
+                        .checkbox(&mut tab_state.compare_names, "Compare names")
+                        .on_hover_text("Files and subfolders also need the same names")
+                        .changed();
+                    ui.add_space(10.0);
+                    ui.label("Similar folders from");
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut tab_state.min_similarity)
+                                .range(1..=100)
+                                .suffix(" %"),
+                        )
+                        .changed();
+                    if changed {
+                        tab_state.reload();
+                    }
+                });
+
+                let duplicate_folders = self
+                    .tab_state_duplicate_folders
+                    .duplicate_folders
+                    .clone()
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "Groups: {}, similar folders: {}",
+                    duplicate_folders.groups.len(),
+                    duplicate_folders.similar_folders.len()
+                ));
+                let mut kept_folder = None;
+                let mut resolved_group = None;
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (index, group) in duplicate_folders.groups.iter().enumerate() {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            let folder = &group.kept_folders[0];
+                            unselectable_label(
+                                ui,
+                                RichText::new(format!(
+                                    "{} folders with {} files, {} each",
+                                    group.kept_folders.len() + group.deletable_folders.len(),
+                                    folder.nr_files,
+                                    format_file_size(folder.size)
+                                ))
+                                .strong(),
+                            );
+                            if ui
+                                .add_enabled(
+                                    !group.deletable_folders.is_empty(),
+                                    egui::Button::new("Resolve"),
+                                )
+                                .on_hover_text(
+                                    "Resolve the files of the crossed out folders using the duplicate resolution of the project",
+                                )
+                                .clicked()
+                            {
+                                resolved_group = Some(index);
+                            }
+                        });
+                        for folder in group.folders() {
+                            let is_kept = group.deletable_folders.is_empty()
+                                || group.kept_folders.iter().any(|x| x.path == folder.path);
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .radio(is_kept && !group.deletable_folders.is_empty(), "")
+                                    .on_hover_text("Keep this folder")
+                                    .clicked()
+                                {
+                                    kept_folder = Some((index, folder.path.clone()));
+                                }
+                                let text = RichText::new(&folder.path);
+                                unselectable_label(
+                                    ui,
+                                    if is_kept { text } else { text.strikethrough() },
+                                );
+                            });
+                        }
+                    }
+
+                    if !duplicate_folders.similar_folders.is_empty() {
+                        ui.separator();
+                        ui.collapsing(
+                            format!(
+                                "{} pairs of folders sharing part of their content",
+                                duplicate_folders.similar_folders.len()
+                            ),
+                            |ui| {
+                                for similar in duplicate_folders.similar_folders.iter() {
+                                    ui.label(format!(
+                                        "{:.0}%  {}  ↔  {}  ({} shared)",
+                                        similar.similarity(),
+                                        similar.folder_a.path,
+                                        similar.folder_b.path,
+                                        format_file_size(similar.shared_size)
+                                    ));
+                                }
+                            },
+                        );
+                    }
+                });
+
+                let groups = self
+                    .tab_state_duplicate_folders
+                    .duplicate_folders
+                    .as_mut()
+                    .map(|x| &mut x.groups);
+                if let (Some((index, path)), Some(groups)) = (kept_folder, groups) {
+                    groups[index].set_kept_folder(&path);
+                }
+                if let Some(index) = resolved_group {
+                    let group = duplicate_folders.groups[index].clone();
+                    if rfd::MessageDialog::new()
+                        .set_title("Resolve duplicate folders")
+                        .set_description(format!(
+                            "Are you sure you want to resolve the files of {} folders in favor of {}?",
+                            group.deletable_folders.len(),
+                            group.kept_folders[0].path
+                        ))
+                        .set_buttons(rfd::MessageButtons::YesNo)
+                        .show()
+                        .eq(&MessageDialogResult::Yes)
+                    {
+                        self.resolve_duplicate_folder_group(&group);
+                    }
+                }
+            });
+        });
+    }
+
+    fn resolve_duplicate_folder_group(&mut self, group: &DuplicateFolderGroup) {
+        match self
+            .app_state
+            .begin_operation("Resolve duplicate folders")
+            .and_then(|operation_id| resolve_duplicate_folder(&self.app_state, group, operation_id))
+        {
+            Ok(failed) => {
+                if !failed.is_empty() {
+                    error_dialog(&format!(
+                        "Failed to resolve {} files or folders. Errors:\n{}",
+                        failed.len(),
+                        failed
+                            .iter()
+                            .map(|(_, err)| err.to_string())
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    ));
+                }
+            }
+            Err(err) => error_dialog(&format!("Failed to resolve folders. Error: {}", err)),
+        }
+        self.tab_state_duplicate_folders.reload();
+        self.tab_state_empty_folders.reload();
+        self.tab_state_quarantine.reload();
+    }
+}
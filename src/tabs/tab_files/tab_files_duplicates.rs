@@ -1,18 +1,163 @@
-use crate::processing::find_duplicates::{
-    delete_duplicate, find_file_duplicates, get_duplicates_processing_state,
-    set_processing_message, FindDuplicatesStateType,
-};
-use crate::state::duplicate::FileKrakenDuplicate;
-use crate::state::AppState;
-use crate::utils::ui_elements::{colored_box, unselectable_label};
+use crate::ui::dialogs::error_dialog;
+use crate::ui::ui_elements::{colored_box, unselectable_label};
 use crate::FileKrakenApp;
-use egui::{Color32, RichText, Ui};
+use egui::{Color32, RichText, TextureHandle, Ui};
 use egui_extras::{Column, TableBuilder, TableRow};
+use file_kraken::processing::find_duplicates::{
+    apply_keeper_rules, find_file_duplicates, get_duplicates_processing_state, set_file_deletable,
+    set_processing_message, FindDuplicatesStateType,
+};
+use file_kraken::processing::resolve::resolve_duplicate;
+use file_kraken::state::deletion::DeletionMode;
+use file_kraken::state::duplicate::{
+    DuplicateResolution, FileKrakenDuplicate, FileKrakenDuplicateType,
+};
+use file_kraken::state::file::FileKrakenFile;
+use file_kraken::state::keeper_rule::KeeperRule;
+use file_kraken::utils::hashing::HashAlgorithm;
+use file_kraken::utils::image_hash::{read_image, ImageHashAlgorithm, IMAGE_HASH_BITS};
+use file_kraken::utils::{format_file_size, format_timestamp, get_longest_parent_path};
+use file_kraken::AppState;
 use rfd::MessageDialogResult;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
+/// Largest side of the thumbnails of similar images
+const THUMBNAIL_SIZE: u32 = 120;
+/// Number of threads decoding thumbnails
+const THUMBNAIL_THREADS: usize = 2;
+
+#[derive(Default)]
+pub struct DuplicatesTabState {
+    /// Keys of the duplicate groups listing all of their files
+    expanded_groups: HashSet<String>,
+    /// Index into [`KeeperRule::examples`] of the rule to add
+    new_keeper_rule: usize,
+    new_keeper_regex: String,
+    /// Thumbnails of similar images by path
+    thumbnails: HashMap<String, ThumbnailState>,
+    /// Started when the first thumbnail is shown
+    thumbnail_decoder: Option<ThumbnailDecoder>,
+}
+
+struct Thumbnail {
+    texture: TextureHandle,
+    /// Size of the full image
+    width: u32,
+    height: u32,
+}
+
+enum ThumbnailState {
+    /// Queued for decoding by the [`ThumbnailDecoder`]
+    Loading,
+    Loaded(Thumbnail),
+    /// The image can't be read or decoded
+    Failed,
+}
+
+/// Scaled down image and the size of the full image
+type DecodedThumbnail = (egui::ColorImage, u32, u32);
+
+/// A few worker threads decoding thumbnails in the order they were requested, they stop
+/// once the decoder is dropped
+struct ThumbnailDecoder {
+    requests: Sender<String>,
+    decoded: Receiver<(String, Option<DecodedThumbnail>)>,
+}
+
+impl ThumbnailDecoder {
+    fn new() -> Self {
+        let (requests, queue) = mpsc::channel::<String>();
+        let (decoded_sender, decoded) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..THUMBNAIL_THREADS {
+            let queue = queue.clone();
+            let decoded_sender = decoded_sender.clone();
+            thread::spawn(move || loop {
+                // the queue is only locked while waiting for the next path
+                let Ok(path) = queue.lock().unwrap().recv() else {
+                    return;
+                };
+                let thumbnail = decode_thumbnail(&path);
+                if decoded_sender.send((path, thumbnail)).is_err() {
+                    return;
+                }
+            });
+        }
+        Self { requests, decoded }
+    }
+}
+
+impl DuplicatesTabState {
+    /// Thumbnail of an image file, queued for decoding in the background the first time it
+    /// is shown
+    fn thumbnail(&mut self, ctx: &egui::Context, path: &str) -> &ThumbnailState {
+        let decoder = self
+            .thumbnail_decoder
+            .get_or_insert_with(ThumbnailDecoder::new);
+        while let Ok((decoded_path, decoded)) = decoder.decoded.try_recv() {
+            // thumbnails cleared in the meantime are queued again when they are shown
+            if let Some(state @ ThumbnailState::Loading) = self.thumbnails.get_mut(&decoded_path) {
+                *state = match decoded {
+                    Some((color_image, width, height)) => ThumbnailState::Loaded(Thumbnail {
+                        texture: ctx.load_texture(&decoded_path, color_image, Default::default()),
+                        width,
+                        height,
+                    }),
+                    None => ThumbnailState::Failed,
+                };
+            }
+        }
+        let state = self.thumbnails.entry(path.to_string()).or_insert_with(|| {
+            match decoder.requests.send(path.to_string()) {
+                Ok(()) => ThumbnailState::Loading,
+                Err(_) => ThumbnailState::Failed,
+            }
+        });
+        if let ThumbnailState::Loading = state {
+            ctx.request_repaint();
+        }
+        state
+    }
+}
+
+/// Scaled down image of a file and the size of the full image, `None` if it can't be read
+/// or decoded
+fn decode_thumbnail(path: &str) -> Option<DecodedThumbnail> {
+    let image = read_image(Path::new(path)).ok()?;
+    let (width, height) = image.dimensions();
+    let small = image::DynamicImage::ImageRgb8(image)
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8();
+    let color_image = egui::ColorImage::from_rgb(
+        [small.width() as usize, small.height() as usize],
+        small.as_raw(),
+    );
+    Some((color_image, width, height))
+}
+
+/// Row of the duplicates table
+enum DuplicateRow<'a> {
+    Group(&'a FileKrakenDuplicate),
+    File(&'a FileKrakenDuplicate, &'a FileKrakenFile),
+    /// Thumbnails of an expanded group of similar images
+    Thumbnails(&'a FileKrakenDuplicate),
+}
+
+impl DuplicateRow<'_> {
+    fn height(&self) -> f32 {
+        match self {
+            DuplicateRow::Thumbnails(_) => THUMBNAIL_SIZE as f32 + 24.0,
+            _ => 18.0,
+        }
+    }
+}
+
 impl FileKrakenApp {
     pub fn files_tab_duplicates(&mut self, ui: &mut Ui) {
         ui.vertical(|ui| {
@@ -33,10 +178,11 @@ impl FileKrakenApp {
                         FindDuplicatesStateType::None => {
                             ui.label("Idle");
                             if ui.button("Find Duplicates").clicked() {
+                                self.tab_state_duplicates.thumbnails.clear();
                                 // new thread
                                 let _app_state = self.app_state.clone();
                                 thread::spawn(move || {
-                                    find_file_duplicates(_app_state.clone());
+                                    run_find_duplicates_thread(_app_state);
                                 });
                             }
                         }
@@ -49,15 +195,22 @@ impl FileKrakenApp {
                         FindDuplicatesStateType::Processed => {
                             ui.label("Finished");
                             if ui.button("Re-run search").clicked() {
+                                self.tab_state_duplicates.thumbnails.clear();
                                 // new thread
                                 let _app_state = self.app_state.clone();
                                 thread::spawn(move || {
-                                    find_file_duplicates(_app_state.clone());
+                                    run_find_duplicates_thread(_app_state);
                                 });
                             }
                         }
                     }
                 });
+                self.hash_algorithm_selector(ui);
+                self.scan_archives_selector(ui);
+                self.similar_images_selector(ui);
+                self.deletion_mode_selector(ui);
+                self.duplicate_resolution_selector(ui);
+                self.keeper_rules_editor(ui);
                 ui.separator();
                 {
                     let duplicates = self
@@ -67,68 +220,69 @@ impl FileKrakenApp {
                         .read()
                         .unwrap();
                     ui.label(format!("Duplicates: {}", duplicates.len()));
-                    let nr_eligible = duplicates
-                        .iter()
-                        .filter(|x| x.deletable_file.is_some())
-                        .count();
+                    let nr_eligible: usize =
+                        duplicates.iter().map(|x| x.deletable_files.len()).sum();
+                    let bytes_reclaimed = *self
+                        .app_state
+                        .find_duplicates_processing
+                        .bytes_reclaimed
+                        .read()
+                        .unwrap();
+                    if bytes_reclaimed > 0 {
+                        ui.label(format!(
+                            "Reclaimed without deleting: {}",
+                            format_file_size(bytes_reclaimed)
+                        ));
+                    }
+                    let resolution = self
+                        .app_state
+                        .get_duplicate_resolution()
+                        .unwrap_or_default();
                     ui.horizontal(|ui| {
                         ui.label(format!("Eligible for deletion: {}", nr_eligible));
                         if nr_eligible > 1
                             && self
-                            .app_state
-                            .find_duplicates_processing
-                            .state
-                            .read()
-                            .unwrap()
-                            .eq(&FindDuplicatesStateType::Processed)
+                                .app_state
+                                .find_duplicates_processing
+                                .state
+                                .read()
+                                .unwrap()
+                                .eq(&FindDuplicatesStateType::Processed)
                         {
                             ui.add_space(5.0);
-                            if ui.button("Delete all eligible duplicates").clicked() {
-                                if rfd::MessageDialog::new()
-                                    .set_title("Delete all eligible duplicates")
-                                    .set_description(
-                                        "Are you sure you want to delete all eligible duplicates?",
-                                    )
+                            let description = resolve_all_description(resolution);
+                            if ui.button(description).clicked()
+                                && rfd::MessageDialog::new()
+                                    .set_title(description)
+                                    .set_description(format!(
+                                        "Are you sure you want to {}?",
+                                        description.to_lowercase()
+                                    ))
                                     .set_buttons(rfd::MessageButtons::YesNo)
                                     .show()
                                     .eq(&MessageDialogResult::Yes)
-                                {
-                                    let _duplicates = self
-                                        .app_state
-                                        .find_duplicates_processing
-                                        .duplicates
-                                        .read()
-                                        .unwrap()
-                                        .clone();
-                                    let _app_state = self.app_state.clone();
-                                    let total_nr_eligible = nr_eligible;
-                                    thread::spawn(move || {
-                                        let mut nr_deleted = 0;
-                                        set_processing_message(&_app_state, format!(
-                                            "Deleting eligible duplicates ... {:.2}% ({}/{})",
-                                            (nr_deleted as f64 / total_nr_eligible as f64) * 100.0,
-                                            nr_deleted, total_nr_eligible
-                                        ));
-                                        for duplicate in _duplicates.iter() {
-                                            if duplicate.deletable_file.is_some() {
-                                                set_processing_message(&_app_state, format!(
-                                                    "Deleting eligible duplicates ... {:.2}% ({}/{})",
-                                                    (nr_deleted as f64 / total_nr_eligible as f64) * 100.0,
-                                                    nr_deleted, total_nr_eligible
-                                                ));
-                                                delete_duplicate(&_app_state, duplicate);
-                                                nr_deleted += 1;
-                                            }
-                                        }
-                                        *get_duplicates_processing_state(&_app_state)
-                                            .deref_mut()
-                                            = FindDuplicatesStateType::Processed;
-                                    });
-                                }
+                            {
+                                let _duplicates = duplicates.clone();
+                                let _app_state = self.app_state.clone();
+                                thread::spawn(move || {
+                                    resolve_all_eligible_duplicates(
+                                        &_app_state,
+                                        &_duplicates,
+                                        nr_eligible,
+                                        description,
+                                    );
+                                });
                             }
                         }
                     });
                 }
+                let is_processed = self
+                    .app_state
+                    .find_duplicates_processing
+                    .state
+                    .read()
+                    .unwrap()
+                    .eq(&FindDuplicatesStateType::Processed);
                 let (eligible_duplicates, ineligible_duplicates) = {
                     let duplicates = self
                         .app_state
@@ -139,16 +293,34 @@ impl FileKrakenApp {
                     (
                         duplicates
                             .iter()
-                            .filter(|x| x.deletable_file.is_some())
+                            .filter(|x| !x.deletable_files.is_empty())
                             .cloned()
                             .collect::<Vec<_>>(),
                         duplicates
                             .iter()
-                            .filter(|x| x.deletable_file.is_none())
+                            .filter(|x| x.deletable_files.is_empty())
                             .cloned()
                             .collect::<Vec<_>>(),
                     )
                 };
+                // expanded groups are followed by a row for each of their files
+                let mut rows = vec![];
+                for duplicate in eligible_duplicates
+                    .iter()
+                    .chain(ineligible_duplicates.iter())
+                {
+                    rows.push(DuplicateRow::Group(duplicate));
+                    if self
+                        .tab_state_duplicates
+                        .expanded_groups
+                        .contains(&group_key(duplicate))
+                    {
+                        rows.extend(duplicate.files().map(|x| DuplicateRow::File(duplicate, x)));
+                        if duplicate.duplicate_type == FileKrakenDuplicateType::SimilarImage {
+                            rows.push(DuplicateRow::Thumbnails(duplicate));
+                        }
+                    }
+                }
 
                 egui::Frame::none()
                     .stroke(egui::Stroke::new(1.0, egui::Color32::DARK_GRAY))
@@ -158,39 +330,49 @@ impl FileKrakenApp {
                         let available_width = ui.available_width();
                         TableBuilder::new(ui)
                             .sense(egui::Sense::click())
+                            .column(Column::exact(20.0))
                             .column(Column::exact(25.0))
                             .column(Column::exact(15.0))
-                            .column(Column::exact(available_width / 2.0 - 40.0))
-                            .column(Column::exact(available_width / 2.0 - 40.0))
+                            .column(Column::exact(available_width / 2.0 - 50.0))
+                            .column(Column::exact(available_width / 2.0 - 50.0))
                             .cell_layout(egui::Layout::top_down_justified(egui::Align::LEFT))
                             .header(25.0, |mut row| {
+                                for _ in 0..3 {
+                                    row.col(|ui| {
+                                        ui.label(RichText::new(" ").strong());
+                                    });
+                                }
                                 row.col(|ui| {
-                                    ui.label(RichText::new(" ").strong());
-                                });
-                                row.col(|ui| {
-                                    ui.label(RichText::new(" ").strong());
-                                });
-                                row.col(|ui| {
-                                    ui.label(RichText::new("Location path 1").strong());
+                                    ui.label(RichText::new("Kept").strong());
                                 });
                                 row.col(|ui| {
-                                    ui.label(RichText::new("Location path 2").strong());
+                                    ui.label(RichText::new("Deletable").strong());
                                 });
                             })
                             .body(|body| {
-                                body.rows(
-                                    18.0,
-                                    eligible_duplicates.len() + ineligible_duplicates.len(),
-                                    |mut row| {
-                                        if let Some(duplicate) = eligible_duplicates
-                                            .iter()
-                                            .chain(ineligible_duplicates.iter())
-                                            .nth(row.index())
-                                        {
-                                            table_row(&self.app_state, &mut row, &duplicate);
-                                        }
-                                    },
-                                );
+                                let heights = rows.iter().map(|x| x.height());
+                                body.heterogeneous_rows(heights, |mut row| {
+                                    match rows[row.index()] {
+                                        DuplicateRow::Group(duplicate) => group_row(
+                                            &self.app_state,
+                                            &mut self.tab_state_duplicates,
+                                            &mut row,
+                                            duplicate,
+                                        ),
+                                        DuplicateRow::File(duplicate, file) => file_row(
+                                            &self.app_state,
+                                            &mut row,
+                                            duplicate,
+                                            file,
+                                            is_processed,
+                                        ),
+                                        DuplicateRow::Thumbnails(duplicate) => thumbnails_row(
+                                            &mut self.tab_state_duplicates,
+                                            &mut row,
+                                            duplicate,
+                                        ),
+                                    }
+                                });
                             });
                     });
             });
@@ -198,34 +380,480 @@ impl FileKrakenApp {
     }
 }
 
-fn table_row(app_state: &Arc<AppState>, row: &mut TableRow, duplicate: &FileKrakenDuplicate) {
-    let color = if duplicate.deletable_file.is_some() {
+impl FileKrakenApp {
+    fn hash_algorithm_selector(&mut self, ui: &mut Ui) {
+        let Ok(current) = self.app_state.get_hash_algorithm() else {
+            return;
+        };
+        let is_processing = matches!(
+            self.app_state
+                .find_duplicates_processing
+                .state
+                .read()
+                .unwrap()
+                .deref(),
+            FindDuplicatesStateType::Processing(_)
+        );
+        ui.horizontal(|ui| {
+            ui.label("Hash algorithm: ");
+            ui.add_enabled_ui(!is_processing, |ui| {
+                let mut selected = current;
+                egui::ComboBox::from_id_source("hash_algorithm")
+                    .selected_text(selected.to_string())
+                    .show_ui(ui, |ui| {
+                        for algorithm in HashAlgorithm::ALL {
+                            ui.selectable_value(&mut selected, algorithm, algorithm.to_string());
+                        }
+                    });
+                if selected != current {
+                    if let Err(err) = self.app_state.set_hash_algorithm(selected) {
+                        error_dialog(&format!("Failed to change hash algorithm. Error: {}", err));
+                    }
+                }
+            });
+        });
+    }
+}
+
+impl FileKrakenApp {
+    fn scan_archives_selector(&mut self, ui: &mut Ui) {
+        let Ok(current) = self.app_state.get_scan_archives() else {
+            return;
+        };
+        let mut selected = current;
+        ui.checkbox(&mut selected, "Look inside zip and tar archives")
+            .on_hover_text("Archives are read on the next scan of their location");
+        if selected != current {
+            if let Err(err) = self.app_state.set_scan_archives(selected) {
+                error_dialog(&format!(
+                    "Failed to change archive scanning. Error: {}",
+                    err
+                ));
+            }
+        }
+    }
+}
+
+impl FileKrakenApp {
+    fn similar_images_selector(&mut self, ui: &mut Ui) {
+        let (Ok(enabled), Ok(algorithm), Ok(threshold)) = (
+            self.app_state.get_similar_images(),
+            self.app_state.get_image_hash_algorithm(),
+            self.app_state.get_similar_image_threshold(),
+        ) else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            let mut selected_enabled = enabled;
+            ui.checkbox(&mut selected_enabled, "Find similar images")
+                .on_hover_text("Group JPEG, PNG and WebP images that look alike, e.g. resized or re-encoded copies");
+            let mut selected_algorithm = algorithm;
+            let mut selected_threshold = threshold;
+            ui.add_enabled_ui(enabled, |ui| {
+                egui::ComboBox::from_id_source("image_hash_algorithm")
+                    .selected_text(image_hash_algorithm_text(selected_algorithm))
+                    .show_ui(ui, |ui| {
+                        for algorithm in ImageHashAlgorithm::ALL {
+                            ui.selectable_value(
+                                &mut selected_algorithm,
+                                algorithm,
+                                image_hash_algorithm_text(algorithm),
+                            );
+                        }
+                    });
+                ui.label("differing in up to");
+                ui.add(
+                    egui::DragValue::new(&mut selected_threshold)
+                        .range(0..=IMAGE_HASH_BITS)
+                        .suffix(format!(" of {} bits", IMAGE_HASH_BITS)),
+                );
+            });
+            let result = if selected_enabled != enabled {
+                self.app_state.set_similar_images(selected_enabled)
+            } else if selected_algorithm != algorithm {
+                self.app_state.set_image_hash_algorithm(selected_algorithm)
+            } else if selected_threshold != threshold {
+                self.app_state.set_similar_image_threshold(selected_threshold)
+            } else {
+                Ok(())
+            };
+            if let Err(err) = result {
+                error_dialog(&format!(
+                    "Failed to change similar image search. Error: {}",
+                    err
+                ));
+            }
+        });
+    }
+}
+
+impl FileKrakenApp {
+    fn deletion_mode_selector(&mut self, ui: &mut Ui) {
+        let Ok(current) = self.app_state.get_deletion_mode() else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("Deleted files: ");
+            let mut selected = current;
+            egui::ComboBox::from_id_source("deletion_mode")
+                .selected_text(deletion_mode_text(selected))
+                .show_ui(ui, |ui| {
+                    for deletion_mode in DeletionMode::ALL {
+                        ui.selectable_value(
+                            &mut selected,
+                            deletion_mode,
+                            deletion_mode_text(deletion_mode),
+                        );
+                    }
+                });
+            if selected != current {
+                if let Err(err) = self.app_state.set_deletion_mode(selected) {
+                    error_dialog(&format!("Failed to change deletion mode. Error: {}", err));
+                }
+            }
+        });
+    }
+}
+
+impl FileKrakenApp {
+    fn duplicate_resolution_selector(&mut self, ui: &mut Ui) {
+        let Ok(current) = self.app_state.get_duplicate_resolution() else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("Resolve duplicates by: ");
+            let mut selected = current;
+            egui::ComboBox::from_id_source("duplicate_resolution")
+                .selected_text(duplicate_resolution_text(selected))
+                .show_ui(ui, |ui| {
+                    for resolution in DuplicateResolution::ALL {
+                        ui.selectable_value(
+                            &mut selected,
+                            resolution,
+                            duplicate_resolution_text(resolution),
+                        );
+                    }
+                });
+            if selected != current {
+                if let Err(err) = self.app_state.set_duplicate_resolution(selected) {
+                    error_dialog(&format!(
+                        "Failed to change duplicate resolution. Error: {}",
+                        err
+                    ));
+                }
+            }
+        });
+    }
+}
+
+impl FileKrakenApp {
+    fn keeper_rules_editor(&mut self, ui: &mut Ui) {
+        let Ok(rules) = self.app_state.get_keeper_rules() else {
+            return;
+        };
+        ui.collapsing(format!("Keeper rules ({})", rules.len()), |ui| {
+            ui.label(
+                "Each rule narrows down which file to keep, later rules break ties. All files still tied after the last rule are kept.",
+            );
+            let mut changed_rules = None;
+            for (i, rule) in rules.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}. {}", i + 1, rule.description()));
+                    if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                        let mut rules = rules.clone();
+                        rules.swap(i - 1, i);
+                        changed_rules = Some(rules);
+                    }
+                    if ui
+                        .add_enabled(i + 1 < rules.len(), egui::Button::new("⏷"))
+                        .clicked()
+                    {
+                        let mut rules = rules.clone();
+                        rules.swap(i, i + 1);
+                        changed_rules = Some(rules);
+                    }
+                    if ui.button("✖").clicked() {
+                        let mut rules = rules.clone();
+                        rules.remove(i);
+                        changed_rules = Some(rules);
+                    }
+                });
+            }
+            if rules.is_empty() {
+                ui.label("No rules, no duplicates will be deleted.");
+            }
+
+            ui.horizontal(|ui| {
+                let examples = KeeperRule::examples();
+                let tab_state = &mut self.tab_state_duplicates;
+                egui::ComboBox::from_id_source("new_keeper_rule")
+                    .selected_text(keeper_rule_kind_text(&examples[tab_state.new_keeper_rule]))
+                    .show_ui(ui, |ui| {
+                        for (i, rule) in examples.iter().enumerate() {
+                            ui.selectable_value(
+                                &mut tab_state.new_keeper_rule,
+                                i,
+                                keeper_rule_kind_text(rule),
+                            );
+                        }
+                    });
+                let new_rule = match &examples[tab_state.new_keeper_rule] {
+                    KeeperRule::PathMatches(_) => {
+                        ui.text_edit_singleline(&mut tab_state.new_keeper_regex);
+                        KeeperRule::PathMatches(tab_state.new_keeper_regex.clone())
+                    }
+                    rule => rule.clone(),
+                };
+                if ui.button("Add").clicked() {
+                    // parsing validates the regex
+                    match new_rule.to_string().parse() {
+                        Ok(new_rule) => {
+                            let mut rules = rules.clone();
+                            rules.push(new_rule);
+                            changed_rules = Some(rules);
+                        }
+                        Err(err) => error_dialog(&format!("Failed to add rule. Error: {}", err)),
+                    }
+                }
+            });
+
+            if let Some(rules) = changed_rules {
+                let result = self.app_state.set_keeper_rules(&rules).and_then(|_| {
+                    if self
+                        .app_state
+                        .find_duplicates_processing
+                        .state
+                        .read()
+                        .unwrap()
+                        .eq(&FindDuplicatesStateType::Processed)
+                    {
+                        apply_keeper_rules(&self.app_state)?;
+                    }
+                    Ok(())
+                });
+                if let Err(err) = result {
+                    error_dialog(&format!("Failed to change keeper rules. Error: {}", err));
+                }
+            }
+        });
+    }
+}
+
+fn keeper_rule_kind_text(rule: &KeeperRule) -> String {
+    match rule {
+        KeeperRule::PathMatches(_) => "Prefer paths matching a regex".to_string(),
+        rule => rule.description(),
+    }
+}
+
+fn image_hash_algorithm_text(algorithm: ImageHashAlgorithm) -> &'static str {
+    match algorithm {
+        ImageHashAlgorithm::AHash => "Average hash",
+        ImageHashAlgorithm::DHash => "Difference hash",
+        ImageHashAlgorithm::PHash => "Perceptual hash (DCT)",
+    }
+}
+
+fn duplicate_resolution_text(resolution: DuplicateResolution) -> &'static str {
+    match resolution {
+        DuplicateResolution::Delete => "Deleting them",
+        DuplicateResolution::Hardlink => "Replacing them with hard links",
+        DuplicateResolution::Reflink => "Sharing their extents (btrfs, XFS)",
+    }
+}
+
+fn deletion_mode_text(deletion_mode: DeletionMode) -> &'static str {
+    match deletion_mode {
+        DeletionMode::Permanent => "Delete permanently",
+        DeletionMode::Trash => "Move to trash",
+        DeletionMode::Quarantine => "Move to quarantine",
+    }
+}
+
+fn resolve_all_description(resolution: DuplicateResolution) -> &'static str {
+    match resolution {
+        DuplicateResolution::Delete => "Delete all eligible duplicates",
+        DuplicateResolution::Hardlink => "Replace all eligible duplicates with hard links",
+        DuplicateResolution::Reflink => "Deduplicate the extents of all eligible duplicates",
+    }
+}
+
+fn resolve_all_eligible_duplicates(
+    app_state: &Arc<AppState>,
+    duplicates: &[FileKrakenDuplicate],
+    total_nr_eligible: usize,
+    description: &str,
+) {
+    let operation_id = match app_state.begin_operation(description) {
+        Ok(operation_id) => operation_id,
+        Err(err) => {
+            error_dialog(&format!("Failed to resolve duplicates. Error: {}", err));
+            *get_duplicates_processing_state(app_state).deref_mut() =
+                FindDuplicatesStateType::Processed;
+            return;
+        }
+    };
+    let mut nr_resolved = 0;
+    let mut failed = vec![];
+    for duplicate in duplicates.iter() {
+        for deletable_file in duplicate.deletable_files.iter() {
+            set_processing_message(
+                app_state,
+                format!(
+                    "Resolving eligible duplicates ... {:.2}% ({}/{})",
+                    (nr_resolved as f64 / total_nr_eligible as f64) * 100.0,
+                    nr_resolved,
+                    total_nr_eligible
+                ),
+            );
+            if let Err(err) = resolve_duplicate(app_state, duplicate, deletable_file, operation_id)
+            {
+                failed.push(err.to_string());
+            }
+            nr_resolved += 1;
+        }
+    }
+    if !failed.is_empty() {
+        error_dialog(&format!(
+            "Failed to resolve {} duplicates. Errors:\n{}",
+            failed.len(),
+            failed.join("\n")
+        ));
+    }
+    *get_duplicates_processing_state(app_state).deref_mut() = FindDuplicatesStateType::Processed;
+}
+
+fn run_find_duplicates_thread(app_state: Arc<AppState>) {
+    if let Err(err) = find_file_duplicates(app_state) {
+        error_dialog(&format!("Failed to find duplicates. Error: {}", err));
+    }
+}
+
+/// Key identifying a duplicate group across searches, the files of exact duplicates share
+/// the same hash
+fn group_key(duplicate: &FileKrakenDuplicate) -> String {
+    if duplicate.duplicate_type == FileKrakenDuplicateType::SimilarImage {
+        // similar images have different hashes and may be reordered by marking them
+        return duplicate
+            .files()
+            .map(|x| format!("similar:{}", x.path))
+            .min()
+            .unwrap_or_default();
+    }
+    duplicate
+        .files()
+        .next()
+        .map(|x| x.hash.clone().unwrap_or_else(|| x.path.clone()))
+        .unwrap_or_default()
+}
+
+/// Label of the first of some files, counting the others
+fn files_label(files: &[FileKrakenFile]) -> String {
+    match files {
+        [] => String::new(),
+        [file] => file_label(file),
+        [file, others @ ..] => format!("{}  (+{} more)", file_label(file), others.len()),
+    }
+}
+
+fn group_row(
+    app_state: &Arc<AppState>,
+    tab_state: &mut DuplicatesTabState,
+    row: &mut TableRow,
+    duplicate: &FileKrakenDuplicate,
+) {
+    let color = if !duplicate.deletable_files.is_empty() {
         Color32::from_rgb(0, 0, 0)
     } else {
         Color32::from_rgb(78, 78, 78)
     };
 
     row.col(|ui| {
-        if duplicate.deletable_file.is_some() {
-            if ui.button("🗑️").clicked() {
-                if rfd::MessageDialog::new()
-                    .set_title("Delete file?")
-                    .set_description(format!(
-                        "Are you sure you want to delete the file \"{}\"?",
-                        duplicate.deletable_file.as_ref().unwrap().path.to_string()
-                    ))
-                    .set_buttons(rfd::MessageButtons::YesNo)
-                    .show()
-                    .eq(&MessageDialogResult::Yes)
-                {
-                    delete_duplicate(app_state, duplicate);
-                }
+        let key = group_key(duplicate);
+        let is_expanded = tab_state.expanded_groups.contains(&key);
+        if ui
+            .small_button(if is_expanded { "⏷" } else { "⏵" })
+            .on_hover_text(format!("Show all {} files", duplicate.files().count()))
+            .clicked()
+            && !tab_state.expanded_groups.remove(&key)
+        {
+            tab_state.expanded_groups.insert(key);
+        }
+    });
+    row.col(|ui| {
+        if duplicate.deletable_files.is_empty() {
+            return;
+        }
+        let resolution = app_state.get_duplicate_resolution().unwrap_or_default();
+        let target = match duplicate.deletable_files.as_slice() {
+            [file] => format!("\"{}\"", file.path),
+            files => format!("the {} marked files", files.len()),
+        };
+        let (icon, description, question) = match resolution {
+            DuplicateResolution::Delete => (
+                "🗑️",
+                "Delete duplicate",
+                format!("Are you sure you want to delete {}?", target),
+            ),
+            DuplicateResolution::Hardlink => (
+                "🔗",
+                "Replace with hard link",
+                format!(
+                    "Are you sure you want to replace {} with hard links?",
+                    target
+                ),
+            ),
+            DuplicateResolution::Reflink => (
+                "⛓",
+                "Deduplicate extents",
+                format!(
+                    "Are you sure you want {} to share the extents of the kept file?",
+                    target
+                ),
+            ),
+        };
+        if ui.button(icon).clicked()
+            && rfd::MessageDialog::new()
+                .set_title(format!("{}?", description))
+                .set_description(question)
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show()
+                .eq(&MessageDialogResult::Yes)
+        {
+            let operation_id =
+                match app_state.begin_operation(&format!("{} {}", description, target)) {
+                    Ok(operation_id) => operation_id,
+                    Err(err) => {
+                        error_dialog(&format!("Failed to resolve duplicate. Error: {}", err));
+                        return;
+                    }
+                };
+            let failed: Vec<String> = duplicate
+                .deletable_files
+                .iter()
+                .filter_map(|file| {
+                    resolve_duplicate(app_state, duplicate, file, operation_id)
+                        .err()
+                        .map(|err| err.to_string())
+                })
+                .collect();
+            if !failed.is_empty() {
+                error_dialog(&format!(
+                    "Failed to resolve duplicate. Errors:\n{}",
+                    failed.join("\n")
+                ));
             }
         }
     });
     row.col(|ui| {
-        if duplicate.deletable_file.is_some() {
+        if !duplicate.deletable_files.is_empty() {
             unselectable_label(ui, RichText::new("🆗").color(Color32::DARK_BLUE));
+        } else if let Some(verification_error) = &duplicate.verification_error {
+            ui.label(RichText::new("⚠").color(Color32::DARK_RED))
+                .on_hover_text(format!("Not resolved: {}", verification_error));
+        } else if duplicate.duplicate_type == FileKrakenDuplicateType::SimilarImage {
+            ui.label(RichText::new("🖼").color(Color32::DARK_BLUE))
+                .on_hover_text("Similar images, mark the ones to delete by hand");
         } else {
             unselectable_label(ui, RichText::new("❌").color(Color32::DARK_RED));
         }
@@ -233,22 +861,134 @@ fn table_row(app_state: &Arc<AppState>, row: &mut TableRow, duplicate: &FileKrak
     row.col(|ui| {
         unselectable_label(
             ui,
-            RichText::new(duplicate.other_files.get(0).unwrap().path.to_string()).color(color),
+            RichText::new(files_label(&duplicate.other_files)).color(color),
         );
     });
     row.col(|ui| {
-        if let Some(deletable_file) = &duplicate.deletable_file {
+        if duplicate.deletable_files.is_empty() {
             unselectable_label(
                 ui,
-                RichText::new(deletable_file.path.to_string())
-                    .color(color)
-                    .strikethrough(),
+                RichText::new(files_label(
+                    duplicate.other_files.get(1..).unwrap_or_default(),
+                ))
+                .color(color),
             );
         } else {
             unselectable_label(
                 ui,
-                RichText::new(duplicate.other_files.get(1).unwrap().path.to_string()).color(color),
+                RichText::new(files_label(&duplicate.deletable_files))
+                    .color(color)
+                    .strikethrough(),
             );
         }
     });
 }
+
+/// A single file of an expanded duplicate group, which can be marked to be kept or deleted
+fn file_row(
+    app_state: &Arc<AppState>,
+    row: &mut TableRow,
+    duplicate: &FileKrakenDuplicate,
+    file: &FileKrakenFile,
+    is_processed: bool,
+) {
+    let is_deletable = duplicate.is_deletable(&file.path);
+    let location =
+        get_longest_parent_path(&file.path, app_state.get_locations_list_readonly().iter())
+            .and_then(|x| app_state.get_location_clone(&x));
+
+    row.col(|_| {});
+    row.col(|ui| {
+        // every group keeps at least one of its files, archive members can't be deleted
+        let can_toggle = is_processed
+            && !file.is_archive_member()
+            && (is_deletable || duplicate.other_files.len() > 1);
+        if ui
+            .add_enabled(can_toggle, egui::SelectableLabel::new(is_deletable, "🗑"))
+            .on_hover_text(if is_deletable {
+                "Marked for deletion, click to keep"
+            } else {
+                "Kept, click to mark for deletion"
+            })
+            .clicked()
+        {
+            set_file_deletable(app_state, &file.path, !is_deletable);
+        }
+    });
+    row.col(|_| {});
+    row.col(|ui| {
+        let text = RichText::new(format!("    {}", file_label(file)));
+        unselectable_label(
+            ui,
+            if is_deletable {
+                text.strikethrough()
+            } else {
+                text
+            },
+        );
+    });
+    row.col(|ui| {
+        unselectable_label(
+            ui,
+            format!(
+                "{}  |  {}  |  modified {}  |  {}",
+                location
+                    .as_ref()
+                    .map(|x| x.location_type.to_string())
+                    .unwrap_or_default(),
+                format_file_size(file.file_len),
+                format_timestamp(file.time_modified),
+                location.map(|x| x.path).unwrap_or_default()
+            ),
+        );
+    });
+}
+
+/// Thumbnails of a group of similar images, the kept images next to the ones marked for
+/// deletion
+fn thumbnails_row(
+    tab_state: &mut DuplicatesTabState,
+    row: &mut TableRow,
+    duplicate: &FileKrakenDuplicate,
+) {
+    for _ in 0..3 {
+        row.col(|_| {});
+    }
+    for files in [&duplicate.other_files, &duplicate.deletable_files] {
+        row.col(|ui| {
+            let ctx = ui.ctx().clone();
+            ui.horizontal(|ui| {
+                for file in files {
+                    ui.vertical(|ui| match tab_state.thumbnail(&ctx, &file.path) {
+                        ThumbnailState::Loaded(thumbnail) => {
+                            ui.image(egui::load::SizedTexture::from_handle(&thumbnail.texture))
+                                .on_hover_text(&file.path);
+                            unselectable_label(
+                                ui,
+                                format!("{}×{}", thumbnail.width, thumbnail.height),
+                            );
+                        }
+                        ThumbnailState::Loading => {
+                            ui.add_sized(
+                                [THUMBNAIL_SIZE as f32, THUMBNAIL_SIZE as f32],
+                                egui::Spinner::new(),
+                            )
+                            .on_hover_text(&file.path);
+                        }
+                        ThumbnailState::Failed => {
+                            ui.label("No preview").on_hover_text(&file.path);
+                        }
+                    });
+                }
+            });
+        });
+    }
+}
+
+/// Path of a file, with the number of hard links to it if there are others
+fn file_label(file: &FileKrakenFile) -> String {
+    match file.nr_links {
+        Some(nr_links) if nr_links > 1 => format!("{}  🔗{}", file.path, nr_links),
+        _ => file.path.clone(),
+    }
+}
@@ -0,0 +1,200 @@
+use crate::ui::dialogs::error_dialog;
+use crate::ui::ui_elements::{colored_box, unselectable_label};
+use crate::FileKrakenApp;
+use egui::{Color32, RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+use file_kraken::processing::empty_folders::{
+    find_empty_folders, remove_empty_folders, EmptyFolder, JUNK_FILE_NAMES,
+};
+use file_kraken::utils::is_path_parent;
+use rfd::MessageDialogResult;
+use std::collections::HashSet;
+
+pub struct EmptyFoldersTabState {
+    /// Searched on demand, `None` after the folders of the project changed
+    empty_folders: Option<Vec<EmptyFolder>>,
+    selected_paths: HashSet<String>,
+    ignore_junk: bool,
+}
+
+impl Default for EmptyFoldersTabState {
+    fn default() -> Self {
+        EmptyFoldersTabState {
+            empty_folders: None,
+            selected_paths: HashSet::new(),
+            ignore_junk: true,
+        }
+    }
+}
+
+impl EmptyFoldersTabState {
+    /// Search the empty folders again the next time the tab is shown
+    pub fn reload(&mut self) {
+        self.empty_folders = None;
+    }
+}
+
+impl FileKrakenApp {
+    pub fn files_tab_empty_folders(&mut self, ui: &mut Ui) {
+        if self.tab_state_empty_folders.empty_folders.is_none() {
+            let tab_state = &mut self.tab_state_empty_folders;
+            match find_empty_folders(&self.app_state, tab_state.ignore_junk) {
+                Ok(folders) => {
+                    let paths: HashSet<&String> = folders.iter().map(|x| &x.path).collect();
+                    tab_state.selected_paths.retain(|x| paths.contains(x));
+                    tab_state.empty_folders = Some(folders);
+                }
+                Err(err) => {
+                    tab_state.empty_folders = Some(vec![]);
+                    error_dialog(&format!("Failed to find empty folders. Error: {}", err));
+                }
+            }
+        }
+
+        ui.vertical(|ui| {
+            colored_box(ui, Color32::LIGHT_GRAY, |ui| {
+                ui.label("Empty folders");
+            });
+            colored_box(ui, Color32::TRANSPARENT, |ui| {
+                let empty_folders = self
+                    .tab_state_empty_folders
+                    .empty_folders
+                    .clone()
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "Folders without files in the scanned locations: {}",
+                    empty_folders.len()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Refresh").clicked() {
+                        self.tab_state_empty_folders.reload();
+                    }
+                    if ui
+                        .checkbox(
+                            &mut self.tab_state_empty_folders.ignore_junk,
+                            "Ignore junk files",
+                        )
+                        .on_hover_text(format!(
+                            "Treat folders that only contain {} as empty",
+                            JUNK_FILE_NAMES.join(", ")
+                        ))
+                        .changed()
+                    {
+                        self.tab_state_empty_folders.reload();
+                    }
+                    if ui.button("Select all").clicked() {
+                        self.tab_state_empty_folders.selected_paths =
+                            empty_folders.iter().map(|x| x.path.clone()).collect();
+                    }
+                    let nr_selected = self.tab_state_empty_folders.selected_paths.len();
+                    if ui
+                        .add_enabled(
+                            nr_selected > 0,
+                            egui::Button::new(format!("Remove selected ({})", nr_selected)),
+                        )
+                        .clicked()
+                        && rfd::MessageDialog::new()
+                            .set_title("Remove empty folders")
+                            .set_description(format!(
+                                "Are you sure you want to remove {} empty folders? Junk files inside them are deleted using the deletion mode of the project.",
+                                nr_selected
+                            ))
+                            .set_buttons(rfd::MessageButtons::YesNo)
+                            .show()
+                            .eq(&MessageDialogResult::Yes)
+                    {
+                        self.remove_selected_empty_folders(&empty_folders);
+                    }
+                });
+
+                egui::Frame::none()
+                    .stroke(egui::Stroke::new(1.0, Color32::DARK_GRAY))
+                    .outer_margin(12.0)
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        let available_width = ui.available_width();
+                        TableBuilder::new(ui)
+                            .column(Column::exact(25.0))
+                            .column(Column::exact(available_width - 125.0))
+                            .column(Column::exact(90.0))
+                            .cell_layout(egui::Layout::top_down_justified(egui::Align::LEFT))
+                            .header(25.0, |mut row| {
+                                row.col(|ui| {
+                                    ui.label(RichText::new(" ").strong());
+                                });
+                                row.col(|ui| {
+                                    ui.label(RichText::new("Folder path").strong());
+                                });
+                                row.col(|ui| {
+                                    ui.label(RichText::new("Junk files").strong());
+                                });
+                            })
+                            .body(|body| {
+                                body.rows(18.0, empty_folders.len(), |mut row| {
+                                    let folder = &empty_folders[row.index()];
+                                    let selected_paths =
+                                        &mut self.tab_state_empty_folders.selected_paths;
+                                    row.col(|ui| {
+                                        let mut selected = selected_paths.contains(&folder.path);
+                                        if ui.checkbox(&mut selected, "").changed() {
+                                            // a folder can only be removed along with
+                                            // its empty subfolders
+                                            for x in empty_folders.iter() {
+                                                if selected
+                                                    && (x.path == folder.path
+                                                        || is_path_parent(&x.path, &folder.path))
+                                                {
+                                                    selected_paths.insert(x.path.clone());
+                                                } else if !selected
+                                                    && (x.path == folder.path
+                                                        || is_path_parent(&folder.path, &x.path))
+                                                {
+                                                    selected_paths.remove(&x.path);
+                                                }
+                                            }
+                                        }
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(ui, &folder.path);
+                                    });
+                                    row.col(|ui| {
+                                        if !folder.junk_files.is_empty() {
+                                            ui.label(folder.junk_files.len().to_string())
+                                                .on_hover_text(folder.junk_files.join("\n"));
+                                        }
+                                    });
+                                });
+                            });
+                    });
+            });
+        });
+    }
+
+    fn remove_selected_empty_folders(&mut self, empty_folders: &[EmptyFolder]) {
+        let selected_paths = std::mem::take(&mut self.tab_state_empty_folders.selected_paths);
+        let selected_folders: Vec<EmptyFolder> = empty_folders
+            .iter()
+            .filter(|x| selected_paths.contains(&x.path))
+            .cloned()
+            .collect();
+        match self.app_state.begin_operation("Remove empty folders") {
+            Ok(operation_id) => {
+                let failed = remove_empty_folders(&self.app_state, &selected_folders, operation_id);
+                if !failed.is_empty() {
+                    error_dialog(&format!(
+                        "Failed to remove {} folders. Errors:\n{}",
+                        failed.len(),
+                        failed
+                            .iter()
+                            .map(|(_, err)| err.to_string())
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    ));
+                }
+            }
+            Err(err) => error_dialog(&format!("Failed to remove folders. Error: {}", err)),
+        }
+        self.tab_state_empty_folders.reload();
+        self.tab_state_quarantine.reload();
+    }
+}
@@ -0,0 +1,239 @@
+use crate::ui::dialogs::error_dialog;
+use crate::ui::file_manager::open_containing_folder;
+use crate::ui::ui_elements::{colored_box, unselectable_label};
+use crate::FileKrakenApp;
+use egui::{Color32, RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+use file_kraken::processing::large_files::{find_large_files, LargeFile, LargeFilesOrder};
+use file_kraken::state::location::FileKrakenLocationType;
+use file_kraken::utils::{format_file_size, format_timestamp};
+use rfd::MessageDialogResult;
+use std::collections::HashSet;
+
+/// Number of files listed at most, the largest ones are shown
+const MAX_LARGE_FILES: usize = 1000;
+
+pub struct LargeFilesTabState {
+    /// Queried on demand, `None` after the filter or the files changed
+    large_files: Option<Vec<LargeFile>>,
+    min_size_mib: u64,
+    /// Locations to list files of, all locations if empty
+    selected_locations: HashSet<String>,
+    order: LargeFilesOrder,
+}
+
+impl Default for LargeFilesTabState {
+    fn default() -> Self {
+        LargeFilesTabState {
+            large_files: None,
+            min_size_mib: 100,
+            selected_locations: HashSet::new(),
+            order: LargeFilesOrder::default(),
+        }
+    }
+}
+
+impl LargeFilesTabState {
+    /// Query the large files again the next time the tab is shown
+    pub fn reload(&mut self) {
+        self.large_files = None;
+    }
+}
+
+impl FileKrakenApp {
+    pub fn files_tab_large_files(&mut self, ui: &mut Ui) {
+        if self.tab_state_large_files.large_files.is_none() {
+            let tab_state = &mut self.tab_state_large_files;
+            let location_paths: Vec<String> =
+                tab_state.selected_locations.iter().cloned().collect();
+            match find_large_files(
+                &self.app_state,
+                tab_state.min_size_mib * 1024 * 1024,
+                &location_paths,
+                tab_state.order,
+                MAX_LARGE_FILES,
+            ) {
+                Ok(files) => tab_state.large_files = Some(files),
+                Err(err) => {
+                    tab_state.large_files = Some(vec![]);
+                    error_dialog(&format!("Failed to find large files. Error: {}", err));
+                }
+            }
+        }
+
+        ui.vertical(|ui| {
+            colored_box(ui, Color32::LIGHT_GRAY, |ui| {
+                ui.label("Large files");
+            });
+            colored_box(ui, Color32::TRANSPARENT, |ui| {
+                let large_files = self
+                    .tab_state_large_files
+                    .large_files
+                    .clone()
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "Files: {} ({}){}",
+                    large_files.len(),
+                    format_file_size(large_files.iter().map(|x| x.file.file_len).sum()),
+                    if large_files.len() >= MAX_LARGE_FILES {
+                        format!(", only the {} largest are listed", MAX_LARGE_FILES)
+                    } else {
+                        String::new()
+                    }
+                ));
+                ui.horizontal(|ui| {
+                    let tab_state = &mut self.tab_state_large_files;
+                    let mut changed = ui.button("Refresh").clicked();
+                    ui.add_space(10.0);
+                    ui.label("Minimum size");
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut tab_state.min_size_mib)
+                                .range(0..=1024 * 1024)
+                                .suffix(" MiB"),
+                        )
+                        .changed();
+                    ui.add_space(10.0);
+                    ui.label("Sort by");
+                    for order in LargeFilesOrder::ALL {
+                        changed |= ui
+                            .selectable_value(
+                                &mut tab_state.order,
+                                order,
+                                large_files_order_text(order),
+                            )
+                            .changed();
+                    }
+                    ui.add_space(10.0);
+                    let locations_text = match tab_state.selected_locations.len() {
+                        0 => "All locations".to_string(),
+                        x => format!("{} locations", x),
+                    };
+                    ui.menu_button(locations_text, |ui| {
+                        for location in self.app_state.get_locations_list_readonly().iter() {
+                            if location.location_type == FileKrakenLocationType::Excluded {
+                                continue;
+                            }
+                            let mut selected =
+                                tab_state.selected_locations.contains(&location.path);
+                            if ui.checkbox(&mut selected, &location.path).changed() {
+                                if selected {
+                                    tab_state.selected_locations.insert(location.path.clone());
+                                } else {
+                                    tab_state.selected_locations.remove(&location.path);
+                                }
+                                changed = true;
+                            }
+                        }
+                    });
+                    if changed {
+                        tab_state.reload();
+                    }
+                });
+
+                egui::Frame::none()
+                    .stroke(egui::Stroke::new(1.0, Color32::DARK_GRAY))
+                    .outer_margin(12.0)
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        let available_width = ui.available_width();
+                        TableBuilder::new(ui)
+                            .column(Column::exact(50.0))
+                            .column(Column::exact(90.0))
+                            .column(Column::exact(120.0))
+                            .column(Column::exact((available_width - 260.0) * 0.7))
+                            .column(Column::exact((available_width - 260.0) * 0.3))
+                            .cell_layout(egui::Layout::top_down_justified(egui::Align::LEFT))
+                            .header(25.0, |mut row| {
+                                for title in [" ", "Size", "Modified", "Path", "Location"] {
+                                    row.col(|ui| {
+                                        ui.label(RichText::new(title).strong());
+                                    });
+                                }
+                            })
+                            .body(|body| {
+                                body.rows(18.0, large_files.len(), |mut row| {
+                                    let large_file = &large_files[row.index()];
+                                    let file_path = &large_file.file.path;
+                                    row.col(|ui| {
+                                        ui.horizontal(|ui| {
+                                            if ui
+                                                .small_button("📂")
+                                                .on_hover_text("Open containing folder")
+                                                .clicked()
+                                            {
+                                                if let Err(err) = open_containing_folder(file_path)
+                                                {
+                                                    error_dialog(&format!(
+                                                        "Failed to open folder. Error: {}",
+                                                        err
+                                                    ));
+                                                }
+                                            }
+                                            if ui
+                                                .small_button("🗑")
+                                                .on_hover_text("Delete")
+                                                .clicked()
+                                            {
+                                                self.delete_large_file(file_path);
+                                            }
+                                        });
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(
+                                            ui,
+                                            format_file_size(large_file.file.file_len),
+                                        );
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(
+                                            ui,
+                                            format_timestamp(large_file.file.time_modified),
+                                        );
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(ui, file_path);
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(ui, &large_file.location_path);
+                                    });
+                                });
+                            });
+                    });
+            });
+        });
+    }
+
+    /// Delete a file using the project's deletion mode after asking the user
+    fn delete_large_file(&mut self, file_path: &str) {
+        let confirmed = rfd::MessageDialog::new()
+            .set_title("Delete file")
+            .set_description(format!(
+                "Are you sure you want to delete the file: \"{}\"?",
+                file_path
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show()
+            .eq(&MessageDialogResult::Yes);
+        if !confirmed {
+            return;
+        }
+        let result = self
+            .app_state
+            .begin_operation(&format!("Delete {}", file_path))
+            .and_then(|operation_id| self.app_state.delete_file(operation_id, file_path));
+        if let Err(err) = result {
+            error_dialog(&format!("Failed to delete file. Error: {}", err));
+        }
+        self.tab_state_large_files.reload();
+        self.tab_state_quarantine.reload();
+    }
+}
+
+fn large_files_order_text(order: LargeFilesOrder) -> &'static str {
+    match order {
+        LargeFilesOrder::Size => "Size",
+        LargeFilesOrder::Age => "Age",
+        LargeFilesOrder::Location => "Location",
+    }
+}
@@ -1,29 +1,258 @@
-use crate::utils::ui_elements::colored_box;
+use crate::ui::dialogs::error_dialog;
+use crate::ui::ui_elements::colored_box;
 use crate::FileKrakenApp;
-use egui::Ui;
+use egui::{Align2, Color32, FontId, RichText, Sense, Ui, Vec2};
+use file_kraken::processing::file_types::find_extension_mismatches;
+use file_kraken::processing::statistics::{get_file_statistics, FileStatistics, StatisticsRow};
+use file_kraken::state::file::FileKrakenFile;
+use file_kraken::state::location::FileKrakenLocationState;
+use file_kraken::utils::format_file_size;
+use file_kraken::FileKrakenError;
+use rfd::FileDialog;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+/// Number of extensions shown in the chart, the full list is exported
+const MAX_CHART_EXTENSIONS: usize = 15;
+
+/// Statistics of the files and the files whose content does not match their extension
+type OverviewResult = Result<(FileStatistics, Vec<FileKrakenFile>), FileKrakenError>;
+
+#[derive(Default)]
+pub struct OverviewTabState {
+    /// Queried in the background on demand, `None` after the files changed
+    statistics: Option<FileStatistics>,
+    loading: Option<Receiver<OverviewResult>>,
+    /// Files whose content does not match their extension, queried with the statistics
+    extension_mismatches: Vec<FileKrakenFile>,
+    /// Location states when the statistics were queried, to refresh them after scans
+    location_states: Vec<FileKrakenLocationState>,
+    measure_count: bool,
+}
+
+impl OverviewTabState {
+    /// Query the statistics again the next time the tab is shown
+    pub fn reload(&mut self) {
+        self.statistics = None;
+        self.loading = None;
+    }
+}
 
 impl FileKrakenApp {
     pub fn files_tab_overview(&mut self, ui: &mut Ui) {
+        let location_states: Vec<FileKrakenLocationState> = self
+            .app_state
+            .get_locations_list_readonly()
+            .iter()
+            .map(|x| x.location_state.clone())
+            .collect();
+        if self.tab_state_overview.location_states != location_states {
+            self.tab_state_overview.location_states = location_states;
+            self.tab_state_overview.reload();
+        }
+        self.poll_overview_statistics(ui);
+        let tab_state = &mut self.tab_state_overview;
+        let statistics = tab_state.statistics.clone().unwrap_or_default();
+
         ui.vertical(|ui| {
-            colored_box(ui, egui::Color32::LIGHT_GRAY, |ui| {
+            colored_box(ui, Color32::LIGHT_GRAY, |ui| {
                 ui.label("Files Overview");
             });
-            colored_box(ui, egui::Color32::TRANSPARENT, |ui| {
+            colored_box(ui, Color32::TRANSPARENT, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("Total Files: ");
-                    let mut total_files = 0;
-                    for location in self.app_state.get_locations_list_readonly().iter() {
-                        total_files += self
-                            .app_state
-                            .get_files_by_location(&location.path)
-                            .unwrap()
-                            .read()
-                            .unwrap()
-                            .len()
+                    ui.label(statistics.nr_files.to_string());
+                    ui.label(format!("({})", format_file_size(statistics.size)));
+                    if tab_state.loading.is_some() {
+                        ui.spinner();
+                    }
+                    ui.add_space(20.0);
+                    if ui.button("Refresh").clicked() {
+                        tab_state.reload();
+                    }
+                    ui.label("Measure");
+                    ui.selectable_value(&mut tab_state.measure_count, false, "Size");
+                    ui.selectable_value(&mut tab_state.measure_count, true, "Files");
+                    if ui.button("Export CSV").clicked() {
+                        export_statistics(&statistics);
                     }
-                    ui.label(total_files.to_string());
+                });
+                let measure_count = tab_state.measure_count;
+
+                let extension_mismatches = &tab_state.extension_mismatches;
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    if !extension_mismatches.is_empty() {
+                        ui.collapsing(
+                            format!(
+                                "{} files whose content does not match their extension",
+                                extension_mismatches.len()
+                            ),
+                            |ui| {
+                                for file in extension_mismatches.iter() {
+                                    ui.label(format!(
+                                        "{}  ({})",
+                                        file.path,
+                                        file.mime_type.as_deref().unwrap_or_default()
+                                    ));
+                                }
+                            },
+                        );
+                    }
+                    ui.columns(2, |columns| {
+                        let category_rows: Vec<&StatisticsRow> =
+                            statistics.by_category.iter().map(|(_, x)| x).collect();
+                        bar_chart(&mut columns[0], "By type", &category_rows, measure_count);
+                        let extension_rows: Vec<&StatisticsRow> = statistics
+                            .by_extension
+                            .iter()
+                            .take(MAX_CHART_EXTENSIONS)
+                            .collect();
+                        bar_chart(
+                            &mut columns[0],
+                            "By extension",
+                            &extension_rows,
+                            measure_count,
+                        );
+                        let mime_type_rows: Vec<&StatisticsRow> =
+                            statistics.by_mime_type.iter().collect();
+                        bar_chart(
+                            &mut columns[0],
+                            "By content",
+                            &mime_type_rows,
+                            measure_count,
+                        );
+                        let location_rows: Vec<&StatisticsRow> =
+                            statistics.by_location.iter().collect();
+                        bar_chart(
+                            &mut columns[1],
+                            "By location",
+                            &location_rows,
+                            measure_count,
+                        );
+                        let age_rows: Vec<&StatisticsRow> = statistics.by_age.iter().collect();
+                        bar_chart(
+                            &mut columns[1],
+                            "By last modification",
+                            &age_rows,
+                            measure_count,
+                        );
+                    });
                 });
             });
         });
     }
+
+    /// Start querying the statistics if needed and pick up the result
+    fn poll_overview_statistics(&mut self, ui: &mut Ui) {
+        let tab_state = &mut self.tab_state_overview;
+        if let Some(receiver) = tab_state.loading.as_ref() {
+            match receiver.try_recv() {
+                Ok(Ok((statistics, extension_mismatches))) => {
+                    tab_state.statistics = Some(statistics);
+                    tab_state.extension_mismatches = extension_mismatches;
+                    tab_state.loading = None;
+                }
+                Ok(Err(err)) => {
+                    tab_state.statistics = Some(FileStatistics::default());
+                    tab_state.extension_mismatches.clear();
+                    tab_state.loading = None;
+                    error_dialog(&format!("Failed to count files. Error: {}", err));
+                }
+                Err(mpsc::TryRecvError::Empty) => ui.ctx().request_repaint(),
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    // don't query again every frame if the thread died
+                    tab_state.statistics = Some(FileStatistics::default());
+                    tab_state.loading = None;
+                }
+            }
+            return;
+        }
+        if tab_state.statistics.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let app_state = self.app_state.clone();
+        thread::spawn(move || {
+            let _ =
+                sender.send(get_file_statistics(&app_state).and_then(|statistics| {
+                    Ok((statistics, find_extension_mismatches(&app_state)?))
+                }));
+        });
+        tab_state.loading = Some(receiver);
+    }
+}
+
+/// Horizontal bars scaled to the largest row, measuring sizes or numbers of files
+fn bar_chart(ui: &mut Ui, title: &str, rows: &[&StatisticsRow], measure_count: bool) {
+    let value = |row: &StatisticsRow| {
+        if measure_count {
+            row.nr_files
+        } else {
+            row.size
+        }
+    };
+    let max_value = rows
+        .iter()
+        .map(|x| value(x))
+        .max()
+        .unwrap_or_default()
+        .max(1);
+
+    ui.add_space(8.0);
+    ui.label(RichText::new(title).strong());
+    for row in rows {
+        let (rect, response) =
+            ui.allocate_exact_size(Vec2::new(ui.available_width(), 18.0), Sense::hover());
+        let label_width = (rect.width() * 0.35).min(220.0);
+        let bar_width = (rect.width() - label_width) * value(row) as f32 / max_value as f32;
+        let painter = ui.painter_at(rect);
+        let label = if row.label.is_empty() {
+            "(none)"
+        } else {
+            &row.label
+        };
+        painter.text(
+            rect.left_center(),
+            Align2::LEFT_CENTER,
+            label,
+            FontId::proportional(12.0),
+            ui.visuals().text_color(),
+        );
+        let bar = egui::Rect::from_min_size(
+            rect.left_top() + Vec2::new(label_width, 2.0),
+            Vec2::new(bar_width.max(1.0), rect.height() - 4.0),
+        );
+        painter.rect_filled(bar, 2.0, Color32::from_rgb(120, 170, 220));
+        painter.text(
+            bar.left_center() + Vec2::new(4.0, 0.0),
+            Align2::LEFT_CENTER,
+            if measure_count {
+                row.nr_files.to_string()
+            } else {
+                format_file_size(row.size)
+            },
+            FontId::proportional(11.0),
+            Color32::BLACK,
+        );
+        response.on_hover_text(format!(
+            "{}: {} files, {}",
+            label,
+            row.nr_files,
+            format_file_size(row.size)
+        ));
+    }
+}
+
+fn export_statistics(statistics: &FileStatistics) {
+    let Some(path) = FileDialog::new()
+        .add_filter("CSV", &["csv"])
+        .set_file_name("file-statistics.csv")
+        .save_file()
+    else {
+        return;
+    };
+    if let Err(err) = std::fs::write(&path, statistics.to_csv()) {
+        error_dialog(&format!("Failed to export statistics. Error: {}", err));
+    }
 }
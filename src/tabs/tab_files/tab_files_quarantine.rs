@@ -0,0 +1,183 @@
+use crate::ui::dialogs::error_dialog;
+use crate::ui::ui_elements::{colored_box, unselectable_label};
+use crate::FileKrakenApp;
+use egui::{Color32, RichText, Ui};
+use egui_extras::{Column, TableBuilder};
+use file_kraken::state::quarantine::QuarantinedFile;
+use file_kraken::utils::format_file_size;
+use rfd::MessageDialogResult;
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Default)]
+pub struct QuarantineTabState {
+    /// Loaded from the project on demand, `None` after the quarantine changed
+    quarantined_files: Option<Vec<QuarantinedFile>>,
+    selected_ids: HashSet<i64>,
+    purge_min_age_days: u64,
+}
+
+impl QuarantineTabState {
+    /// Load the quarantined files from the project again the next time the tab is shown
+    pub fn reload(&mut self) {
+        self.quarantined_files = None;
+    }
+}
+
+impl FileKrakenApp {
+    pub fn files_tab_quarantine(&mut self, ui: &mut Ui) {
+        if self.tab_state_quarantine.quarantined_files.is_none() {
+            match self.app_state.get_quarantined_files() {
+                Ok(files) => self.tab_state_quarantine.quarantined_files = Some(files),
+                Err(err) => {
+                    self.tab_state_quarantine.quarantined_files = Some(vec![]);
+                    error_dialog(&format!("Failed to load quarantine. Error: {}", err));
+                }
+            }
+        }
+
+        ui.vertical(|ui| {
+            colored_box(ui, Color32::LIGHT_GRAY, |ui| {
+                ui.label("Quarantine");
+            });
+            colored_box(ui, Color32::TRANSPARENT, |ui| {
+                if let Ok(quarantine_dir) = self.app_state.get_quarantine_dir() {
+                    ui.label(format!("Directory: {}", quarantine_dir));
+                }
+                let quarantined_files = self
+                    .tab_state_quarantine
+                    .quarantined_files
+                    .clone()
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "Quarantined files: {} ({})",
+                    quarantined_files.len(),
+                    format_file_size(quarantined_files.iter().map(|x| x.file_len).sum())
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Refresh").clicked() {
+                        self.tab_state_quarantine.reload();
+                    }
+                    let nr_selected = self.tab_state_quarantine.selected_ids.len();
+                    if ui
+                        .add_enabled(
+                            nr_selected > 0,
+                            egui::Button::new(format!("Restore selected ({})", nr_selected)),
+                        )
+                        .clicked()
+                    {
+                        self.restore_selected_quarantined_files();
+                    }
+                    ui.add_space(10.0);
+                    ui.label("Purge files older than");
+                    ui.add(
+                        egui::DragValue::new(&mut self.tab_state_quarantine.purge_min_age_days)
+                            .range(0..=3650),
+                    );
+                    ui.label("days");
+                    if ui.button("Purge").clicked()
+                        && rfd::MessageDialog::new()
+                            .set_title("Purge quarantine")
+                            .set_description(format!(
+                                "Are you sure you want to permanently delete all files quarantined more than {} days ago?",
+                                self.tab_state_quarantine.purge_min_age_days
+                            ))
+                            .set_buttons(rfd::MessageButtons::YesNo)
+                            .show()
+                            .eq(&MessageDialogResult::Yes)
+                    {
+                        if let Err(err) = self
+                            .app_state
+                            .purge_quarantine(self.tab_state_quarantine.purge_min_age_days)
+                        {
+                            error_dialog(&format!("Failed to purge quarantine. Error: {}", err));
+                        }
+                        self.tab_state_quarantine.reload();
+                        self.tab_state_quarantine.selected_ids.clear();
+                    }
+                });
+
+                egui::Frame::none()
+                    .stroke(egui::Stroke::new(1.0, Color32::DARK_GRAY))
+                    .outer_margin(12.0)
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        let available_width = ui.available_width();
+                        TableBuilder::new(ui)
+                            .column(Column::exact(25.0))
+                            .column(Column::exact(available_width - 240.0))
+                            .column(Column::exact(90.0))
+                            .column(Column::exact(110.0))
+                            .cell_layout(egui::Layout::top_down_justified(egui::Align::LEFT))
+                            .header(25.0, |mut row| {
+                                row.col(|ui| {
+                                    ui.label(RichText::new(" ").strong());
+                                });
+                                row.col(|ui| {
+                                    ui.label(RichText::new("Original path").strong());
+                                });
+                                row.col(|ui| {
+                                    ui.label(RichText::new("Size").strong());
+                                });
+                                row.col(|ui| {
+                                    ui.label(RichText::new("Quarantined").strong());
+                                });
+                            })
+                            .body(|body| {
+                                let now = SystemTime::now()
+                                    .duration_since(UNIX_EPOCH)
+                                    .map(|x| x.as_secs())
+                                    .unwrap_or(0);
+                                body.rows(18.0, quarantined_files.len(), |mut row| {
+                                    let file = &quarantined_files[row.index()];
+                                    row.col(|ui| {
+                                        let mut selected =
+                                            self.tab_state_quarantine.selected_ids.contains(&file.id);
+                                        if ui.checkbox(&mut selected, "").changed() {
+                                            if selected {
+                                                self.tab_state_quarantine.selected_ids.insert(file.id);
+                                            } else {
+                                                self.tab_state_quarantine.selected_ids.remove(&file.id);
+                                            }
+                                        }
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(ui, &file.original_path);
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(ui, format_file_size(file.file_len));
+                                    });
+                                    row.col(|ui| {
+                                        unselectable_label(
+                                            ui,
+                                            format!(
+                                                "{} days ago",
+                                                now.saturating_sub(file.time_quarantined)
+                                                    / (24 * 60 * 60)
+                                            ),
+                                        );
+                                    });
+                                });
+                            });
+                    });
+            });
+        });
+    }
+
+    fn restore_selected_quarantined_files(&mut self) {
+        let mut failed = vec![];
+        for id in self.tab_state_quarantine.selected_ids.drain() {
+            if let Err(err) = self.app_state.restore_quarantined_file(id) {
+                failed.push(err.to_string());
+            }
+        }
+        if !failed.is_empty() {
+            error_dialog(&format!(
+                "Failed to restore {} files. Errors:\n{}",
+                failed.len(),
+                failed.join("\n")
+            ));
+        }
+        self.tab_state_quarantine.reload();
+    }
+}
@@ -1,11 +1,15 @@
-use crate::processing::scan::scan_location_files;
-use crate::state::location::{FileKrakenLocationState, FileKrakenLocationType};
-use crate::state::AppState;
-use crate::utils::ui_elements::{colored_box, unselectable_label};
+use crate::ui::dialogs::error_dialog;
+use crate::ui::ui_elements::{colored_box, unselectable_label};
 use crate::FileKrakenApp;
-use egui::{Label, RichText, TextStyle, Ui, Vec2, Window};
+use egui::{Label, RichText, TextStyle, Ui, Window};
 use egui_extras::{Column, TableBody, TableBuilder};
+use file_kraken::processing::scan::scan_location_files;
+use file_kraken::state::location::{
+    FileKrakenLocation, FileKrakenLocationState, FileKrakenLocationType,
+};
+use file_kraken::AppState;
 use rfd::FileDialog;
+use std::cmp::Reverse;
 use std::sync::Arc;
 use std::thread;
 
@@ -18,6 +22,8 @@ pub struct LocationTabState {
     modify_location_dialog_open: bool,
     modify_location_path: String,
     modify_location_type: FileKrakenLocationType,
+    new_location_ignore_pattern: String,
+    new_project_ignore_pattern: String,
 }
 
 impl FileKrakenApp {
@@ -72,6 +78,28 @@ fn right_column(_self: &mut FileKrakenApp, ui: &mut Ui) {
                                     location.location_type.clone();
                             });
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("Priority:");
+                        let mut priority = location.priority;
+                        if ui
+                            .add(egui::DragValue::new(&mut priority))
+                            .on_hover_text(
+                                "Duplicates in locations with a higher priority are kept",
+                            )
+                            .changed()
+                        {
+                            if let Err(err) = _self.app_state.set_location_priority(
+                                true,
+                                &location.path,
+                                priority,
+                            ) {
+                                error_dialog(&format!(
+                                    "Failed to change location priority. Error: {}",
+                                    err
+                                ));
+                            }
+                        }
+                    });
                     ui.horizontal(|ui| {
                         ui.label("Files:");
                         ui.label(
@@ -116,7 +144,9 @@ fn right_column(_self: &mut FileKrakenApp, ui: &mut Ui) {
                         }
                     });
 
-                    if location.location_state != FileKrakenLocationState::Scanning
+                    if location.location_type == FileKrakenLocationType::Excluded {
+                        ui.label("Excluded locations are not scanned");
+                    } else if location.location_state != FileKrakenLocationState::Scanning
                         && location.location_state != FileKrakenLocationState::Deleting
                     {
                         ui.button("Scan location")
@@ -127,12 +157,30 @@ fn right_column(_self: &mut FileKrakenApp, ui: &mut Ui) {
                                 let _app_state = _self.app_state.clone();
                                 let _path = location.path.clone();
                                 thread::spawn(move || {
-                                    scan_location_files(_app_state.clone(), &_path);
+                                    match scan_location_files(_app_state.clone(), &_path) {
+                                        Ok(report) => show_failed_paths(&report.failed_paths),
+                                        Err(err) => error_dialog(&format!(
+                                            "Failed to scan location. Error: {}",
+                                            err
+                                        )),
+                                    }
                                 });
                             });
                     }
                 });
             });
+
+            colored_box(ui, egui::Color32::LIGHT_GRAY, |ui| {
+                ui.label("Ignore patterns:");
+            });
+            colored_box(ui, egui::Color32::TRANSPARENT, |ui| {
+                ignore_patterns_editor(
+                    &_self.app_state,
+                    ui,
+                    Some(&location.path),
+                    &mut _self.tab_state_locations.new_location_ignore_pattern,
+                );
+            });
         } else {
             colored_box(ui, egui::Color32::LIGHT_GRAY, |ui| {
                 ui.vertical_centered_justified(|ui| ui.label("Selected location not found"));
@@ -145,6 +193,57 @@ fn right_column(_self: &mut FileKrakenApp, ui: &mut Ui) {
     }
 }
 
+// list the ignore patterns of a location (or the project) with controls to edit them
+fn ignore_patterns_editor(
+    app_state: &Arc<AppState>,
+    ui: &mut Ui,
+    location_path: Option<&str>,
+    new_pattern: &mut String,
+) {
+    ui.vertical(|ui| {
+        let patterns = app_state
+            .get_ignore_patterns(location_path)
+            .unwrap_or_default();
+        for pattern in patterns {
+            ui.horizontal(|ui| {
+                if ui.button("🗑️").clicked() {
+                    if let Err(err) = app_state.remove_ignore_pattern(location_path, &pattern) {
+                        error_dialog(&format!("Failed to remove ignore pattern. Error: {}", err));
+                    }
+                }
+                ui.monospace(&pattern);
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(new_pattern)
+                .on_hover_text("Globs like *.tmp, **/node_modules or .git/, or re:<regex>");
+            if ui.button("➕").clicked() && !new_pattern.is_empty() {
+                match app_state.add_ignore_pattern(location_path, new_pattern) {
+                    Ok(_) => new_pattern.clear(),
+                    Err(err) => {
+                        error_dialog(&format!("Failed to add ignore pattern. Error: {}", err))
+                    }
+                }
+            }
+        });
+        ui.label("Changes apply on the next scan");
+    });
+}
+
+fn show_failed_paths(failed_paths: &[String]) {
+    if failed_paths.len() > 10 {
+        error_dialog(&format!(
+            "Failed to get file path for {} files. Example: {}",
+            failed_paths.len(),
+            failed_paths[0]
+        ));
+    } else {
+        for failed_path in failed_paths {
+            error_dialog(&format!("Failed to get file path for file {}", failed_path));
+        }
+    }
+}
+
 fn modify_location_dialog_window(_self: &mut FileKrakenApp, ui: &mut Ui) {
     let app_state = _self.app_state.clone();
     let mut modify_location_dialog_open = _self.tab_state_locations.modify_location_dialog_open;
@@ -181,10 +280,12 @@ fn modify_location_dialog_window(_self: &mut FileKrakenApp, ui: &mut Ui) {
             ui.vertical_centered_justified(|ui| {
                 if ui.button("Modify").clicked() {
                     modify_location_dialog_open = false;
-                    app_state.modify_location_type(
-                        &_self.tab_state_locations.modify_location_path.clone(),
+                    if let Err(err) = app_state.modify_location_type(
+                        &_self.tab_state_locations.modify_location_path,
                         _self.tab_state_locations.modify_location_type.clone(),
-                    );
+                    ) {
+                        error_dialog(&format!("Failed to modify location. Error: {}", err));
+                    }
                 }
                 if ui.button("Cancel").clicked() {
                     modify_location_dialog_open = false;
@@ -243,12 +344,14 @@ fn add_location_dialog_window(_self: &mut FileKrakenApp, ui: &mut Ui) {
                 if ui.button("Add").clicked() {
                     add_location_dialog_open = false;
 
-                    app_state.add_location(
+                    if let Err(err) = app_state.add_location(
                         true,
                         &_self.tab_state_locations.add_location_path,
                         &_self.tab_state_locations.add_location_type,
                         &FileKrakenLocationState::Unscanned,
-                    );
+                    ) {
+                        error_dialog(&format!("Failed to add location. Error: {}", err));
+                    }
 
                     // reset the dialog
                     _self.tab_state_locations.add_location_path = String::new();
@@ -268,13 +371,21 @@ fn left_column(_self: &mut FileKrakenApp, ui: &mut Ui) {
         .inner_margin(6.0)
         .show(ui, |ui| {
             let available_width = ui.available_width();
+            // highest priority first, duplicates in the upper locations are kept
+            let mut locations = app_state.get_locations_list_readonly().clone();
+            locations.sort_by_key(|x| Reverse(x.priority));
+            let mut location_paths: Vec<String> =
+                locations.iter().map(|x| x.path.clone()).collect();
+            let mut reordered = false;
             TableBuilder::new(ui)
                 .sense(egui::Sense::click())
                 .column(Column::exact(16.0))
+                .column(Column::exact(44.0))
                 .column(Column::exact(20.0))
-                .column(Column::exact(available_width - 36.0))
+                .column(Column::exact(available_width - 80.0))
                 .cell_layout(egui::Layout::top_down_justified(egui::Align::LEFT))
                 .header(25.0, |mut row| {
+                    row.col(|_| {});
                     row.col(|_| {});
                     row.col(|_| {});
                     row.col(|ui| {
@@ -282,34 +393,59 @@ fn left_column(_self: &mut FileKrakenApp, ui: &mut Ui) {
                     });
                 })
                 .body(|mut body| {
-                    for location in app_state.get_locations_list_readonly().iter() {
-                        table_row(
+                    for (i, location) in locations.iter().enumerate() {
+                        if let Some(move_up) = table_row(
                             &app_state,
                             &mut body,
-                            &location.path,
-                            &location.location_type,
-                            &location.location_state,
+                            location,
+                            i,
+                            locations.len(),
                             &mut _self.tab_state_locations.selected_location,
-                        );
+                        ) {
+                            location_paths.swap(i, if move_up { i - 1 } else { i + 1 });
+                            reordered = true;
+                        }
                     }
                 });
+            if reordered {
+                if let Err(err) = app_state.set_location_order(&location_paths) {
+                    error_dialog(&format!("Failed to reorder locations. Error: {}", err));
+                }
+            }
         });
     ui.vertical_centered_justified(|ui| {
         ui.button("➕ add location").clicked().then(|| {
             _self.tab_state_locations.add_location_dialog_open = true;
         });
     });
+
+    colored_box(ui, egui::Color32::LIGHT_GRAY, |ui| {
+        ui.label("Project ignore patterns:");
+    });
+    colored_box(ui, egui::Color32::TRANSPARENT, |ui| {
+        ignore_patterns_editor(
+            &app_state,
+            ui,
+            None,
+            &mut _self.tab_state_locations.new_project_ignore_pattern,
+        );
+    });
 }
 
-// render a single row of the locations table
+// render a single row of the locations table, returning whether the location should be
+// moved up or down
 fn table_row(
     app_state: &Arc<AppState>,
     body: &mut TableBody,
-    path: &str,
-    location_type: &FileKrakenLocationType,
-    location_state: &FileKrakenLocationState,
+    location: &FileKrakenLocation,
+    index: usize,
+    nr_locations: usize,
     selected_location: &mut Option<String>,
-) {
+) -> Option<bool> {
+    let path = location.path.as_str();
+    let location_type = &location.location_type;
+    let location_state = &location.location_state;
+    let mut move_up = None;
     body.row(18.0, |mut row| {
         row.set_selected(
             selected_location
@@ -321,7 +457,7 @@ fn table_row(
             if ui.button("🗑️").clicked()
                 && rfd::MessageDialog::new()
                     .set_title("Remove location")
-                    .set_description(&format!(
+                    .set_description(format!(
                         "Are you sure you want to remove the location: \"{}\"?",
                         path
                     ))
@@ -332,10 +468,30 @@ fn table_row(
                 let _app_state = app_state.clone();
                 let _path = path.to_string();
                 thread::spawn(move || {
-                    _app_state.remove_location(true, &_path);
+                    if let Err(err) = _app_state.remove_location(true, &_path) {
+                        error_dialog(&format!("Failed to remove location. Error: {}", err));
+                    }
                 });
             }
         });
+        row.col(|ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(index > 0, egui::Button::new("⏶").small())
+                    .on_hover_text("Higher priority")
+                    .clicked()
+                {
+                    move_up = Some(true);
+                }
+                if ui
+                    .add_enabled(index + 1 < nr_locations, egui::Button::new("⏷").small())
+                    .on_hover_text("Lower priority")
+                    .clicked()
+                {
+                    move_up = Some(false);
+                }
+            });
+        });
         row.col(|ui| match location_type {
             FileKrakenLocationType::Preferred => {
                 ui.add(Label::selectable(Label::new("⭐"), false));
@@ -360,4 +516,5 @@ fn table_row(
             *selected_location = Some(path.to_string());
         });
     });
+    move_up
 }
@@ -0,0 +1,18 @@
+use log::error;
+
+pub fn error_dialog(msg: &str) {
+    error!("{}", msg);
+    let dialog = rfd::MessageDialog::new()
+        .set_title("Error")
+        .set_description(msg)
+        .set_buttons(rfd::MessageButtons::Ok);
+    dialog.show();
+}
+
+pub fn info_dialog(title: &str, msg: &str) {
+    let dialog = rfd::MessageDialog::new()
+        .set_title(title)
+        .set_description(msg)
+        .set_buttons(rfd::MessageButtons::Ok);
+    dialog.show();
+}
@@ -0,0 +1,28 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+/// Show the folder containing a file in the file manager of the system
+pub fn open_containing_folder(file_path: &str) -> io::Result<()> {
+    let folder = Path::new(file_path)
+        .parent()
+        .unwrap_or(Path::new(file_path));
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", file_path));
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(file_path);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(folder);
+        command
+    };
+    // reap the process once the file manager was started
+    let mut child = command.spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
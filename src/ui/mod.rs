@@ -0,0 +1,4 @@
+pub mod dialogs;
+pub mod file_manager;
+pub mod treemap;
+pub mod ui_elements;
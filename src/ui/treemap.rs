@@ -0,0 +1,76 @@
+use egui::{Pos2, Rect};
+
+/// Split `rect` into one rectangle per size using the squarified treemap algorithm, which
+/// keeps the rectangles close to squares.
+///
+/// The sizes have to be sorted largest first, the rectangles are returned in the same order.
+pub fn squarify(sizes: &[u64], rect: Rect) -> Vec<Rect> {
+    let total: f64 = sizes.iter().map(|&x| x as f64).sum();
+    if total <= 0.0 || rect.area() <= 0.0 {
+        return vec![Rect::NOTHING; sizes.len()];
+    }
+    let scale = rect.area() as f64 / total;
+    let areas: Vec<f64> = sizes.iter().map(|&x| x as f64 * scale).collect();
+
+    let mut rects = Vec::with_capacity(sizes.len());
+    let mut remaining = rect;
+    let mut start = 0;
+    while start < areas.len() {
+        // add items to the row along the short side as long as its aspect ratios improve
+        let side = remaining.width().min(remaining.height()) as f64;
+        let mut end = start + 1;
+        let mut worst = worst_aspect_ratio(&areas[start..end], side);
+        while end < areas.len() {
+            let next_worst = worst_aspect_ratio(&areas[start..=end], side);
+            if next_worst > worst {
+                break;
+            }
+            worst = next_worst;
+            end += 1;
+        }
+
+        let row_area: f64 = areas[start..end].iter().sum();
+        if remaining.width() >= remaining.height() {
+            // a column at the left
+            let width = (row_area / remaining.height() as f64) as f32;
+            let mut y = remaining.min.y;
+            for &area in areas[start..end].iter() {
+                let height = (area / width as f64) as f32;
+                rects.push(Rect::from_min_max(
+                    Pos2::new(remaining.min.x, y),
+                    Pos2::new(remaining.min.x + width, y + height),
+                ));
+                y += height;
+            }
+            remaining.min.x += width;
+        } else {
+            // a row at the top
+            let height = (row_area / remaining.width() as f64) as f32;
+            let mut x = remaining.min.x;
+            for &area in areas[start..end].iter() {
+                let width = (area / height as f64) as f32;
+                rects.push(Rect::from_min_max(
+                    Pos2::new(x, remaining.min.y),
+                    Pos2::new(x + width, remaining.min.y + height),
+                ));
+                x += width;
+            }
+            remaining.min.y += height;
+        }
+        start = end;
+    }
+    rects
+}
+
+/// The largest aspect ratio of the rectangles of a row laid out along `side`
+fn worst_aspect_ratio(row: &[f64], side: f64) -> f64 {
+    let sum: f64 = row.iter().sum();
+    let max = row.iter().copied().fold(f64::MIN, f64::max);
+    let min = row.iter().copied().fold(f64::MAX, f64::min);
+    if sum <= 0.0 || min <= 0.0 {
+        return f64::MAX;
+    }
+    let side_squared = side * side;
+    let sum_squared = sum * sum;
+    (side_squared * max / sum_squared).max(sum_squared / (side_squared * min))
+}
@@ -0,0 +1,497 @@
+use crate::utils::magic::{sniff_mime_type, MAGIC_BYTES_LEN};
+use flate2::read::MultiGzDecoder;
+use log::error;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use xz2::read::XzDecoder;
+use zip::{CompressionMethod, ZipArchive};
+
+/// Separates the path of an archive from the name of a member in virtual member paths,
+/// e.g. `backup.zip!/dir/file.jpg`
+pub const ARCHIVE_MEMBER_SEPARATOR: &str = "!/";
+
+/// A file inside an archive, its content is passed to the visitor of [`read_archive`]
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path inside the archive, without a leading slash
+    pub name: String,
+    /// Seconds since the unix epoch, 0 if unknown
+    pub time_modified: u64,
+}
+
+/// Virtual path of a member of an archive
+pub fn archive_member_path(archive_path: &str, member_name: &str) -> String {
+    format!(
+        "{}{}{}",
+        archive_path,
+        ARCHIVE_MEMBER_SEPARATOR,
+        member_name.trim_start_matches('/')
+    )
+}
+
+/// Split a virtual member path into the archive path and the member name.
+///
+/// Folder and member names may contain the separator as well, so the path is split at the
+/// last separator following a path that `is_archive` accepts, e.g.
+/// `/data/wow!/backup.zip!/a.jpg` is a member of `/data/wow!/backup.zip`.
+pub fn split_archive_member_path(
+    path: &str,
+    mut is_archive: impl FnMut(&str) -> bool,
+) -> Option<(&str, &str)> {
+    path.rmatch_indices(ARCHIVE_MEMBER_SEPARATOR)
+        .map(|(i, separator)| (&path[..i], &path[i + separator.len()..]))
+        .find(|(archive_path, _)| is_archive(archive_path))
+}
+
+/// Whether the members of archives of a detected MIME type can be read
+pub fn is_readable_archive(mime_type: &str) -> bool {
+    matches!(
+        mime_type,
+        "application/zip" | "application/x-tar" | "application/gzip" | "application/x-xz"
+    )
+}
+
+/// Call `visit` with every regular file of a zip, tar, gzip or xz compressed archive.
+///
+/// A compressed file that does not contain a tar archive has a single member named like the
+/// archive without its `.gz` or `.xz` extension. Encrypted zip members and members using
+/// other compression methods than deflate are skipped.
+pub fn read_archive(
+    path: &Path,
+    mut visit: impl FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut magic = vec![];
+    (&mut file)
+        .take(MAGIC_BYTES_LEN as u64)
+        .read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let mime_type = sniff_mime_type(&magic);
+    match mime_type {
+        "application/zip" => read_zip(BufReader::new(file), &mut visit),
+        "application/x-tar" => read_tar(BufReader::new(file), &mut visit),
+        "application/gzip" | "application/x-xz" => {
+            let entry = ArchiveEntry {
+                name: path
+                    .file_stem()
+                    .map(|x| x.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                time_modified: file
+                    .metadata()?
+                    .modified()
+                    .ok()
+                    .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |x| x.as_secs()),
+            };
+            let reader = BufReader::new(file);
+            if mime_type == "application/gzip" {
+                read_compressed(MultiGzDecoder::new(reader), &entry, &mut visit)
+            } else {
+                read_compressed(XzDecoder::new_multi_decoder(reader), &entry, &mut visit)
+            }
+        }
+        mime_type => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("can't read the members of {} files", mime_type),
+        )),
+    }
+}
+
+/// Visit the tar archive inside a decompressed stream, or the whole stream as `entry` if it
+/// is no tar archive
+fn read_compressed(
+    mut decoder: impl Read,
+    entry: &ArchiveEntry,
+    visit: &mut impl FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut head = vec![];
+    (&mut decoder)
+        .take(MAGIC_BYTES_LEN as u64)
+        .read_to_end(&mut head)?;
+    let is_tar = sniff_mime_type(&head) == "application/x-tar";
+    let mut reader = Cursor::new(head).chain(decoder);
+    if is_tar {
+        read_tar(reader, visit)
+    } else {
+        visit(entry, &mut reader)
+    }
+}
+
+/// Visit the regular files of a tar archive, long names of GNU and pax headers included
+fn read_tar(
+    reader: impl Read,
+    visit: &mut impl FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !matches!(
+            entry.header().entry_type(),
+            tar::EntryType::Regular | tar::EntryType::Continuous
+        ) {
+            continue;
+        }
+        let archive_entry = ArchiveEntry {
+            name: entry
+                .path()?
+                .to_string_lossy()
+                .trim_start_matches("./")
+                .to_string(),
+            time_modified: entry.header().mtime().unwrap_or_default(),
+        };
+        let size = entry.size();
+        let mut counted = CountingReader {
+            reader: &mut entry,
+            nr_bytes: 0,
+        };
+        visit(&archive_entry, &mut counted)?;
+        // skip what the visitor did not read, the archive is cut off if anything is missing
+        io::copy(&mut counted, &mut io::sink())?;
+        if counted.nr_bytes < size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated tar archive",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Counts the bytes read through it
+struct CountingReader<R> {
+    reader: R,
+    nr_bytes: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let nr_bytes = self.reader.read(buf)?;
+        self.nr_bytes += nr_bytes as u64;
+        Ok(nr_bytes)
+    }
+}
+
+/// Visit the regular files of a zip archive, zip64 archives included
+fn read_zip(
+    reader: impl Read + Seek,
+    visit: &mut impl FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut archive = ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let entry = {
+            let member = archive.by_index_raw(i)?;
+            if !member.is_file() {
+                continue;
+            }
+            if member.encrypted() {
+                error!("Skipping encrypted zip member {}", member.name());
+                continue;
+            }
+            if !matches!(
+                member.compression(),
+                CompressionMethod::Stored | CompressionMethod::Deflated
+            ) {
+                error!(
+                    "Skipping zip member {} using compression method {}",
+                    member.name(),
+                    member.compression()
+                );
+                continue;
+            }
+            ArchiveEntry {
+                name: member.name().to_string(),
+                time_modified: member
+                    .last_modified()
+                    .map_or(0, |x| dos_time_to_unix(x.datepart(), x.timepart())),
+            }
+        };
+        visit(&entry, &mut archive.by_index(i)?)?;
+    }
+    Ok(())
+}
+
+/// Seconds since the unix epoch of an MS-DOS date and time, which have no time zone
+fn dos_time_to_unix(date: u16, time: u16) -> u64 {
+    let year = i64::from(date >> 9) + 1980;
+    let month = i64::from((date >> 5) & 0xf).clamp(1, 12);
+    let day = i64::from(date & 0x1f).max(1);
+    // days since the epoch of the proleptic gregorian calendar
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = i64::from(time >> 11) * 3600
+        + i64::from((time >> 5) & 0x3f) * 60
+        + i64::from(time & 0x1f) * 2;
+    (days * 86400 + seconds).max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use xz2::write::XzEncoder;
+
+    const TAR_BLOCK_SIZE: u64 = 512;
+
+    /// Tar header block of an entry
+    fn tar_header(name: &str, size: u64, entry_type: u8) -> Vec<u8> {
+        let mut header = vec![0u8; TAR_BLOCK_SIZE as usize];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[136..147].copy_from_slice(format!("{:011o}", 1_700_000_000).as_bytes());
+        header[156] = entry_type;
+        header[257..265].copy_from_slice(b"ustar\x0000");
+        // the checksum is calculated with spaces in its own field
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&x| u32::from(x)).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        header
+    }
+
+    /// Data of a tar entry padded to whole blocks
+    fn tar_data(data: &[u8]) -> Vec<u8> {
+        let mut padded = data.to_vec();
+        padded.resize(data.len().div_ceil(TAR_BLOCK_SIZE as usize) * 512, 0);
+        padded
+    }
+
+    /// Pax record whose length includes the digits of the length itself
+    fn pax_record(key: &str, value: &str) -> String {
+        let content_len = key.len() + value.len() + 3;
+        let mut length = content_len + 1;
+        while length != content_len + length.to_string().len() {
+            length = content_len + length.to_string().len();
+        }
+        format!("{} {}={}\n", length, key, value)
+    }
+
+    /// Names and contents of all members of an archive
+    fn members(
+        read: impl FnOnce(
+            &mut dyn FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+        ) -> io::Result<()>,
+    ) -> io::Result<Vec<(String, String)>> {
+        let mut members = vec![];
+        read(&mut |entry, data| {
+            let mut content = String::new();
+            data.read_to_string(&mut content)?;
+            members.push((entry.name.clone(), content));
+            Ok(())
+        })?;
+        Ok(members)
+    }
+
+    fn pax_tar() -> Vec<u8> {
+        let long_name = format!("{}/photo.jpg", "folder".repeat(20));
+        let records = pax_record("path", &long_name) + &pax_record("size", "5");
+        [
+            tar_header("PaxHeaders/photo.jpg", records.len() as u64, b'x'),
+            tar_data(records.as_bytes()),
+            // the size in the header is overridden by the pax record
+            tar_header("photo.jpg", 0, b'0'),
+            tar_data(b"hello"),
+            tar_header("notes.txt", 3, b'0'),
+            tar_data(b"abc"),
+            vec![0; 2 * TAR_BLOCK_SIZE as usize],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn pax_records_set_the_path_and_size_of_the_next_entry() {
+        let long_name = format!("{}/photo.jpg", "folder".repeat(20));
+        let tar = pax_tar();
+        assert_eq!(
+            members(|mut visit| read_tar(tar.as_slice(), &mut visit)).unwrap(),
+            [
+                (long_name, "hello".to_string()),
+                ("notes.txt".to_string(), "abc".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn gzip_streams_contain_a_tar_archive_or_a_single_member() {
+        let entry = ArchiveEntry {
+            name: "notes".to_string(),
+            time_modified: 0,
+        };
+        let gzip = |data: &[u8]| {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let tar_gz = gzip(&pax_tar());
+        let tar_members = members(|mut visit| {
+            read_compressed(MultiGzDecoder::new(tar_gz.as_slice()), &entry, &mut visit)
+        })
+        .unwrap();
+        assert_eq!(tar_members.len(), 2);
+        assert_eq!(tar_members[1], ("notes.txt".to_string(), "abc".to_string()));
+
+        let text_gz = gzip(b"just some text");
+        assert_eq!(
+            members(|mut visit| {
+                read_compressed(MultiGzDecoder::new(text_gz.as_slice()), &entry, &mut visit)
+            })
+            .unwrap(),
+            [("notes".to_string(), "just some text".to_string())]
+        );
+    }
+
+    #[test]
+    fn xz_streams_contain_a_tar_archive_or_a_single_member() {
+        let entry = ArchiveEntry {
+            name: "notes".to_string(),
+            time_modified: 0,
+        };
+        let xz = |data: &[u8]| {
+            let mut encoder = XzEncoder::new(vec![], 6);
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let tar_xz = xz(&pax_tar());
+        let tar_members = members(|mut visit| {
+            read_compressed(
+                XzDecoder::new_multi_decoder(tar_xz.as_slice()),
+                &entry,
+                &mut visit,
+            )
+        })
+        .unwrap();
+        assert_eq!(tar_members.len(), 2);
+        assert_eq!(tar_members[1], ("notes.txt".to_string(), "abc".to_string()));
+
+        let text_xz = xz(b"just some text");
+        assert_eq!(
+            members(|mut visit| {
+                read_compressed(
+                    XzDecoder::new_multi_decoder(text_xz.as_slice()),
+                    &entry,
+                    &mut visit,
+                )
+            })
+            .unwrap(),
+            [("notes".to_string(), "just some text".to_string())]
+        );
+    }
+
+    #[test]
+    fn zip64_fields_replace_the_saturated_sizes_and_offsets() {
+        let name = b"dir/photo.jpg";
+        let content = b"hello zip64";
+        let dos_time = [0x00u8, 0x00, 0x21, 0x00];
+        let mut crc = flate2::Crc::new();
+        crc.update(content);
+        let crc = crc.sum().to_le_bytes();
+
+        let mut zip = b"PK\x03\x04".to_vec();
+        zip.extend([45, 0, 0, 0, 0, 0]);
+        zip.extend(dos_time);
+        zip.extend(crc);
+        zip.extend((content.len() as u32).to_le_bytes());
+        zip.extend((content.len() as u32).to_le_bytes());
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+        zip.extend(name);
+        zip.extend(content);
+
+        let directory_offset = zip.len() as u64;
+        let mut directory = b"PK\x01\x02".to_vec();
+        directory.extend([45, 0, 45, 0, 0, 0, 0, 0]);
+        directory.extend(dos_time);
+        directory.extend(crc);
+        directory.extend(u32::MAX.to_le_bytes());
+        directory.extend(u32::MAX.to_le_bytes());
+        directory.extend((name.len() as u16).to_le_bytes());
+        directory.extend(28u16.to_le_bytes());
+        directory.extend([0u8; 10]);
+        directory.extend(u32::MAX.to_le_bytes());
+        directory.extend(name);
+        directory.extend(1u16.to_le_bytes());
+        directory.extend(24u16.to_le_bytes());
+        directory.extend((content.len() as u64).to_le_bytes());
+        directory.extend((content.len() as u64).to_le_bytes());
+        directory.extend(0u64.to_le_bytes());
+        zip.extend(&directory);
+
+        // zip64 end of central directory record and its locator
+        let record_offset = zip.len() as u64;
+        zip.extend(b"PK\x06\x06");
+        zip.extend(44u64.to_le_bytes());
+        zip.extend([45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        zip.extend(1u64.to_le_bytes());
+        zip.extend(1u64.to_le_bytes());
+        zip.extend((directory.len() as u64).to_le_bytes());
+        zip.extend(directory_offset.to_le_bytes());
+        zip.extend(b"PK\x06\x07");
+        zip.extend([0u8; 4]);
+        zip.extend(record_offset.to_le_bytes());
+        zip.extend(1u32.to_le_bytes());
+        zip.extend(b"PK\x05\x06");
+        zip.extend([0u8; 6]);
+        zip.extend(u16::MAX.to_le_bytes());
+        zip.extend(u32::MAX.to_le_bytes());
+        zip.extend(u32::MAX.to_le_bytes());
+        zip.extend([0u8; 2]);
+
+        assert_eq!(
+            members(|mut visit| read_zip(Cursor::new(&zip), &mut visit)).unwrap(),
+            [("dir/photo.jpg".to_string(), "hello zip64".to_string())]
+        );
+    }
+
+    #[test]
+    fn truncated_archives_are_errors() {
+        let tar = pax_tar();
+        for len in [100, 1500, 1538, 2000] {
+            assert!(members(|mut visit| read_tar(&tar[..len], &mut visit)).is_err());
+        }
+        let zip = b"PK\x03\x04\x14\x00\x00\x00\x08\x00";
+        assert!(members(|mut visit| read_zip(Cursor::new(zip), &mut visit)).is_err());
+    }
+
+    #[test]
+    fn member_paths_are_split_after_the_archive() {
+        let is_archive = |path: &str| path == "/data/wow!/backup.zip";
+        assert_eq!(
+            split_archive_member_path("/data/wow!/backup.zip!/a.jpg", is_archive),
+            Some(("/data/wow!/backup.zip", "a.jpg"))
+        );
+        assert_eq!(
+            split_archive_member_path("/data/wow!/backup.zip!/hey!/a.jpg", is_archive),
+            Some(("/data/wow!/backup.zip", "hey!/a.jpg"))
+        );
+        assert_eq!(
+            split_archive_member_path("/data/wow!/a.jpg", is_archive),
+            None
+        );
+    }
+
+    #[test]
+    fn dos_times_are_converted_to_unix_times() {
+        // 2024-03-15 13:45:30
+        let date = (44 << 9) | (3 << 5) | 15;
+        let time = (13 << 11) | (45 << 5) | (30 / 2);
+        assert_eq!(dos_time_to_unix(date, time), 1_710_510_330);
+        // 1980-01-01 00:00:00, the earliest DOS time
+        assert_eq!(dos_time_to_unix(0x21, 0), 315_532_800);
+    }
+}
@@ -0,0 +1,124 @@
+//! Sharing the extents of identical files on copy-on-write filesystems like btrfs and XFS.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Bytes deduplicated per ioctl call, filesystems may cap larger requests
+const DEDUPE_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Let `dest` share the extents of the identical file `src` using the `FIDEDUPERANGE`
+/// ioctl, returning the number of bytes deduplicated.
+///
+/// The kernel compares the contents itself and never changes the data of either file.
+/// Errors describe why the files could not be deduplicated, e.g. because the
+/// filesystem does not support it.
+#[cfg(target_os = "linux")]
+pub fn dedupe_file(src: &Path, dest: &Path) -> io::Result<u64> {
+    use std::os::fd::AsRawFd;
+
+    let src_file = fs::File::open(src)?;
+    // deduplicating into a file requires write access to it, or owning it
+    let dest_file = fs::OpenOptions::new()
+        .write(true)
+        .open(dest)
+        .or_else(|_| fs::File::open(dest))?;
+    let file_len = src_file.metadata()?.len();
+    if dest_file.metadata()?.len() != file_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file sizes differ",
+        ));
+    }
+
+    let mut offset = 0;
+    while offset < file_len {
+        let length = DEDUPE_CHUNK_SIZE.min(file_len - offset);
+        let mut range = FileDedupeRange {
+            src_offset: offset,
+            src_length: length,
+            dest_count: 1,
+            reserved1: 0,
+            reserved2: 0,
+            info: [FileDedupeRangeInfo {
+                dest_fd: dest_file.as_raw_fd() as i64,
+                dest_offset: offset,
+                bytes_deduped: 0,
+                status: 0,
+                reserved: 0,
+            }],
+        };
+        // SAFETY: range matches struct file_dedupe_range with one trailing info entry
+        let result = unsafe { libc::ioctl(src_file.as_raw_fd(), FIDEDUPERANGE, &mut range) };
+        if result < 0 {
+            return Err(describe_error(io::Error::last_os_error()));
+        }
+        let info = &range.info[0];
+        if info.status == FILE_DEDUPE_RANGE_DIFFERS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "contents differ",
+            ));
+        }
+        if info.status < 0 {
+            return Err(describe_error(io::Error::from_raw_os_error(-info.status)));
+        }
+        if info.bytes_deduped == 0 {
+            return Err(io::Error::other(
+                "the filesystem did not deduplicate any bytes",
+            ));
+        }
+        offset += info.bytes_deduped;
+    }
+    Ok(file_len)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn dedupe_file(_src: &Path, _dest: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "deduplication is only supported on linux",
+    ))
+}
+
+/// `_IOWR(0x94, 54, struct file_dedupe_range)`
+#[cfg(target_os = "linux")]
+const FIDEDUPERANGE: libc::Ioctl = 0xC018_9436u32 as libc::Ioctl;
+#[cfg(target_os = "linux")]
+const FILE_DEDUPE_RANGE_DIFFERS: i32 = 1;
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct FileDedupeRange {
+    src_offset: u64,
+    src_length: u64,
+    dest_count: u16,
+    reserved1: u16,
+    reserved2: u32,
+    info: [FileDedupeRangeInfo; 1],
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct FileDedupeRangeInfo {
+    dest_fd: i64,
+    dest_offset: u64,
+    bytes_deduped: u64,
+    status: i32,
+    reserved: u32,
+}
+
+/// Explain the errors returned by filesystems that cannot deduplicate the files
+#[cfg(target_os = "linux")]
+fn describe_error(err: io::Error) -> io::Error {
+    let reason = match err.raw_os_error() {
+        Some(libc::EOPNOTSUPP) | Some(libc::ENOTTY) | Some(libc::EINVAL) => {
+            "the filesystem does not support deduplication"
+        }
+        Some(libc::EXDEV) => "the files are on different filesystems",
+        Some(libc::EPERM) | Some(libc::EACCES) => "no permission to deduplicate the files",
+        Some(libc::ETXTBSY) => "one of the files is being executed",
+        _ => return err,
+    };
+    io::Error::new(err.kind(), format!("{} ({})", reason, err))
+}
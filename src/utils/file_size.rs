@@ -0,0 +1,35 @@
+/// Format a byte count as a human readable size, e.g. `1.50 GiB`
+pub fn format_file_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+/// Parse a size like `500`, `64K`, `1.5G` or `2 GiB` into bytes, units are powers of 1024
+pub fn parse_file_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let number_len = size
+        .find(|x: char| !x.is_ascii_digit() && x != '.')
+        .unwrap_or(size.len());
+    let number: f64 = size[..number_len].parse().ok()?;
+    let unit = size[number_len..].trim().to_ascii_lowercase();
+    let exponent = match unit.trim_end_matches("ib").trim_end_matches('b') {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        _ => return None,
+    };
+    Some((number * 1024f64.powi(exponent)) as u64)
+}
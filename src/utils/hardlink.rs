@@ -0,0 +1,69 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Atomically replace `path` with a hard link to `target`.
+///
+/// The link is created under a temporary name next to `path` and renamed over it, so
+/// `path` never disappears. Refuses files on different devices, which cannot be linked.
+#[cfg(unix)]
+pub fn replace_with_hardlink(path: &Path, target: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::symlink_metadata(path)?;
+    let target_metadata = fs::metadata(target)?;
+    if metadata.dev() != target_metadata.dev() {
+        return Err(io::Error::new(
+            io::ErrorKind::CrossesDevices,
+            format!("{} is on a different device", target.display()),
+        ));
+    }
+    if metadata.ino() == target_metadata.ino() {
+        return Ok(());
+    }
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut i = 0;
+    let temp_path = loop {
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".fkr-link-{}-{}", std::process::id(), i));
+        let temp_path = path.with_file_name(temp_name);
+        match fs::hard_link(target, &temp_path) {
+            Ok(()) => break temp_path,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => i += 1,
+            Err(err) => return Err(err),
+        }
+    };
+
+    if let Err(err) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn replace_with_hardlink(_path: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "hard links are only supported on unix systems",
+    ))
+}
+
+/// Replace a hard link with an independent copy of the file it points to
+pub fn replace_with_copy(path: &Path) -> io::Result<()> {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".fkr-copy-{}", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    fs::copy(path, &temp_path)?;
+    if let Err(err) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    Ok(())
+}
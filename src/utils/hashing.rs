@@ -1,12 +1,139 @@
+use crate::error::FileKrakenError;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
 use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fmt::Formatter;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::str::FromStr;
 use std::{fs, io};
+use xxhash_rust::xxh3::Xxh3;
 
-pub fn hash_file(file_path: &str) -> String {
-    let mut hasher = Sha256::new();
-    let mut file =
-        fs::File::open(file_path).expect(&format!("Failed to open file {:?}", file_path));
-    let _bytes_written =
-        io::copy(&mut file, &mut hasher).expect(&format!("Failed to hash file {:?}", file_path));
+/// Number of bytes read from both the start and the end of a file for its partial hash
+pub const PARTIAL_HASH_SIZE: u64 = 4096;
 
-    format!("{:X}", hasher.finalize())
+/// Hash algorithms available for comparing file contents
+#[derive(PartialEq, Eq, Hash, Default, Debug, Clone, Copy)]
+pub enum HashAlgorithm {
+    /// Cryptographic, the algorithm used by older projects
+    #[default]
+    Sha256,
+    /// Cryptographic and much faster than SHA-256, suited for verification
+    Blake3,
+    /// Non-cryptographic 128 bit xxHash3, fastest for trusted local disks
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 3] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Blake3,
+        HashAlgorithm::Xxh3,
+    ];
+
+    pub fn hasher(&self) -> Box<dyn FileHasher> {
+        match self {
+            HashAlgorithm::Sha256 => Box::new(Sha256::new()),
+            HashAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+            HashAlgorithm::Xxh3 => Box::new(Xxh3Writer(Xxh3::new())),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                HashAlgorithm::Sha256 => "sha256",
+                HashAlgorithm::Blake3 => "blake3",
+                HashAlgorithm::Xxh3 => "xxh3",
+            }
+        )
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "xxh3" => Ok(HashAlgorithm::Xxh3),
+            x => Err(FileKrakenError::UnknownHashAlgorithm(x.to_string())),
+        }
+    }
+}
+
+impl FromSql for HashAlgorithm {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+/// Incremental hasher that file contents are copied into
+pub trait FileHasher: Write + Send {
+    /// Consume the hasher, returning the upper case hex digest
+    fn finalize_hex(self: Box<Self>) -> String;
+}
+
+impl FileHasher for Sha256 {
+    fn finalize_hex(self: Box<Self>) -> String {
+        format!("{:X}", self.finalize())
+    }
+}
+
+impl FileHasher for blake3::Hasher {
+    fn finalize_hex(self: Box<Self>) -> String {
+        self.finalize().to_hex().to_uppercase()
+    }
+}
+
+struct Xxh3Writer(Xxh3);
+
+impl Write for Xxh3Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FileHasher for Xxh3Writer {
+    fn finalize_hex(self: Box<Self>) -> String {
+        format!("{:032X}", self.0.digest128())
+    }
+}
+
+pub fn hash_file(file_path: &str, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut hasher = algorithm.hasher();
+    let mut file = fs::File::open(file_path)?;
+    let _bytes_written = io::copy(&mut file, &mut hasher)?;
+
+    Ok(hasher.finalize_hex())
+}
+
+/// Hash only the first and last [`PARTIAL_HASH_SIZE`] bytes of a file
+pub fn hash_file_partial(file_path: &str, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut hasher = algorithm.hasher();
+    let mut file = fs::File::open(file_path)?;
+    let file_len = file.metadata()?.len();
+
+    io::copy(&mut (&mut file).take(PARTIAL_HASH_SIZE), &mut hasher)?;
+    if file_len > PARTIAL_HASH_SIZE {
+        let tail_start = file_len
+            .saturating_sub(PARTIAL_HASH_SIZE)
+            .max(PARTIAL_HASH_SIZE);
+        file.seek(SeekFrom::Start(tail_start))?;
+        io::copy(&mut file.take(PARTIAL_HASH_SIZE), &mut hasher)?;
+    }
+
+    Ok(hasher.finalize_hex())
 }
@@ -0,0 +1,97 @@
+use crate::error::FileKrakenError;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::path::Path;
+
+/// A single compiled ignore pattern.
+///
+/// Supported syntax:
+/// - `re:<regex>` matches the regex against the full path
+/// - a trailing `/` (e.g. `.git/`) only matches directories
+/// - globs containing a `/` (e.g. `**/node_modules`) match the path relative to the location
+/// - any other glob (e.g. `*.tmp`) matches the file or directory name
+#[derive(Debug, Clone)]
+pub struct IgnorePattern {
+    matcher: PatternMatcher,
+    directories_only: bool,
+}
+
+#[derive(Debug, Clone)]
+enum PatternMatcher {
+    Regex(Regex),
+    Name(GlobMatcher),
+    RelativePath(GlobMatcher),
+}
+
+impl IgnorePattern {
+    pub fn new(pattern: &str) -> Result<Self, FileKrakenError> {
+        let invalid = |err: &dyn std::fmt::Display| {
+            FileKrakenError::InvalidIgnorePattern(format!("{}: {}", pattern, err))
+        };
+
+        if let Some(regex) = pattern.strip_prefix("re:") {
+            return Ok(IgnorePattern {
+                matcher: PatternMatcher::Regex(Regex::new(regex).map_err(|err| invalid(&err))?),
+                directories_only: false,
+            });
+        }
+
+        let directories_only = pattern.ends_with('/');
+        let glob = pattern.trim_end_matches('/');
+        if glob.is_empty() {
+            return Err(invalid(&"empty pattern"));
+        }
+        let matcher = GlobBuilder::new(glob.trim_start_matches('/'))
+            .literal_separator(true)
+            .build()
+            .map_err(|err| invalid(&err))?
+            .compile_matcher();
+        Ok(IgnorePattern {
+            matcher: if glob.contains('/') {
+                PatternMatcher::RelativePath(matcher)
+            } else {
+                PatternMatcher::Name(matcher)
+            },
+            directories_only,
+        })
+    }
+
+    pub fn is_match(&self, path: &Path, root: &Path, is_dir: bool) -> bool {
+        if self.directories_only && !is_dir {
+            return false;
+        }
+        match &self.matcher {
+            PatternMatcher::Regex(regex) => regex.is_match(&path.to_string_lossy()),
+            PatternMatcher::Name(glob) => path.file_name().is_some_and(|x| glob.is_match(x)),
+            PatternMatcher::RelativePath(glob) => path
+                .strip_prefix(root)
+                .is_ok_and(|relative| glob.is_match(relative)),
+        }
+    }
+}
+
+/// Ignore patterns of a project, each rooted at the location it was defined for
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreMatcher {
+    rules: Vec<(String, IgnorePattern)>,
+}
+
+impl IgnoreMatcher {
+    pub fn add(&mut self, root: &str, pattern: &str) -> Result<(), FileKrakenError> {
+        self.rules
+            .push((root.to_string(), IgnorePattern::new(pattern)?));
+        Ok(())
+    }
+
+    /// Whether a file or any of its parent directories is ignored
+    pub fn is_file_ignored(&self, path: &Path) -> bool {
+        self.is_ignored(path, false) || path.ancestors().skip(1).any(|x| self.is_ignored(x, true))
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.rules.iter().any(|(root, pattern)| {
+            let root = Path::new(root);
+            path.starts_with(root) && path != root && pattern.is_match(path, root, is_dir)
+        })
+    }
+}
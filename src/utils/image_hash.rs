@@ -0,0 +1,176 @@
+use crate::error::FileKrakenError;
+use crate::utils::magic::sniff_mime_type;
+use image::imageops::FilterType;
+use image::{imageops, GrayImage, ImageFormat, RgbImage};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use std::f64::consts::PI;
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::str::FromStr;
+use std::{fs, io};
+
+/// Perceptual hashes have 64 bits, similar images differ in few of them
+pub const IMAGE_HASH_BITS: u32 = 64;
+
+/// Differing bits up to which images count as similar unless the project sets otherwise
+pub const DEFAULT_SIMILAR_IMAGE_THRESHOLD: u32 = 10;
+
+/// Side length of the image the DCT of a pHash is calculated from
+const PHASH_SIZE: u32 = 32;
+
+/// Perceptual hash algorithms for finding resized and re-encoded copies of images
+#[derive(PartialEq, Eq, Hash, Default, Debug, Clone, Copy)]
+pub enum ImageHashAlgorithm {
+    /// Brighter or darker than the mean, fastest and the least robust
+    AHash,
+    /// Brighter or darker than the neighbouring pixel, robust against color changes
+    #[default]
+    DHash,
+    /// Low frequencies of the DCT, the most robust against re-encoding
+    PHash,
+}
+
+impl ImageHashAlgorithm {
+    pub const ALL: [ImageHashAlgorithm; 3] = [
+        ImageHashAlgorithm::AHash,
+        ImageHashAlgorithm::DHash,
+        ImageHashAlgorithm::PHash,
+    ];
+}
+
+impl fmt::Display for ImageHashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ImageHashAlgorithm::AHash => "ahash",
+                ImageHashAlgorithm::DHash => "dhash",
+                ImageHashAlgorithm::PHash => "phash",
+            }
+        )
+    }
+}
+
+impl FromStr for ImageHashAlgorithm {
+    type Err = FileKrakenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ahash" => Ok(ImageHashAlgorithm::AHash),
+            "dhash" => Ok(ImageHashAlgorithm::DHash),
+            "phash" => Ok(ImageHashAlgorithm::PHash),
+            _ => Err(FileKrakenError::UnknownImageHashAlgorithm(s.to_string())),
+        }
+    }
+}
+
+impl FromSql for ImageHashAlgorithm {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+/// Whether images of a detected MIME type can be decoded for perceptual hashing
+pub fn is_hashable_image(mime_type: &str) -> bool {
+    image_format(mime_type).is_some()
+}
+
+fn image_format(mime_type: &str) -> Option<ImageFormat> {
+    match mime_type {
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/png" => Some(ImageFormat::Png),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+/// Decode an image for hashing and thumbnails, detecting its format from the content
+pub fn read_image(path: &Path) -> io::Result<RgbImage> {
+    let data = fs::read(path)?;
+    let mime_type = sniff_mime_type(&data);
+    let format = image_format(mime_type).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("can't decode images of type {}", mime_type),
+        )
+    })?;
+    image::load_from_memory_with_format(&data, format)
+        .map(|x| x.to_rgb8())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Perceptual hash of an image as 16 hex digits
+pub fn image_hash(image: &RgbImage, algorithm: ImageHashAlgorithm) -> String {
+    let gray: GrayImage = imageops::grayscale(image);
+    let bits = match algorithm {
+        ImageHashAlgorithm::AHash => {
+            let small = imageops::resize(&gray, 8, 8, FilterType::Triangle);
+            let mean = small.pixels().map(|x| x.0[0] as u32).sum::<u32>() / 64;
+            hash_bits(small.pixels().map(|x| x.0[0] as u32 > mean))
+        }
+        ImageHashAlgorithm::DHash => {
+            let small = imageops::resize(&gray, 9, 8, FilterType::Triangle);
+            hash_bits(
+                (0..8)
+                    .flat_map(|y| (0..8).map(move |x| (x, y)))
+                    .map(|(x, y)| small.get_pixel(x, y).0[0] < small.get_pixel(x + 1, y).0[0]),
+            )
+        }
+        ImageHashAlgorithm::PHash => {
+            let small = imageops::resize(&gray, PHASH_SIZE, PHASH_SIZE, FilterType::Triangle);
+            let coefficients = low_frequencies(&small);
+            // the DC coefficient only tells the brightness
+            let mut sorted = coefficients[1..].to_vec();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let median = sorted[sorted.len() / 2];
+            hash_bits(coefficients.iter().map(|x| *x > median))
+        }
+    };
+    format!("{:016x}", bits)
+}
+
+fn hash_bits(bits: impl Iterator<Item = bool>) -> u64 {
+    bits.take(IMAGE_HASH_BITS as usize)
+        .fold(0, |hash, bit| (hash << 1) | bit as u64)
+}
+
+/// The 8x8 lowest frequencies of the two dimensional DCT-II of a square image
+fn low_frequencies(image: &GrayImage) -> Vec<f64> {
+    let size = image.width() as usize;
+    let cosines: Vec<Vec<f64>> = (0..8)
+        .map(|u| {
+            (0..size)
+                .map(|x| ((2 * x + 1) as f64 * u as f64 * PI / (2 * size) as f64).cos())
+                .collect()
+        })
+        .collect();
+    // rows first, then columns
+    let rows: Vec<Vec<f64>> = (0..size)
+        .map(|y| {
+            (0..8)
+                .map(|u| {
+                    (0..size)
+                        .map(|x| image.get_pixel(x as u32, y as u32).0[0] as f64 * cosines[u][x])
+                        .sum()
+                })
+                .collect()
+        })
+        .collect();
+    cosines
+        .iter()
+        .flat_map(|column_cosines| {
+            let rows = &rows;
+            (0..8).map(move |u| {
+                rows.iter()
+                    .zip(column_cosines)
+                    .map(|(row, cosine)| row[u] * cosine)
+                    .sum()
+            })
+        })
+        .collect()
+}
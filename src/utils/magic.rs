@@ -0,0 +1,218 @@
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+/// Number of bytes read from the start of a file to detect its type, enough for the tar
+/// header magic at offset 257
+pub const MAGIC_BYTES_LEN: usize = 512;
+
+/// Type of files whose content is not recognized
+pub const MIME_TYPE_UNKNOWN: &str = "application/octet-stream";
+/// Type of files that look like UTF-8 text
+pub const MIME_TYPE_TEXT: &str = "text/plain";
+/// Type of files without content
+pub const MIME_TYPE_EMPTY: &str = "application/x-empty";
+
+/// Detected MIME types and the extensions files of that type usually have
+const MIME_TYPE_EXTENSIONS: [(&str, &[&str]); 27] = [
+    (
+        "application/zip",
+        &[
+            "zip", "jar", "apk", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "cbz", "xpi",
+            "whl",
+        ],
+    ),
+    ("application/gzip", &["gz", "tgz"]),
+    ("application/x-xz", &["xz", "txz"]),
+    ("application/x-bzip2", &["bz2", "tbz2"]),
+    ("application/zstd", &["zst"]),
+    ("application/x-7z-compressed", &["7z"]),
+    ("application/vnd.rar", &["rar", "cbr"]),
+    ("application/x-tar", &["tar"]),
+    ("application/pdf", &["pdf"]),
+    ("application/x-sqlite3", &["sqlite", "sqlite3", "fkrproj"]),
+    ("application/x-executable", &["so", "o"]),
+    ("application/x-msdownload", &["exe", "dll", "sys"]),
+    ("image/jpeg", &["jpg", "jpeg", "jpe", "jfif"]),
+    ("image/png", &["png"]),
+    ("image/gif", &["gif"]),
+    ("image/webp", &["webp"]),
+    ("image/tiff", &["tif", "tiff", "dng", "nef", "cr2", "arw"]),
+    ("image/heic", &["heic", "heif"]),
+    ("video/mp4", &["mp4", "m4v", "3gp"]),
+    ("video/quicktime", &["mov", "qt"]),
+    ("video/x-matroska", &["mkv", "mka", "webm"]),
+    ("video/x-msvideo", &["avi"]),
+    ("audio/mp4", &["m4a", "m4b"]),
+    ("audio/mpeg", &["mp3"]),
+    ("audio/flac", &["flac"]),
+    ("audio/ogg", &["ogg", "oga", "ogv", "opus"]),
+    ("audio/wav", &["wav"]),
+];
+
+/// Detect the MIME type of a file from its first bytes
+pub fn read_mime_type(path: &Path) -> io::Result<&'static str> {
+    let mut buffer = Vec::with_capacity(MAGIC_BYTES_LEN);
+    File::open(path)?
+        .take(MAGIC_BYTES_LEN as u64)
+        .read_to_end(&mut buffer)?;
+    Ok(sniff_mime_type(&buffer))
+}
+
+/// Detect the MIME type of content from its first bytes, at most [`MAGIC_BYTES_LEN`] are
+/// looked at
+pub fn sniff_mime_type(bytes: &[u8]) -> &'static str {
+    let at = |offset: usize, magic: &[u8]| bytes.get(offset..offset + magic.len()) == Some(magic);
+
+    if bytes.is_empty() {
+        MIME_TYPE_EMPTY
+    } else if at(0, b"PK\x03\x04") || at(0, b"PK\x05\x06") || at(0, b"PK\x07\x08") {
+        "application/zip"
+    } else if at(0, b"\x1f\x8b") {
+        "application/gzip"
+    } else if at(0, b"\xfd7zXZ\x00") {
+        "application/x-xz"
+    } else if at(0, b"BZh") {
+        "application/x-bzip2"
+    } else if at(0, b"\x28\xb5\x2f\xfd") {
+        "application/zstd"
+    } else if at(0, b"7z\xbc\xaf\x27\x1c") {
+        "application/x-7z-compressed"
+    } else if at(0, b"Rar!\x1a\x07") {
+        "application/vnd.rar"
+    } else if at(257, b"ustar") {
+        "application/x-tar"
+    } else if at(0, b"%PDF-") {
+        "application/pdf"
+    } else if at(0, b"SQLite format 3\x00") {
+        "application/x-sqlite3"
+    } else if at(0, b"\x7fELF") {
+        "application/x-executable"
+    } else if at(0, b"MZ") {
+        "application/x-msdownload"
+    } else if at(0, b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if at(0, b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        "image/gif"
+    } else if at(0, b"II*\x00") || at(0, b"MM\x00*") {
+        "image/tiff"
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        "image/webp"
+    } else if at(0, b"RIFF") && at(8, b"AVI ") {
+        "video/x-msvideo"
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        "audio/wav"
+    } else if at(4, b"ftyp") {
+        // ISO base media files tell their flavour in the major brand
+        match bytes.get(8..12) {
+            Some(b"qt  ") => "video/quicktime",
+            Some(b"heic" | b"heix" | b"mif1" | b"msf1") => "image/heic",
+            Some(b"M4A " | b"M4B ") => "audio/mp4",
+            _ => "video/mp4",
+        }
+    } else if [b"moov", b"mdat", b"wide", b"pnot"]
+        .iter()
+        .any(|x| at(4, *x))
+    {
+        // QuickTime movies written before the ftyp atom existed
+        "video/quicktime"
+    } else if at(0, b"\x1a\x45\xdf\xa3") {
+        "video/x-matroska"
+    } else if at(0, b"ID3") || is_mpeg_audio_frame(bytes) {
+        "audio/mpeg"
+    } else if at(0, b"fLaC") {
+        "audio/flac"
+    } else if at(0, b"OggS") {
+        "audio/ogg"
+    } else if is_text(bytes) {
+        MIME_TYPE_TEXT
+    } else {
+        MIME_TYPE_UNKNOWN
+    }
+}
+
+/// Header of an MPEG audio frame: 11 sync bits, then a version, layer, bitrate and sample
+/// rate that are not reserved, which rules out AAC streams with their layer of 0
+fn is_mpeg_audio_frame(bytes: &[u8]) -> bool {
+    let [0xff, flags, rates, ..] = *bytes else {
+        return false;
+    };
+    flags & 0xe0 == 0xe0
+        && (flags >> 3) & 0x3 != 0x1
+        && (flags >> 1) & 0x3 != 0x0
+        && rates >> 4 != 0xf
+        && (rates >> 2) & 0x3 != 0x3
+}
+
+/// UTF-8 without NUL bytes, a character may be cut off at the end
+fn is_text(bytes: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => return false,
+    };
+    !bytes[..valid].contains(&0)
+}
+
+/// The MIME type usually stored with a file extension without the dot, if it is detectable
+pub fn mime_type_of_extension(extension: &str) -> Option<&'static str> {
+    let extension = extension.to_ascii_lowercase();
+    MIME_TYPE_EXTENSIONS
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        .map(|(mime_type, _)| *mime_type)
+}
+
+/// Whether the content of a file contradicts its extension without the dot.
+///
+/// Only extensions of detectable types are checked, as the content of e.g. a `.txt` file
+/// may be anything. Empty files never disagree.
+pub fn is_extension_mismatch(extension: &str, mime_type: &str) -> bool {
+    let extension = extension.to_ascii_lowercase();
+    if mime_type == MIME_TYPE_EMPTY || mime_type_of_extension(&extension).is_none() {
+        return false;
+    }
+    !MIME_TYPE_EXTENSIONS
+        .iter()
+        .any(|(x, extensions)| *x == mime_type && extensions.contains(&extension.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mpeg_audio_frames_of_all_versions_and_layers_are_detected() {
+        // MPEG-1, MPEG-2 and MPEG-2.5 of layer III, II and I, with and without CRC
+        for flags in [0xfb, 0xfa, 0xf3, 0xf2, 0xe3, 0xe2, 0xfd, 0xfc, 0xff, 0xf5] {
+            assert_eq!(sniff_mime_type(&[0xff, flags, 0x90, 0x64]), "audio/mpeg");
+        }
+        assert_eq!(sniff_mime_type(b"ID3\x04\x00"), "audio/mpeg");
+        // AAC streams have the same sync bits, but a layer of 0
+        assert_ne!(sniff_mime_type(&[0xff, 0xf1, 0x50, 0x80]), "audio/mpeg");
+        assert_ne!(sniff_mime_type(&[0xff, 0xfb, 0xf0, 0x64]), "audio/mpeg");
+        assert!(!is_extension_mismatch(
+            "mp3",
+            sniff_mime_type(&[0xff, 0xfa, 0x90, 0x64])
+        ));
+    }
+
+    #[test]
+    fn quicktime_movies_without_ftyp_atom_are_detected() {
+        for atom in [b"moov", b"mdat", b"wide", b"pnot"] {
+            let movie = [&[0, 0, 0, 8][..], atom, b"\x00\x00\x00\x00"].concat();
+            assert_eq!(sniff_mime_type(&movie), "video/quicktime");
+        }
+        assert_eq!(
+            sniff_mime_type(b"\x00\x00\x00\x14ftypqt  \x00\x00\x02\x00"),
+            "video/quicktime"
+        );
+        assert!(!is_extension_mismatch(
+            "mov",
+            sniff_mime_type(b"\x00\x00\x00\x08wide\x00\x00\x00\x00")
+        ));
+    }
+}
@@ -1,6 +1,17 @@
-pub mod dialogs;
+pub mod archive;
+pub mod dedupe;
+mod file_size;
+pub mod hardlink;
 pub mod hashing;
+pub mod ignore_patterns;
+pub mod image_hash;
+pub mod magic;
+mod move_file;
 mod parent_path;
-pub mod ui_elements;
+mod timestamp;
+pub mod trash;
 
-pub use parent_path::get_longest_parent_path;
+pub use file_size::{format_file_size, parse_file_size};
+pub use move_file::move_file;
+pub use parent_path::{get_longest_parent_path, is_path_parent};
+pub use timestamp::format_timestamp;
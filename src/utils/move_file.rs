@@ -0,0 +1,32 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Move a file, creating the parent directories of the destination.
+///
+/// Never overwrites an existing file. Moves across devices fall back to copying the
+/// file, keeping its modification time, and removing the original.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match fs::rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            let modified = fs::metadata(from)?.modified()?;
+            fs::copy(from, to)?;
+            fs::File::options()
+                .write(true)
+                .open(to)?
+                .set_modified(modified)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
@@ -26,7 +26,7 @@ pub fn get_longest_parent_path<'a>(
             file_parent_location = location.path.clone();
         }
     });
-    if file_parent_location.len() > 0 {
+    if !file_parent_location.is_empty() {
         Some(file_parent_location)
     } else {
         None
@@ -0,0 +1,213 @@
+//! Moving files into the trash as described by the freedesktop.org trash specification.
+
+use crate::utils::move_file;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Location of a file after it was moved into a trash directory
+#[derive(Debug, Clone)]
+pub struct TrashedFile {
+    /// The file itself, inside the `files` directory of the trash
+    pub path: PathBuf,
+    /// Its `.trashinfo` file, inside the `info` directory of the trash
+    pub info_path: PathBuf,
+}
+
+/// Move a file into the trash of the user.
+///
+/// Files on the same device as the home trash (`$XDG_DATA_HOME/Trash`) are moved there,
+/// files on other volumes into `$topdir/.Trash/$uid` or `$topdir/.Trash-$uid` of the
+/// volume, so they never have to be copied.
+#[cfg(unix)]
+pub fn move_to_trash(path: &Path) -> io::Result<TrashedFile> {
+    use std::os::unix::fs::MetadataExt;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let device = fs::symlink_metadata(path)?.dev();
+    let (trash_dir, top_dir) = find_trash_dir(path, device)?;
+
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    create_private_dir(&files_dir)?;
+    create_private_dir(&info_dir)?;
+
+    // the .trashinfo file is created exclusively to reserve the name in the trash
+    let original_path = match &top_dir {
+        Some(top_dir) => path.strip_prefix(top_dir).unwrap_or(path),
+        None => path,
+    };
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(original_path),
+        deletion_date()
+    );
+    let mut i = 1;
+    let (trashed_path, info_path) = loop {
+        let mut name = file_name.to_os_string();
+        if i > 1 {
+            name.push(format!(".{}", i));
+        }
+        let trashed_path = files_dir.join(&name);
+        name.push(".trashinfo");
+        let info_path = info_dir.join(&name);
+        i += 1;
+
+        if trashed_path.symlink_metadata().is_ok() {
+            continue;
+        }
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(mut info_file) => {
+                io::Write::write_all(&mut info_file, info.as_bytes())?;
+                break (trashed_path, info_path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    };
+
+    if let Err(err) = fs::rename(path, &trashed_path) {
+        let _ = fs::remove_file(&info_path);
+        return Err(err);
+    }
+    Ok(TrashedFile {
+        path: trashed_path,
+        info_path,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn move_to_trash(_path: &Path) -> io::Result<TrashedFile> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the trash is only supported on unix systems",
+    ))
+}
+
+/// Move a trashed file back to its original path and remove its `.trashinfo` file
+pub fn restore_from_trash(trashed_file: &TrashedFile, original_path: &Path) -> io::Result<()> {
+    move_file(&trashed_file.path, original_path)?;
+    match fs::remove_file(&trashed_file.info_path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Pick the trash directory for a file on `device`, along with the top directory of the
+/// volume if it is not the home trash
+#[cfg(unix)]
+fn find_trash_dir(path: &Path, device: u64) -> io::Result<(PathBuf, Option<PathBuf>)> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let home_trash = home_trash_dir()?;
+    let home_trash_device = home_trash
+        .ancestors()
+        .find_map(|x| fs::metadata(x).ok())
+        .map(|x| x.dev());
+    if home_trash_device == Some(device) {
+        return Ok((home_trash, None));
+    }
+
+    let top_dir = path
+        .ancestors()
+        .skip(1)
+        .take_while(|x| fs::metadata(x).is_ok_and(|x| x.dev() == device))
+        .last()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no trash directory found"))?
+        .to_path_buf();
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+
+    // an administrator provided $topdir/.Trash must be a real directory with the sticky bit
+    let shared_trash = top_dir.join(".Trash");
+    if fs::symlink_metadata(&shared_trash)
+        .is_ok_and(|x| x.is_dir() && x.permissions().mode() & 0o1000 != 0)
+    {
+        let trash_dir = shared_trash.join(uid.to_string());
+        if create_private_dir(&trash_dir).is_ok() {
+            return Ok((trash_dir, Some(top_dir)));
+        }
+    }
+
+    let trash_dir = top_dir.join(format!(".Trash-{}", uid));
+    create_private_dir(&trash_dir)?;
+    let metadata = fs::symlink_metadata(&trash_dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} is not a trash directory of this user",
+                trash_dir.display()
+            ),
+        ));
+    }
+    Ok((trash_dir, Some(top_dir)))
+}
+
+#[cfg(unix)]
+fn home_trash_dir() -> io::Result<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|x| Path::new(x).is_absolute())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|x| Path::new(&x).join(".local/share")))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    Ok(data_home.join("Trash"))
+}
+
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    match fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)
+    {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Percent-encode a path for the `Path` key of a `.trashinfo` file
+#[cfg(unix)]
+fn encode_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Current local time as `YYYY-MM-DDThh:mm:ss`
+#[cfg(unix)]
+fn deletion_date() -> String {
+    // SAFETY: localtime_r only writes to the provided tm struct
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}